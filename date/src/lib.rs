@@ -19,16 +19,133 @@ impl Date {
                 .expect("FAKE_NOW is invalid"),
         }
     }
-    pub fn from_str<T: AsRef<str>>(value: T) -> Result<Self, DateError> {
-        Self::parse_from_str(value.as_ref(), Self::DEFAULT_FORMAT)
-    }
     pub fn parse_from_str(value: &str, format: &str) -> Result<Self, DateError> {
         let inner = chrono::NaiveDate::parse_from_str(value, format)
             .map_err(|_| DateError::ParseError(value.to_string()))?;
         Ok(Self { inner })
     }
-    pub fn to_string(&self) -> String {
-        self.inner.format(Self::DEFAULT_FORMAT).to_string()
+    // Feeds in the wild do not agree on a date format, so this tries the ones we meet.
+    // The date is taken as written in the value, the offset is not applied.
+    //
+    // - RFC 2822 (RSS pubDate), including obsolete zones like `GMT` or `PDT` and missing seconds
+    // - RFC 3339 / W3CDTF (Atom, JSON Feed, dc:date), including date only values like `2024-06-22`
+    pub fn parse_feed_date(value: &str) -> Result<Self, DateError> {
+        let value = value.trim();
+        if let Ok(datetime) = chrono::DateTime::parse_from_rfc2822(value) {
+            return Ok(Self {
+                inner: datetime.naive_local().date(),
+            });
+        }
+        if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+            return Ok(Self {
+                inner: datetime.naive_local().date(),
+            });
+        }
+        Self::parse_w3cdtf(value)
+            .or_else(|| Self::parse_loose_rfc2822(value))
+            .ok_or_else(|| DateError::ParseError(value.to_string()))
+    }
+    // seconds since the unix epoch, like the `time` of Hacker News items, taken in UTC
+    pub fn from_unix_timestamp(seconds: i64) -> Result<Self, DateError> {
+        let datetime = chrono::DateTime::from_timestamp(seconds, 0)
+            .ok_or_else(|| DateError::ParseError(seconds.to_string()))?;
+        Ok(Self {
+            inner: datetime.date_naive(),
+        })
+    }
+    // `YYYY-MM-DD` followed by an optional time part, or `YYYY-MM`
+    fn parse_w3cdtf(value: &str) -> Option<Self> {
+        let (date, rest) = value.split_at_checked(10).unwrap_or((value, ""));
+        if !rest.is_empty() && !rest.starts_with(['T', ' ']) {
+            return None;
+        }
+        let inner = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .or_else(|_| chrono::NaiveDate::parse_from_str(&format!("{}-01", date), "%Y-%m-%d"))
+            .ok()?;
+        Some(Self { inner })
+    }
+    // `[Day,] DD Mon YYYY ...` with whatever time and zone follows,
+    // e.g. `UTC` or `JST` zones which RFC 2822 does not know, or a weekday that does not match
+    fn parse_loose_rfc2822(value: &str) -> Option<Self> {
+        let mut tokens = value.split_whitespace().peekable();
+        if tokens.peek()?.ends_with(',') {
+            tokens.next();
+        }
+        let date = tokens.take(3).collect::<Vec<_>>().join(" ");
+        let inner = chrono::NaiveDate::parse_from_str(&date, "%d %b %Y").ok()?;
+        Some(Self { inner })
+    }
+}
+impl std::str::FromStr for Date {
+    type Err = DateError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse_from_str(value, Self::DEFAULT_FORMAT)
+    }
+}
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.inner.format(Self::DEFAULT_FORMAT))
+    }
+}
+
+impl Date {
+    // the first and the last second of the day, for comparing with a `DateTime`
+    pub fn start_of_day(&self) -> DateTime {
+        DateTime {
+            inner: self.inner.and_time(chrono::NaiveTime::MIN),
+        }
+    }
+    pub fn end_of_day(&self) -> DateTime {
+        DateTime {
+            inner: self
+                .inner
+                .and_hms_opt(23, 59, 59)
+                .expect("23:59:59 is a valid time"),
+        }
+    }
+}
+
+pub const FAKE_NOW_TIME: &str = "2024-10-12T09:30:00";
+// A moment in local time, to the second.
+// Shown as `2024-10-12T09:30:00`, which sorts in time order as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    inner: chrono::NaiveDateTime,
+}
+impl DateTime {
+    const DEFAULT_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S";
+    #[cfg(not(test))]
+    pub fn now() -> Self {
+        use chrono::Timelike;
+        let now = chrono::Local::now().naive_local();
+        Self {
+            inner: now.with_nanosecond(0).unwrap_or(now),
+        }
+    }
+    #[cfg(test)]
+    pub fn now() -> Self {
+        Self {
+            inner: chrono::NaiveDateTime::parse_from_str(FAKE_NOW_TIME, Self::DEFAULT_FORMAT)
+                .expect("FAKE_NOW_TIME is invalid"),
+        }
+    }
+    pub fn date(&self) -> Date {
+        Date {
+            inner: self.inner.date(),
+        }
+    }
+}
+impl std::str::FromStr for DateTime {
+    type Err = DateError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let inner = chrono::NaiveDateTime::parse_from_str(value, Self::DEFAULT_FORMAT)
+            .map_err(|_| DateError::ParseError(value.to_string()))?;
+        Ok(Self { inner })
+    }
+}
+impl std::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.inner.format(Self::DEFAULT_FORMAT))
     }
 }
 
@@ -39,6 +156,7 @@ pub enum DateError {
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
 
     use super::*;
     #[test]
@@ -54,4 +172,60 @@ mod tests {
         let date = Date::from_str(s).unwrap();
         assert_eq!(date.to_string(), s);
     }
+    #[test]
+    fn date_time_to_string() {
+        let s = "2024-06-22T02:22:32";
+        let date_time = DateTime::from_str(s).unwrap();
+        assert_eq!(date_time.to_string(), s);
+        assert_eq!(
+            date_time.date(),
+            Date::from_str("2024-06-22:00:00:00").unwrap()
+        );
+    }
+    #[test]
+    fn day_contains_date_time() {
+        let date = Date::from_str("2024-06-22:00:00:00").unwrap();
+        let date_time = DateTime::from_str("2024-06-22T02:22:32").unwrap();
+        assert!(date.start_of_day() <= date_time && date_time <= date.end_of_day());
+        assert_eq!(date.end_of_day().to_string(), "2024-06-22T23:59:59");
+    }
+    #[test]
+    fn parse_feed_date() {
+        let expected = Date::from_str("2024-06-22:00:00:00").unwrap();
+        for value in [
+            "Sat, 22 Jun 2024 02:22:32 +0000",
+            "Sat, 22 Jun 2024 02:22:32 GMT",
+            "Sat, 22 Jun 2024 02:22:32 PDT",
+            "Sat, 22 Jun 2024 02:22 +0000",
+            "22 Jun 2024 02:22:32 +0000",
+            "Sat, 22 Jun 2024 02:22:32 UTC",
+            "Fri, 22 Jun 2024 02:22:32 JST",
+            "2024-06-22T02:22:32Z",
+            "2024-06-22T02:22:32.123+09:00",
+            "2024-06-22T02:22+09:00",
+            "2024-06-22",
+            " 2024-06-22 02:22:32 ",
+        ] {
+            assert_eq!(Date::parse_feed_date(value).unwrap(), expected, "{}", value);
+        }
+    }
+    #[test]
+    fn parse_feed_date_keeps_date_as_written() {
+        let date = Date::parse_feed_date("2024-06-22T23:00:00-05:00").unwrap();
+        assert_eq!(date, Date::from_str("2024-06-22:00:00:00").unwrap());
+    }
+    #[test]
+    fn from_unix_timestamp() {
+        assert_eq!(
+            Date::from_unix_timestamp(1719022952).unwrap(),
+            Date::from_str("2024-06-22:00:00:00").unwrap()
+        );
+        assert!(Date::from_unix_timestamp(i64::MAX).is_err());
+    }
+    #[test]
+    fn parse_invalid_feed_date() {
+        for value in ["", "yesterday", "2024-13-01", "32 Jun 2024 02:22:32 GMT"] {
+            assert!(Date::parse_feed_date(value).is_err(), "{}", value);
+        }
+    }
 }
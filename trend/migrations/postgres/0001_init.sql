@@ -0,0 +1,14 @@
+CREATE TABLE trend_info (
+    id UUID PRIMARY KEY,
+    user_id VARCHAR(255) NOT NULL,
+    link TEXT NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    description TEXT NOT NULL DEFAULT '',
+    memo TEXT NOT NULL DEFAULT '',
+    service VARCHAR(255) NOT NULL,
+    status VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    UNIQUE (user_id, link)
+);
+CREATE INDEX trend_info_user_id_created_at ON trend_info (user_id, created_at DESC);
@@ -0,0 +1,11 @@
+CREATE TABLE trend_history (
+    id BIGSERIAL PRIMARY KEY,
+    trend_id UUID NOT NULL REFERENCES trend_info (id) ON DELETE CASCADE,
+    event VARCHAR(255) NOT NULL,
+    from_status VARCHAR(255),
+    to_status VARCHAR(255),
+    memo TEXT,
+    changed_at TIMESTAMP NOT NULL
+);
+CREATE INDEX trend_history_trend_id ON trend_history (trend_id, changed_at);
+CREATE INDEX trend_history_changed_at ON trend_history (changed_at);
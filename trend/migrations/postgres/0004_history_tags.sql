@@ -0,0 +1,2 @@
+-- the tags after a `tags_changed` event, joined by `,`
+ALTER TABLE trend_history ADD COLUMN tags TEXT;
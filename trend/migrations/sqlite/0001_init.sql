@@ -0,0 +1,15 @@
+-- dates are kept as the text of `Date`, which sorts in time order
+CREATE TABLE trend_info (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    link TEXT NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    description TEXT NOT NULL DEFAULT '',
+    memo TEXT NOT NULL DEFAULT '',
+    service TEXT NOT NULL,
+    status TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (user_id, link)
+);
+CREATE INDEX trend_info_user_id_created_at ON trend_info (user_id, created_at DESC);
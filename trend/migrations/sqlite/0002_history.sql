@@ -0,0 +1,12 @@
+-- changed_at is kept as the text of `DateTime`, which sorts in time order
+CREATE TABLE trend_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    trend_id TEXT NOT NULL REFERENCES trend_info (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    from_status TEXT,
+    to_status TEXT,
+    memo TEXT,
+    changed_at TEXT NOT NULL
+);
+CREATE INDEX trend_history_trend_id ON trend_history (trend_id, changed_at);
+CREATE INDEX trend_history_changed_at ON trend_history (changed_at);
@@ -0,0 +1,6 @@
+CREATE TABLE trend_tag (
+    trend_id TEXT NOT NULL REFERENCES trend_info (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (trend_id, tag)
+);
+CREATE INDEX trend_tag_tag ON trend_tag (tag);
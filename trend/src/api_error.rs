@@ -0,0 +1,322 @@
+use std::fmt::Display;
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::{
+    domain::{StatusError, UserTrendInfoError},
+    raw::{
+        aggregate::AggregateRawTrendCollectorError, atom::RemoteAtomRawTrendCollectorError,
+        feed::RemoteFeedRawTrendCollectorError, rss::RemoteRssRawTrendCollectorError,
+    },
+    repository::TrendInfoEntityError,
+    source::{
+        repository::TrendSourceRepositoryError, use_case::AddTrendSourceError, TrendSourceError,
+    },
+    use_case::{TrendRequestError, UserTrendInfoRepositoryError},
+};
+
+// An error answered by the server, as `{"code": ..., "message": ...}`.
+// Clients may match on `code`, it is kept stable; `message` is for humans and may change.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Display) -> Self {
+        Self {
+            status,
+            code,
+            message: message.to_string(),
+        }
+    }
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+    pub fn code(&self) -> &str {
+        self.code
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    pub fn unauthorized() -> Self {
+        Self::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "a valid bearer token is required",
+        )
+    }
+}
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+impl std::error::Error for ApiError {}
+
+#[derive(serde::Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            code: self.code,
+            message: &self.message,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<UserTrendInfoRepositoryError> for ApiError {
+    fn from(e: UserTrendInfoRepositoryError) -> Self {
+        let (status, code) = match e {
+            UserTrendInfoRepositoryError::AlreadyExists(_) => {
+                (StatusCode::CONFLICT, "trend_already_exists")
+            }
+            UserTrendInfoRepositoryError::NotFoundError(_) => {
+                (StatusCode::NOT_FOUND, "trend_not_found")
+            }
+            // a stored row which is no longer valid, not the fault of the client
+            UserTrendInfoRepositoryError::ConvertError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "corrupt_trend")
+            }
+            UserTrendInfoRepositoryError::SaveError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "storage_error")
+            }
+        };
+        Self::new(status, code, e)
+    }
+}
+impl From<TrendInfoEntityError> for ApiError {
+    fn from(e: TrendInfoEntityError) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "corrupt_trend", e)
+    }
+}
+impl From<StatusError> for ApiError {
+    fn from(e: StatusError) -> Self {
+        match e {
+            StatusError::InvalidStatus(_) => {
+                Self::new(StatusCode::BAD_REQUEST, "invalid_status", e)
+            }
+            // well formed, but not allowed by the domain, e.g. moving back to New
+            StatusError::InvalidStatusChange(_, _) => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_status_change", e)
+            }
+        }
+    }
+}
+impl From<UserTrendInfoError> for ApiError {
+    fn from(e: UserTrendInfoError) -> Self {
+        match e {
+            UserTrendInfoError::InvalidStatus(e) => Self::from(e),
+            UserTrendInfoError::InvalidTag(_) => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_tag", e)
+            }
+            // the memo is too long
+            e => Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_memo", e),
+        }
+    }
+}
+impl From<TrendRequestError> for ApiError {
+    fn from(e: TrendRequestError) -> Self {
+        let code = match e {
+            TrendRequestError::InvalidStatus(_) => "invalid_status",
+            TrendRequestError::InvalidTag(_) => "invalid_tag",
+            TrendRequestError::InvalidDate(_) => "invalid_date",
+            TrendRequestError::InvalidOrder(_) => "invalid_order",
+            TrendRequestError::InvalidCursor(_) => "invalid_cursor",
+            TrendRequestError::InvalidChange(e) => return Self::from(e),
+        };
+        Self::new(StatusCode::BAD_REQUEST, code, e)
+    }
+}
+
+impl From<TrendSourceError> for ApiError {
+    fn from(e: TrendSourceError) -> Self {
+        let (status, code) = match e {
+            TrendSourceError::EmptyName => (StatusCode::BAD_REQUEST, "empty_source_name"),
+            TrendSourceError::InvalidUrl(_) => (StatusCode::BAD_REQUEST, "invalid_source_url"),
+            TrendSourceError::ForbiddenHost(_) => {
+                (StatusCode::BAD_REQUEST, "forbidden_source_host")
+            }
+            TrendSourceError::InvalidFormat(_) => {
+                (StatusCode::BAD_REQUEST, "invalid_source_format")
+            }
+            TrendSourceError::InvalidInterval(_) => {
+                (StatusCode::BAD_REQUEST, "invalid_source_interval")
+            }
+            TrendSourceError::DuplicateName(_) => (StatusCode::CONFLICT, "source_already_exists"),
+            TrendSourceError::NotFound(_) => (StatusCode::NOT_FOUND, "source_not_found"),
+            TrendSourceError::UnsupportedFilter(_) => {
+                (StatusCode::BAD_REQUEST, "unsupported_source_filter")
+            }
+        };
+        Self::new(status, code, e)
+    }
+}
+impl From<TrendSourceRepositoryError> for ApiError {
+    fn from(e: TrendSourceRepositoryError) -> Self {
+        let (status, code) = match e {
+            TrendSourceRepositoryError::AlreadyExists(_) => {
+                (StatusCode::CONFLICT, "source_already_exists")
+            }
+            TrendSourceRepositoryError::NotFoundError(_) => {
+                (StatusCode::NOT_FOUND, "source_not_found")
+            }
+            TrendSourceRepositoryError::ConvertError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "corrupt_source")
+            }
+            TrendSourceRepositoryError::SaveError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "storage_error")
+            }
+        };
+        Self::new(status, code, e)
+    }
+}
+impl From<AddTrendSourceError> for ApiError {
+    fn from(e: AddTrendSourceError) -> Self {
+        match e {
+            AddTrendSourceError::UnreadableFeed(_) => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, "unreadable_feed", e)
+            }
+            AddTrendSourceError::RepositoryError(e) => Self::from(e),
+        }
+    }
+}
+
+// A feed we could not reach or read is a failure of the upstream, not of the server.
+impl From<RemoteRssRawTrendCollectorError> for ApiError {
+    fn from(e: RemoteRssRawTrendCollectorError) -> Self {
+        let code = match e {
+            RemoteRssRawTrendCollectorError::RequestError(_) => "feed_unavailable",
+            RemoteRssRawTrendCollectorError::RssError(_) => "unreadable_feed",
+        };
+        Self::new(StatusCode::BAD_GATEWAY, code, e)
+    }
+}
+impl From<RemoteAtomRawTrendCollectorError> for ApiError {
+    fn from(e: RemoteAtomRawTrendCollectorError) -> Self {
+        let code = match e {
+            RemoteAtomRawTrendCollectorError::RequestError(_) => "feed_unavailable",
+            RemoteAtomRawTrendCollectorError::AtomError(_) => "unreadable_feed",
+        };
+        Self::new(StatusCode::BAD_GATEWAY, code, e)
+    }
+}
+impl From<RemoteFeedRawTrendCollectorError> for ApiError {
+    fn from(e: RemoteFeedRawTrendCollectorError) -> Self {
+        let code = match e {
+            RemoteFeedRawTrendCollectorError::RequestError(_) => "feed_unavailable",
+            RemoteFeedRawTrendCollectorError::FeedError(_) => "unreadable_feed",
+        };
+        Self::new(StatusCode::BAD_GATEWAY, code, e)
+    }
+}
+impl From<AggregateRawTrendCollectorError> for ApiError {
+    fn from(e: AggregateRawTrendCollectorError) -> Self {
+        let (status, code) = match e {
+            AggregateRawTrendCollectorError::DuplicateName(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "duplicate_source")
+            }
+            AggregateRawTrendCollectorError::AllFailed(_) => {
+                (StatusCode::BAD_GATEWAY, "all_sources_failed")
+            }
+        };
+        Self::new(status, code, e)
+    }
+}
+
+// requests axum could not read at all
+impl From<JsonRejection> for ApiError {
+    fn from(e: JsonRejection) -> Self {
+        Self::new(e.status(), "invalid_body", e.body_text())
+    }
+}
+impl From<QueryRejection> for ApiError {
+    fn from(e: QueryRejection) -> Self {
+        Self::new(e.status(), "invalid_query", e.body_text())
+    }
+}
+impl From<PathRejection> for ApiError {
+    fn from(e: PathRejection) -> Self {
+        Self::new(e.status(), "invalid_path", e.body_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        domain::{Status, UserTrendInfo, UserTrendInfoId},
+        raw::{aggregate::SourceCollectionError, RawTrendInfo, Service},
+        workflow::Workflow,
+    };
+    use date::Date;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn error_is_answered_as_json_with_code() {
+        let e = ApiError::from(UserTrendInfoRepositoryError::NotFoundError(
+            "id-1".to_string(),
+        ));
+
+        let response = e.into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "trend_not_found");
+        assert_eq!(body["message"], "NotFoundError: id-1");
+    }
+    #[test]
+    fn domain_errors_are_unprocessable() {
+        let mut user_trend = UserTrendInfo::new(
+            UserTrendInfoId::new("id-1"),
+            RawTrendInfo::new("title", "link", "desc", Service::aws_updates(), Date::now()),
+        );
+        user_trend
+            .change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+
+        let e = ApiError::from(
+            user_trend
+                .change_status(Status::NEW, &Workflow::default())
+                .unwrap_err(),
+        );
+        assert_eq!(e.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(e.code(), "invalid_status_change");
+
+        let e = ApiError::from(user_trend.change_memo("a".repeat(501)).unwrap_err());
+        assert_eq!(e.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(e.code(), "invalid_memo");
+    }
+    #[test]
+    fn malformed_requests_are_bad_requests() {
+        let e = ApiError::from(TrendRequestError::InvalidCursor("x".to_string()));
+
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(e.code(), "invalid_cursor");
+    }
+    #[test]
+    fn failed_feeds_are_bad_gateway() {
+        let e = ApiError::from(AggregateRawTrendCollectorError::AllFailed(vec![(
+            "aws".to_string(),
+            SourceCollectionError::Timeout(Duration::from_secs(30)),
+        )]));
+
+        assert_eq!(e.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(e.code(), "all_sources_failed");
+    }
+}
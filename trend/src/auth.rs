@@ -0,0 +1,160 @@
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use sha2::{Digest, Sha256};
+use user::UserId;
+
+// Who may call the api, by their bearer token.
+// Only the SHA-256 of each token is configured, so the file does not hold the tokens themselves.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    // hex of the SHA-256 of the token
+    users: HashMap<String, UserId>,
+}
+impl TokenRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // config file is chosen by its extension, `.toml` or `.json`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TokenRegistryError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(TokenRegistryError::IoError)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(TokenRegistryError::UnknownExtension(
+                path.display().to_string(),
+            )),
+        }
+    }
+    pub fn from_toml(content: &str) -> Result<Self, TokenRegistryError> {
+        let config: UsersConfig = toml::from_str(content).map_err(TokenRegistryError::TomlError)?;
+        config.try_into()
+    }
+    pub fn from_json(content: &str) -> Result<Self, TokenRegistryError> {
+        let config: UsersConfig =
+            serde_json::from_str(content).map_err(TokenRegistryError::JsonError)?;
+        config.try_into()
+    }
+    pub fn add(&mut self, user_id: UserId, token_sha256: &str) -> Result<(), TokenRegistryError> {
+        let hash = token_sha256.to_ascii_lowercase();
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(TokenRegistryError::InvalidHash(user_id.to_string()));
+        }
+        if self.users.contains_key(&hash) {
+            return Err(TokenRegistryError::DuplicateToken(user_id.to_string()));
+        }
+        self.users.insert(hash, user_id);
+        Ok(())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+    // the user of the token, `None` for an unknown token
+    pub fn authenticate(&self, token: &str) -> Option<UserId> {
+        self.users.get(&hash_token(token)).cloned()
+    }
+}
+
+// hex of the SHA-256 of the token, as written in the config,
+// e.g. `printf %s "$TOKEN" | sha256sum`
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum TokenRegistryError {
+    IoError(std::io::Error),
+    TomlError(toml::de::Error),
+    JsonError(serde_json::Error),
+    UnknownExtension(String),
+    // token_sha256 of the user is not 64 hex digits
+    InvalidHash(String),
+    DuplicateToken(String),
+}
+impl Display for TokenRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenRegistryError::IoError(e) => write!(f, "IoError: {}", e),
+            TokenRegistryError::TomlError(e) => write!(f, "TomlError: {}", e),
+            TokenRegistryError::JsonError(e) => write!(f, "JsonError: {}", e),
+            TokenRegistryError::UnknownExtension(s) => write!(f, "UnknownExtension: {}", s),
+            TokenRegistryError::InvalidHash(s) => write!(f, "InvalidHash: {}", s),
+            TokenRegistryError::DuplicateToken(s) => write!(f, "DuplicateToken: {}", s),
+        }
+    }
+}
+impl std::error::Error for TokenRegistryError {}
+
+#[derive(serde::Deserialize)]
+struct UsersConfig {
+    #[serde(default)]
+    users: Vec<UserConfig>,
+}
+#[derive(serde::Deserialize)]
+struct UserConfig {
+    id: String,
+    token_sha256: String,
+}
+impl TryFrom<UsersConfig> for TokenRegistry {
+    type Error = TokenRegistryError;
+    fn try_from(config: UsersConfig) -> Result<Self, Self::Error> {
+        let mut registry = TokenRegistry::new();
+        for user in config.users {
+            registry.add(UserId::new(user.id), &user.token_sha256)?;
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authenticate_users_by_their_token() {
+        let toml = format!(
+            r#"
+[[users]]
+id = "alice"
+token_sha256 = "{}"
+
+[[users]]
+id = "bob"
+token_sha256 = "{}"
+"#,
+            hash_token("alice-secret"),
+            hash_token("bob-secret").to_uppercase()
+        );
+        let registry = TokenRegistry::from_toml(&toml).unwrap();
+
+        assert_eq!(
+            registry.authenticate("alice-secret").unwrap().to_string(),
+            "alice"
+        );
+        assert_eq!(
+            registry.authenticate("bob-secret").unwrap().to_string(),
+            "bob"
+        );
+        assert!(registry.authenticate("alice").is_none());
+        assert!(registry.authenticate("").is_none());
+    }
+    #[test]
+    fn token_hash_is_sha256_hex() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+    #[test]
+    fn user_with_invalid_hash_should_fail() {
+        let json = r#"{ "users": [{ "id": "alice", "token_sha256": "secret" }] }"#;
+
+        assert!(matches!(
+            TokenRegistry::from_json(json),
+            Err(TokenRegistryError::InvalidHash(_))
+        ));
+    }
+}
@@ -1,22 +1,35 @@
-use date::Date;
+use date::{Date, DateTime};
 
-use crate::raw::RawTrendInfo;
-use std::fmt::Display;
+use crate::{raw::RawTrendInfo, workflow::Workflow};
+use std::{borrow::Cow, collections::BTreeSet, fmt::Display, str::FromStr};
 
+// A trend read by `list` or `search` of a repository has an empty history,
+// only `get` reads the stored one, so a list of trends does not read the history of each.
+// Changes made to it are still recorded and stored by `update`.
 #[derive(Debug, Clone)]
 pub struct UserTrendInfo {
     id: UserTrendInfoId,
     raw_info: RawTrendInfo,
     memo: Memo,
     status: Status,
+    tags: BTreeSet<Tag>,
+    summary: Option<Summary>,
+    // oldest first, the first `recorded` entries are already stored
+    history: Vec<TrendHistoryEntry>,
+    recorded: usize,
 }
 impl UserTrendInfo {
+    const MAX_TAGS: usize = 10;
     pub fn new(id: UserTrendInfoId, raw_info: RawTrendInfo) -> Self {
         Self {
             id,
             raw_info,
             memo: Memo::new(),
-            status: Status::New,
+            status: Status::NEW,
+            tags: BTreeSet::new(),
+            summary: None,
+            history: vec![],
+            recorded: 0,
         }
     }
     pub fn id(&self) -> &UserTrendInfoId {
@@ -40,21 +53,108 @@ impl UserTrendInfo {
     pub fn created_at(&self) -> &Date {
         self.raw_info.created_at()
     }
-    pub fn change_status(&mut self, new_status: Status) -> Result<(), UserTrendInfoError> {
-        self.status = self
-            .status
-            .change_status(new_status)
-            .map(|s| s)
-            .map_err(|e| UserTrendInfoError::InvalidStatus(e))?;
+    // the workflow decides which statuses exist and which changes are allowed
+    pub fn change_status(
+        &mut self,
+        new_status: Status,
+        workflow: &Workflow,
+    ) -> Result<(), UserTrendInfoError> {
+        workflow
+            .change(&self.status, &new_status)
+            .map_err(UserTrendInfoError::InvalidStatus)?;
+        if self.status != new_status {
+            let old_status = std::mem::replace(&mut self.status, new_status);
+            self.record(TrendEvent::StatusChanged {
+                from: old_status,
+                to: self.status.clone(),
+            });
+        }
         Ok(())
     }
     pub fn status(&self) -> Status {
-        self.status
+        self.status.clone()
+    }
+    // the status as stored, not a change
+    pub(crate) fn restore_status(&mut self, status: Status) {
+        self.status = status;
     }
     pub fn change_memo(&mut self, new_memo: String) -> Result<(), UserTrendInfoError> {
+        if self.memo.0 == new_memo {
+            return Ok(());
+        }
         self.memo
             .change_memo(new_memo)
-            .map_err(|e| UserTrendInfoError::InvalidMemo(e))
+            .map_err(UserTrendInfoError::InvalidMemo)?;
+        self.record(TrendEvent::MemoChanged(self.memo.0.clone()));
+        Ok(())
+    }
+    // sorted by name
+    pub fn tags(&self) -> impl Iterator<Item = &Tag> {
+        self.tags.iter()
+    }
+    pub fn has_tag(&self, tag: &Tag) -> bool {
+        self.tags.contains(tag)
+    }
+    // adding a tag the trend already has is not an error
+    pub fn add_tag(&mut self, tag: &str) -> Result<(), UserTrendInfoError> {
+        self.change_tags(None, Some(tag))
+    }
+    // removing a tag the trend does not have is not an error
+    pub fn remove_tag(&mut self, tag: &str) -> Result<(), UserTrendInfoError> {
+        self.change_tags(Some(tag), None)
+    }
+    // Removes and then adds tags as one change, so a swap of tags is one entry of the history.
+    // Nothing is changed when a tag is invalid.
+    pub fn change_tags<'a>(
+        &mut self,
+        remove: impl IntoIterator<Item = &'a str>,
+        add: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), UserTrendInfoError> {
+        let mut tags = self.tags.clone();
+        for tag in remove {
+            tags.remove(&Tag::from_str(tag).map_err(UserTrendInfoError::InvalidTag)?);
+        }
+        for tag in add {
+            tags.insert(Tag::from_str(tag).map_err(UserTrendInfoError::InvalidTag)?);
+        }
+        if tags.len() > Self::MAX_TAGS {
+            return Err(UserTrendInfoError::InvalidTag(TagError::TooMany(
+                Self::MAX_TAGS,
+            )));
+        }
+        if tags != self.tags {
+            self.tags = tags;
+            self.record(TrendEvent::TagsChanged(self.tags.iter().cloned().collect()));
+        }
+        Ok(())
+    }
+    // None until a summarizer has been run on the trend
+    pub fn summary(&self) -> Option<&Summary> {
+        self.summary.as_ref()
+    }
+    pub fn set_summary(&mut self, summary: Summary) {
+        self.summary = Some(summary);
+    }
+    // changes of status, memo and tags, oldest first
+    // only trends read by `get` of a repository come with the stored history
+    pub fn history(&self) -> &[TrendHistoryEntry] {
+        &self.history
+    }
+    fn record(&mut self, event: TrendEvent) {
+        self.history.push(TrendHistoryEntry {
+            at: DateTime::now(),
+            event,
+        });
+    }
+    // changes made since the trend was read, for the repository to store
+    pub(crate) fn unrecorded_history(&self) -> &[TrendHistoryEntry] {
+        &self.history[self.recorded..]
+    }
+    // replaces the history by the stored one
+    pub(crate) fn with_history(mut self, history: Vec<TrendHistoryEntry>) -> Self {
+        self.recorded = history.len();
+        self.history = history;
+        self
     }
 }
 
@@ -64,12 +164,14 @@ pub enum UserTrendInfoError {
     InvalidMemo(MemoError),
     #[allow(private_interfaces)]
     InvalidStatus(StatusError),
+    InvalidTag(TagError),
 }
 impl Display for UserTrendInfoError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            UserTrendInfoError::InvalidMemo(e) => write!(f, "InvalidMemo: {}", e.to_string()),
-            UserTrendInfoError::InvalidStatus(e) => write!(f, "InvalidStatus: {}", e.to_string()),
+            UserTrendInfoError::InvalidMemo(e) => write!(f, "InvalidMemo: {}", e),
+            UserTrendInfoError::InvalidStatus(e) => write!(f, "InvalidStatus: {}", e),
+            UserTrendInfoError::InvalidTag(e) => write!(f, "InvalidTag: {}", e),
         }
     }
 }
@@ -77,6 +179,14 @@ impl std::error::Error for UserTrendInfoError {}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UserTrendInfoId(pub(super) String);
+impl UserTrendInfoId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Memo(String);
@@ -107,40 +217,94 @@ impl Display for MemoError {
 }
 impl std::error::Error for MemoError {}
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
-pub enum Status {
-    New,
-    Reading,
-    ToDo,
-    Done,
+// A label to group trends by topic, e.g. `lambda` or `security`.
+// Tags ignore case, so they are kept in lowercase.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tag(String);
+impl Tag {
+    const MAX_LEN: usize = 30;
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
-impl Status {
-    pub fn from_str(s: &str) -> Result<Self, StatusError> {
-        match s {
-            "New" => Ok(Status::New),
-            "Reading" => Ok(Status::Reading),
-            "ToDo" => Ok(Status::ToDo),
-            "Done" => Ok(Status::Done),
-            _ => Err(StatusError::InvalidStatus(s.to_string())),
+impl FromStr for Tag {
+    type Err = TagError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tag = s.trim().to_lowercase();
+        if tag.is_empty() {
+            return Err(TagError::Empty);
+        }
+        let len = tag.chars().count();
+        if len > Self::MAX_LEN {
+            return Err(TagError::TooLong(len));
+        }
+        // letters, digits, `-` and `_`, so a list of tags can be separated by `,`
+        if let Some(c) = tag
+            .chars()
+            .find(|c| !(c.is_alphanumeric() || *c == '-' || *c == '_'))
+        {
+            return Err(TagError::InvalidChar(c));
         }
+        Ok(Self(tag))
     }
-    fn change_status(self, to: Self) -> Result<Self, StatusError> {
-        match (self, to) {
-            (old, new) if old == new => Ok(old),
-            // New to other is always allowed
-            (Status::New, s) => Ok(s),
-            // Some status to New is always denied
-            (s, Status::New) => Err(StatusError::InvalidStatusChange(s, Status::New)),
-            _ => Ok(to),
+}
+#[derive(Debug)]
+pub enum TagError {
+    Empty,
+    TooLong(usize),
+    InvalidChar(char),
+    // the most tags a trend can have
+    TooMany(usize),
+}
+impl Display for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagError::Empty => write!(f, "empty"),
+            TagError::TooLong(len) => write!(f, "too long: {}", len),
+            TagError::InvalidChar(c) => write!(f, "invalid char: {:?}", c),
+            TagError::TooMany(max) => write!(f, "too many: at most {}", max),
         }
     }
+}
+impl std::error::Error for TagError {}
+
+// The gist of a trend, made from its title and description by a `TrendSummarizer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary(String);
+impl Summary {
+    pub fn new(summary: impl Into<String>) -> Self {
+        Self(summary.into())
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// A status by its name, which statuses exist is up to the `Workflow`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Status(Cow<'static, str>);
+impl Status {
+    // every trend starts as New
+    pub const NEW: Status = Status(Cow::Borrowed("New"));
+    pub const READING: Status = Status(Cow::Borrowed("Reading"));
+    pub const TO_DO: Status = Status(Cow::Borrowed("ToDo"));
+    pub const DONE: Status = Status(Cow::Borrowed("Done"));
     pub fn to_str(&self) -> &str {
-        match self {
-            Status::New => "New",
-            Status::Reading => "Reading",
-            Status::ToDo => "ToDo",
-            Status::Done => "Done",
+        &self.0
+    }
+}
+// any name is a status, whether the workflow has it is checked on changing to it
+impl FromStr for Status {
+    type Err = StatusError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(StatusError::InvalidStatus(s.to_string()));
         }
+        Ok(Self(Cow::Owned(s.to_string())))
     }
 }
 #[derive(Debug)]
@@ -166,13 +330,146 @@ impl Display for StatusError {
 
 impl std::error::Error for StatusError {}
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrendEvent {
+    StatusChanged { from: Status, to: Status },
+    // the memo after the change
+    MemoChanged(String),
+    // the tags after the change, sorted by name
+    TagsChanged(Vec<Tag>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrendHistoryEntry {
+    at: DateTime,
+    event: TrendEvent,
+}
+impl TrendHistoryEntry {
+    pub(crate) fn new(at: DateTime, event: TrendEvent) -> Self {
+        Self { at, event }
+    }
+    pub fn at(&self) -> DateTime {
+        self.at
+    }
+    pub fn event(&self) -> &TrendEvent {
+        &self.event
+    }
+}
+
+// an entry of the history with the trend it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrendHistoryRecord {
+    trend_id: UserTrendInfoId,
+    entry: TrendHistoryEntry,
+}
+impl TrendHistoryRecord {
+    pub fn new(trend_id: UserTrendInfoId, entry: TrendHistoryEntry) -> Self {
+        Self { trend_id, entry }
+    }
+    pub fn trend_id(&self) -> &UserTrendInfoId {
+        &self.trend_id
+    }
+    pub fn entry(&self) -> &TrendHistoryEntry {
+        &self.entry
+    }
+}
+
+// a saved trend as the api shows it
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct UserTrend {
+    id: String,
+    title: String,
+    link: String,
+    desc: String,
+    // omitted when the trend has not been summarized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    service: String,
+    memo: String,
+    status: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    created_at: String,
+}
+impl From<UserTrendInfo> for UserTrend {
+    fn from(user_trend: UserTrendInfo) -> Self {
+        Self {
+            id: user_trend.id.0.clone(),
+            title: user_trend.title().to_string(),
+            link: user_trend.link().to_string(),
+            desc: user_trend.raw_info.desc().to_string(),
+            summary: user_trend
+                .summary()
+                .filter(|summary| !summary.is_empty())
+                .map(|summary| summary.as_str().to_string()),
+            service: user_trend.from().to_string(),
+            memo: user_trend.memo().to_string(),
+            status: user_trend.status().to_str().to_string(),
+            tags: user_trend
+                .tags()
+                .map(|tag| tag.as_str().to_string())
+                .collect(),
+            created_at: user_trend.created_at().to_string(),
+        }
+    }
+}
+
+// an entry of the history as the api shows it
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TrendHistory {
+    trend_id: String,
+    at: String,
+    // `status_changed`, `memo_changed` or `tags_changed`
+    event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<String>>,
+}
+impl From<TrendHistoryRecord> for TrendHistory {
+    fn from(record: TrendHistoryRecord) -> Self {
+        let mut history = Self {
+            trend_id: record.trend_id.0,
+            at: record.entry.at.to_string(),
+            event: String::new(),
+            from: None,
+            to: None,
+            memo: None,
+            tags: None,
+        };
+        match record.entry.event {
+            TrendEvent::StatusChanged { from, to } => {
+                history.event = "status_changed".to_string();
+                history.from = Some(from.to_str().to_string());
+                history.to = Some(to.to_str().to_string());
+            }
+            TrendEvent::MemoChanged(memo) => {
+                history.event = "memo_changed".to_string();
+                history.memo = Some(memo);
+            }
+            TrendEvent::TagsChanged(tags) => {
+                history.event = "tags_changed".to_string();
+                history.tags = Some(tags.into_iter().map(|tag| tag.0).collect());
+            }
+        }
+        history
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use date::Date;
 
     use crate::{
-        domain::{Status, UserTrendInfo, UserTrendInfoId},
+        domain::{
+            Status, TagError, TrendEvent, UserTrendInfo, UserTrendInfoError, UserTrendInfoId,
+        },
         raw::{RawTrendInfo, Service},
+        workflow::Workflow,
     };
 
     #[test]
@@ -205,10 +502,133 @@ mod tests {
         let raw_info =
             RawTrendInfo::new("title", "link", "desc", Service::aws_updates(), Date::now());
         let mut info = UserTrendInfo::new(id, raw_info);
-        let new_status = Status::Reading;
+        let new_status = Status::READING;
 
-        info.change_status(new_status).unwrap();
+        info.change_status(new_status.clone(), &Workflow::default())
+            .unwrap();
 
         assert_eq!(info.status, new_status);
     }
+    #[test]
+    fn changes_are_recorded_in_history() {
+        let id = UserTrendInfoId("id".to_string());
+        let raw_info =
+            RawTrendInfo::new("title", "link", "desc", Service::aws_updates(), Date::now());
+        let mut info = UserTrendInfo::new(id, raw_info);
+
+        info.change_status(Status::READING, &Workflow::default())
+            .unwrap();
+        info.change_memo("good".to_string()).unwrap();
+        // neither is a change
+        info.change_status(Status::READING, &Workflow::default())
+            .unwrap();
+        info.change_memo("good".to_string()).unwrap();
+        // a denied change is not recorded
+        info.change_status(Status::NEW, &Workflow::default())
+            .unwrap_err();
+
+        let events: Vec<_> = info.history().iter().map(|e| e.event().clone()).collect();
+        assert_eq!(
+            events,
+            vec![
+                TrendEvent::StatusChanged {
+                    from: Status::NEW,
+                    to: Status::READING
+                },
+                TrendEvent::MemoChanged("good".to_string()),
+            ]
+        );
+        assert_eq!(info.unrecorded_history().len(), 2);
+    }
+    #[test]
+    fn stored_history_is_not_unrecorded() {
+        let id = UserTrendInfoId("id".to_string());
+        let raw_info =
+            RawTrendInfo::new("title", "link", "desc", Service::aws_updates(), Date::now());
+        let mut info = UserTrendInfo::new(id, raw_info);
+        info.change_status(Status::TO_DO, &Workflow::default())
+            .unwrap();
+        let stored = info.history().to_vec();
+
+        let mut info = info.with_history(stored);
+        info.change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+
+        assert_eq!(info.history().len(), 2);
+        assert_eq!(
+            info.unrecorded_history()[0].event(),
+            &TrendEvent::StatusChanged {
+                from: Status::TO_DO,
+                to: Status::DONE
+            }
+        );
+    }
+    #[test]
+    fn tags_are_validated_and_kept_once() {
+        let id = UserTrendInfoId("id".to_string());
+        let raw_info =
+            RawTrendInfo::new("title", "link", "desc", Service::aws_updates(), Date::now());
+        let mut info = UserTrendInfo::new(id, raw_info);
+
+        info.add_tag("Security").unwrap();
+        info.add_tag(" lambda ").unwrap();
+        info.add_tag("security").unwrap();
+        assert!(matches!(
+            info.add_tag("cost saving"),
+            Err(UserTrendInfoError::InvalidTag(TagError::InvalidChar(' ')))
+        ));
+        assert!(matches!(
+            info.add_tag(""),
+            Err(UserTrendInfoError::InvalidTag(TagError::Empty))
+        ));
+
+        let tags: Vec<_> = info.tags().map(|tag| tag.as_str()).collect();
+        assert_eq!(tags, vec!["lambda", "security"]);
+
+        info.remove_tag("LAMBDA").unwrap();
+        info.remove_tag("unknown").unwrap();
+        let tags: Vec<_> = info.tags().map(|tag| tag.as_str()).collect();
+        assert_eq!(tags, vec!["security"]);
+    }
+    #[test]
+    fn changes_of_tags_are_recorded_once_per_change() {
+        let id = UserTrendInfoId("id".to_string());
+        let raw_info =
+            RawTrendInfo::new("title", "link", "desc", Service::aws_updates(), Date::now());
+        let mut info = UserTrendInfo::new(id, raw_info);
+
+        info.change_tags(None, ["lambda", "cost"]).unwrap();
+        info.change_tags(["cost"], ["security"]).unwrap();
+        // neither is a change
+        info.change_tags(["unknown"], ["lambda"]).unwrap();
+        // nothing is changed by an invalid tag
+        info.change_tags(["lambda"], ["cost saving"]).unwrap_err();
+
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.parse().unwrap()).collect();
+        let events: Vec<_> = info.history().iter().map(|e| e.event().clone()).collect();
+        assert_eq!(
+            events,
+            vec![
+                TrendEvent::TagsChanged(tags(&["cost", "lambda"])),
+                TrendEvent::TagsChanged(tags(&["lambda", "security"])),
+            ]
+        );
+    }
+    #[test]
+    fn trend_has_limited_tags() {
+        let id = UserTrendInfoId("id".to_string());
+        let raw_info =
+            RawTrendInfo::new("title", "link", "desc", Service::aws_updates(), Date::now());
+        let mut info = UserTrendInfo::new(id, raw_info);
+        for i in 0..UserTrendInfo::MAX_TAGS {
+            info.add_tag(&format!("tag{}", i)).unwrap();
+        }
+
+        assert!(matches!(
+            info.add_tag("one-more"),
+            Err(UserTrendInfoError::InvalidTag(TagError::TooMany(_)))
+        ));
+        // already there
+        info.add_tag("tag0").unwrap();
+    }
 }
@@ -1,7 +1,13 @@
+pub mod api_error;
+pub mod auth;
 pub mod domain;
 pub mod raw;
 pub mod repository;
+pub mod scheduler;
+pub mod source;
+pub mod summary;
 pub mod use_case;
+pub mod workflow;
 
 #[cfg(test)]
 mod tests {
@@ -19,7 +25,7 @@ mod tests {
     //        Date::parse_from_str("2021-01-01", "%Y-%m-%d").unwrap(),
     //    );
     //    let memo = "so, interesting!";
-    //    let status = Status::ToDo;
+    //    let status = Status::TO_DO;
     //    let save_info = SaveUserTrendInfoBuilder::new(user_id)
     //        .memo(memo)
     //        .status(status)
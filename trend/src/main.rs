@@ -1,13 +1,286 @@
-use std::env;
+use std::{env, sync::Arc, time::Duration};
 
-use axum::{routing::get, Json, Router};
-use trend::raw::{rss::RemoteRssRawTrendCollector, RawTrendCollector, Trend};
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        FromRequestParts, Path, Query, State,
+    },
+    http::{header, request::Parts, StatusCode},
+    routing::{delete, get},
+    Json, Router,
+};
+use trend::{
+    api_error::ApiError,
+    auth::TokenRegistry,
+    domain::{TrendHistory, TrendHistoryRecord, UserTrend, UserTrendInfo, UserTrendInfoId},
+    raw::{
+        aggregate::{AggregateRawTrendCollector, AggregatedRawTrends},
+        conditional::{CollectionCache, FetchValidatorStore, FetchValidators},
+        x::XRawTrendCollector,
+        AnyRawTrendCollector, CollectedRawTrends, CollectionReport, Trend,
+    },
+    repository::AnyUserTrendInfoRepository,
+    scheduler::TrendCollectionScheduler,
+    source::{
+        repository::JsonFileTrendSourceRepository,
+        use_case::{add_trend_source, delete_trend_source, list_trend_sources},
+        Source, TrendSource, TrendSourceConfig, TrendSourceRegistry,
+    },
+    summary::{
+        cache::SummaryCache, extractive::ExtractiveSummarizer, llm::LlmSummarizer,
+        summarize_trends, AnySummarizer, FallbackSummarizer,
+    },
+    use_case::{
+        delete_trend, get_trend, save_new_trend, search_trends, trend_history, update_trend,
+        NewTrend, TrendChange, TrendHistoryParams, TrendList, TrendQueryParams,
+    },
+    workflow::{WorkflowConfig, Workflows},
+};
+use user::UserId;
 
-async fn new() -> Json<Vec<Trend>> {
+#[derive(Clone)]
+struct AppState {
+    tokens: Arc<TokenRegistry>,
+    registry: Arc<TrendSourceRegistry>,
+    // last collections of the registry for /new
+    collections: Arc<CollectionCache>,
+    sources: Arc<JsonFileTrendSourceRepository>,
+    trends: Arc<AnyUserTrendInfoRepository>,
+    workflows: Arc<Workflows>,
+    summarizer: Arc<AnySummarizer>,
+}
+
+// requests are made on behalf of the user whose token is given by `Authorization: Bearer <token>`
+struct CurrentUser(UserId);
+#[async_trait]
+impl FromRequestParts<AppState> for CurrentUser {
+    type Rejection = ApiError;
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| state.tokens.authenticate(token.trim()))
+            .map(CurrentUser)
+            .ok_or_else(ApiError::unauthorized)
+    }
+}
+
+// fails only when no source could be collected, see /new/diagnostics for each source
+async fn new(State(state): State<AppState>) -> Result<Json<Vec<Trend>>, ApiError> {
     println!("called new");
-    let aws = RemoteRssRawTrendCollector::aws_updates();
-    let infos = aws.collect().await.unwrap();
-    Json(<Vec<Trend>>::from(infos))
+    let trends = collect_all(&state).await.into_result()?;
+    Ok(Json(<Vec<Trend>>::from(trends)))
+}
+
+async fn new_diagnostics(State(state): State<AppState>) -> Json<CollectionReport> {
+    println!("called new_diagnostics");
+    let aggregated = collect_all(&state).await;
+    Json(CollectionReport::from(&aggregated))
+}
+
+// feeds are fetched conditionally with the validators of their last collection,
+// which is used again when they are not modified
+async fn collect_all(state: &AppState) -> AggregatedRawTrends {
+    let mut collectors = vec![];
+    for source in state.registry.sources() {
+        let collector = match state.collections.validators(source.name()).await {
+            Some(validators) => source.collector().with_validators(validators),
+            None => source.collector(),
+        };
+        collectors.push((source.name().to_string(), collector));
+    }
+    let mut results = vec![];
+    // names of the registry are unique, see TrendSourceRegistry::add
+    for (name, result) in AggregateRawTrendCollector::new(collectors)
+        .expect("source names are unique")
+        .collect_separately()
+        .await
+    {
+        let result = match result {
+            Ok(collected) => Ok(state.collections.resolve(&name, collected).await),
+            Err(e) => Err(e),
+        };
+        results.push((name, result));
+    }
+    let aggregated = AggregatedRawTrends::from_results(results);
+    for (name, e) in aggregated.failures() {
+        println!("failed to collect {}: {}", name, e);
+    }
+    log_skipped(aggregated.trends());
+    aggregated
+}
+
+fn log_skipped(infos: &CollectedRawTrends) {
+    if infos.is_all_skipped() {
+        println!("no usable items, the feed format may have changed");
+    }
+    if !infos.skipped().is_empty() {
+        println!("skipped {} items", infos.skipped().len());
+        for skipped in infos.skipped() {
+            println!("  {}", skipped);
+        }
+    }
+}
+
+// trends are shown with the gist of their description
+async fn summarize(state: &AppState, user_trends: &mut [UserTrendInfo]) {
+    for (id, e) in summarize_trends(state.summarizer.as_ref(), user_trends).await {
+        println!("failed to summarize {}: {}", id.as_str(), e);
+    }
+}
+
+async fn list_sources(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+) -> Result<Json<Vec<Source>>, ApiError> {
+    println!("called list_sources");
+    let sources = list_trend_sources(state.sources.as_ref(), user_id).await?;
+    Ok(Json(sources.into_iter().map(Source::from).collect()))
+}
+
+async fn add_source(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    config: Result<Json<TrendSourceConfig>, JsonRejection>,
+) -> Result<(StatusCode, Json<Source>), ApiError> {
+    println!("called add_source");
+    let Json(config) = config?;
+    let source = TrendSource::try_from(config)?;
+    source.check_host().await?;
+    let collector = source.collector();
+    let source = add_trend_source(state.sources.as_ref(), &collector, user_id, source).await?;
+    Ok((StatusCode::CREATED, Json(Source::from(source))))
+}
+
+async fn delete_source(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    Path(name): Path<String>,
+) -> Result<Json<Source>, ApiError> {
+    println!("called delete_source");
+    let source = delete_trend_source(state.sources.as_ref(), user_id, &name).await?;
+    Ok(Json(Source::from(source)))
+}
+
+async fn list_trends(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    params: Result<Query<TrendQueryParams>, QueryRejection>,
+) -> Result<Json<TrendList>, ApiError> {
+    println!("called list_trends");
+    let Query(params) = params?;
+    let workflow = state.workflows.of(&user_id);
+    let query = params.into_query(user_id, workflow)?;
+    let mut page = search_trends(state.trends.as_ref(), &query).await?;
+    summarize(&state, page.trends_mut()).await;
+    Ok(Json(TrendList::from(page)))
+}
+
+async fn add_trend(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    new_trend: Result<Json<NewTrend>, JsonRejection>,
+) -> Result<(StatusCode, Json<UserTrend>), ApiError> {
+    println!("called add_trend");
+    let Json(new_trend) = new_trend?;
+    let workflow = state.workflows.of(&user_id);
+    let save_info = new_trend.into_save_info(user_id, workflow)?;
+    let mut user_trend = save_new_trend(state.trends.as_ref(), save_info).await?;
+    summarize(&state, std::slice::from_mut(&mut user_trend)).await;
+    Ok((StatusCode::CREATED, Json(UserTrend::from(user_trend))))
+}
+
+async fn show_trend(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    Path(id): Path<String>,
+) -> Result<Json<UserTrend>, ApiError> {
+    println!("called show_trend");
+    let mut user_trend =
+        get_trend(state.trends.as_ref(), user_id, &UserTrendInfoId::new(id)).await?;
+    summarize(&state, std::slice::from_mut(&mut user_trend)).await;
+    Ok(Json(UserTrend::from(user_trend)))
+}
+
+async fn change_trend(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    Path(id): Path<String>,
+    change: Result<Json<TrendChange>, JsonRejection>,
+) -> Result<Json<UserTrend>, ApiError> {
+    println!("called change_trend");
+    let workflow = state.workflows.of(&user_id);
+    let mut user_trend = get_trend(
+        state.trends.as_ref(),
+        user_id.clone(),
+        &UserTrendInfoId::new(id),
+    )
+    .await?;
+    let Json(change) = change?;
+    change.apply(&mut user_trend, workflow)?;
+    let mut user_trend = update_trend(state.trends.as_ref(), user_id, user_trend).await?;
+    summarize(&state, std::slice::from_mut(&mut user_trend)).await;
+    Ok(Json(UserTrend::from(user_trend)))
+}
+
+async fn remove_trend(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    Path(id): Path<String>,
+) -> Result<Json<UserTrend>, ApiError> {
+    println!("called remove_trend");
+    let user_trend =
+        delete_trend(state.trends.as_ref(), user_id, &UserTrendInfoId::new(id)).await?;
+    Ok(Json(UserTrend::from(user_trend)))
+}
+
+// changes of a trend, oldest first
+async fn show_trend_history(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TrendHistory>>, ApiError> {
+    println!("called show_trend_history");
+    let user_trend = get_trend(state.trends.as_ref(), user_id, &UserTrendInfoId::new(id)).await?;
+    let history = user_trend
+        .history()
+        .iter()
+        .map(|entry| {
+            TrendHistory::from(TrendHistoryRecord::new(
+                user_trend.id().clone(),
+                entry.clone(),
+            ))
+        })
+        .collect();
+    Ok(Json(history))
+}
+
+// changes of every trend of the user in a period, e.g. `?since=2024-06-17&until=2024-06-23`
+async fn list_history(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+    params: Result<Query<TrendHistoryParams>, QueryRejection>,
+) -> Result<Json<Vec<TrendHistory>>, ApiError> {
+    println!("called list_history");
+    let Query(params) = params?;
+    let query = params.into_query(user_id)?;
+    let records = trend_history(state.trends.as_ref(), &query).await?;
+    Ok(Json(records.into_iter().map(TrendHistory::from).collect()))
+}
+
+// statuses of the workflow of the user and the statuses each may change to
+async fn show_workflow(
+    State(state): State<AppState>,
+    CurrentUser(user_id): CurrentUser,
+) -> Json<WorkflowConfig> {
+    println!("called show_workflow");
+    Json(WorkflowConfig::from(state.workflows.of(&user_id)))
 }
 
 async fn health_check() -> &'static str {
@@ -15,13 +288,193 @@ async fn health_check() -> &'static str {
     "ok"
 }
 
+// sources are read from TREND_SOURCES (a .toml or .json file),
+// AWS updates is collected when it is not set
+fn load_sources() -> TrendSourceRegistry {
+    match env::var("TREND_SOURCES") {
+        Ok(path) => TrendSourceRegistry::load(&path)
+            .unwrap_or_else(|e| panic!("failed to load sources from {}: {}", path, e)),
+        Err(_) => {
+            let mut registry = TrendSourceRegistry::new();
+            registry.add(TrendSource::aws_updates()).unwrap();
+            registry
+        }
+    }
+}
+
+// users and the SHA-256 of their tokens are read from TREND_USERS (a .toml or .json file),
+// without it every request on behalf of a user is refused
+fn load_tokens() -> TokenRegistry {
+    match env::var("TREND_USERS") {
+        Ok(path) => TokenRegistry::load(&path)
+            .unwrap_or_else(|e| panic!("failed to load users from {}: {}", path, e)),
+        Err(_) => {
+            println!("TREND_USERS is not set, requests on behalf of users are refused");
+            TokenRegistry::new()
+        }
+    }
+}
+
+// the workflow of statuses is read from TREND_WORKFLOW (a .toml or .json file),
+// which may also give users their own,
+// New, Reading, ToDo and Done are used when it is not set
+fn load_workflows() -> Workflows {
+    match env::var("TREND_WORKFLOW") {
+        Ok(path) => Workflows::load(&path)
+            .unwrap_or_else(|e| panic!("failed to load workflow from {}: {}", path, e)),
+        Err(_) => Workflows::default(),
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    env::var(name).ok().map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|e| panic!("invalid {} {}: {}", name, value, e))
+    })
+}
+
+// summaries are cut at TREND_SUMMARY_MAX_CHARS chars (200 by default).
+// When TREND_SUMMARY_LLM_URL is set, the chat completions endpoint under it
+// summarizes with TREND_SUMMARY_LLM_MODEL, TREND_SUMMARY_LLM_API_KEY,
+// TREND_SUMMARY_LLM_PROMPT and TREND_SUMMARY_LLM_MAX_TOKENS,
+// its summaries are cached in TREND_SUMMARY_CACHE (summary_cache.json by default)
+// and the trends are summarized offline while it is down.
+async fn load_summarizer() -> AnySummarizer {
+    let mut extractive = ExtractiveSummarizer::new();
+    if let Some(max_chars) = parse_env("TREND_SUMMARY_MAX_CHARS") {
+        extractive = extractive.with_max_chars(max_chars);
+    }
+    let Ok(base_url) = env::var("TREND_SUMMARY_LLM_URL") else {
+        return AnySummarizer::Extractive(extractive);
+    };
+    let model = env::var("TREND_SUMMARY_LLM_MODEL").unwrap_or_else(|_| "gpt-4o-mini".to_string());
+    let summary_cache =
+        env::var("TREND_SUMMARY_CACHE").unwrap_or_else(|_| "summary_cache.json".to_string());
+    let cache = SummaryCache::load(&summary_cache)
+        .await
+        .unwrap_or_else(|e| panic!("failed to load summary cache from {}: {}", summary_cache, e));
+    println!("Summarizing with {} at {}", model, base_url);
+    let mut llm = LlmSummarizer::new(base_url, model).with_cache(Arc::new(cache));
+    if let Ok(api_key) = env::var("TREND_SUMMARY_LLM_API_KEY") {
+        llm = llm.with_api_key(api_key);
+    }
+    if let Ok(prompt) = env::var("TREND_SUMMARY_LLM_PROMPT") {
+        llm = llm.with_prompt(prompt);
+    }
+    if let Some(max_tokens) = parse_env("TREND_SUMMARY_LLM_MAX_TOKENS") {
+        llm = llm.with_max_tokens(max_tokens);
+    }
+    AnySummarizer::Llm(FallbackSummarizer::new(llm, extractive))
+}
+
+// accounts and queries of X are collected in background for the subscribers
+// when TREND_X points at a .toml or .json file with the bearer token,
+// not on requests to /new, which anyone could use to spend the rate limit
+fn load_x() -> Option<XRawTrendCollector> {
+    let path = env::var("TREND_X").ok()?;
+    Some(
+        XRawTrendCollector::load(&path)
+            .unwrap_or_else(|e| panic!("failed to load X config from {}: {}", path, e)),
+    )
+}
+
+// sources of every user are collected in background,
+// every TREND_COLLECT_INTERVAL_MINUTES (60 by default) unless the source has its own interval.
+// The sources of the registry are collected for the users in TREND_SUBSCRIBERS (comma separated).
+fn spawn_scheduler(
+    state: &AppState,
+    validators: FetchValidatorStore,
+    x: Option<XRawTrendCollector>,
+) {
+    let subscribers: Vec<_> = env::var("TREND_SUBSCRIBERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|user_id| user_id.trim())
+        .filter(|user_id| !user_id.is_empty())
+        .map(UserId::new)
+        .collect();
+    let interval_minutes = env::var("TREND_COLLECT_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .filter(|minutes| (1..=u64::MAX / 60).contains(minutes))
+        .unwrap_or(60);
+    let mut scheduler = TrendCollectionScheduler::new(
+        state.trends.clone(),
+        state.sources.clone(),
+        |source: &TrendSource, validators: Option<FetchValidators>| match validators {
+            Some(validators) => source.collector().with_validators(validators),
+            None => source.collector(),
+        },
+    )
+    .with_shared_sources(state.registry.sources().to_vec(), subscribers)
+    .with_validators(Arc::new(validators))
+    .with_default_interval(Duration::from_secs(interval_minutes * 60));
+    if let Some(x) = x {
+        scheduler = scheduler.with_shared_collector("x", AnyRawTrendCollector::X(Arc::new(x)));
+    }
+    tokio::spawn(scheduler.run(Duration::from_secs(60)));
+}
+
 #[tokio::main]
 async fn main() {
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let addr = format!("0.0.0.0:{}", port);
+    let registry = load_sources();
+    println!("Collecting from {} sources", registry.sources().len());
+    let source_store =
+        env::var("TREND_SOURCE_STORE").unwrap_or_else(|_| "sources.json".to_string());
+    let fetch_cache =
+        env::var("TREND_FETCH_CACHE").unwrap_or_else(|_| "fetch_cache.json".to_string());
+    let validators = FetchValidatorStore::load(&fetch_cache)
+        .await
+        .unwrap_or_else(|e| panic!("failed to load fetch cache from {}: {}", fetch_cache, e));
+    // `postgres://...` for Postgres or `sqlite:<path>` for a single file
+    let database_url = env::var("DATABASE_URL").ok();
+    if database_url.is_none() {
+        println!("DATABASE_URL is not set, trends are kept in memory");
+    }
+    let trends = AnyUserTrendInfoRepository::connect(database_url.as_deref())
+        .await
+        .unwrap_or_else(|e| panic!("failed to open the trend store: {}", e));
+    let workflows = load_workflows();
+    println!(
+        "Statuses: {}",
+        workflows
+            .default_workflow()
+            .statuses()
+            .map(|status| status.to_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let state = AppState {
+        tokens: Arc::new(load_tokens()),
+        registry: Arc::new(registry),
+        collections: Arc::new(CollectionCache::new()),
+        sources: Arc::new(JsonFileTrendSourceRepository::new(source_store)),
+        trends: Arc::new(trends),
+        workflows: Arc::new(workflows),
+        summarizer: Arc::new(load_summarizer().await),
+    };
+    spawn_scheduler(&state, validators, load_x());
     let app = Router::new()
         .route("/new", get(new))
-        .route("/health_check", get(health_check));
+        .route("/new/diagnostics", get(new_diagnostics))
+        .route("/sources", get(list_sources).post(add_source))
+        .route("/sources/:name", delete(delete_source))
+        .route("/trends", get(list_trends).post(add_trend))
+        .route(
+            "/trends/:id",
+            get(show_trend).patch(change_trend).delete(remove_trend),
+        )
+        .route("/trends/:id/history", get(show_trend_history))
+        .route("/history", get(list_history))
+        .route("/workflow", get(show_workflow))
+        .route("/health_check", get(health_check))
+        .with_state(state);
 
     println!("Listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
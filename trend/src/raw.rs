@@ -1,31 +1,184 @@
+use std::{sync::Arc, time::Duration};
+
 use date::Date;
 
+pub mod aggregate;
+pub mod atom;
+pub mod conditional;
+pub mod description;
+pub mod feed;
+pub mod hacker_news;
+pub mod json_feed;
+pub mod public;
 pub mod rss;
+pub mod x;
 
 pub trait RawTrendCollector {
     type Error: std::error::Error;
     #[allow(async_fn_in_trait)]
     async fn collect(&self) -> Result<CollectedRawTrends, Self::Error>;
 }
+impl<C: RawTrendCollector> RawTrendCollector for &C {
+    type Error = C::Error;
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        (**self).collect().await
+    }
+}
+// A collector of any kind, concrete so collectors of feeds and of X can be aggregated.
+pub enum AnyRawTrendCollector {
+    Feed(feed::RemoteFeedRawTrendCollector),
+    // shared, it remembers the rate limit between collections
+    X(Arc<x::XRawTrendCollector>),
+    HackerNews(hacker_news::HackerNewsRawTrendCollector),
+}
+impl AnyRawTrendCollector {
+    // fetch feeds conditionally, other collectors do not use the validators
+    pub fn with_validators(self, validators: conditional::FetchValidators) -> Self {
+        match self {
+            AnyRawTrendCollector::Feed(collector) => {
+                AnyRawTrendCollector::Feed(collector.with_validators(validators))
+            }
+            collector => collector,
+        }
+    }
+}
+impl RawTrendCollector for AnyRawTrendCollector {
+    type Error = AnyRawTrendCollectorError;
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        match self {
+            AnyRawTrendCollector::Feed(collector) => collector
+                .collect()
+                .await
+                .map_err(AnyRawTrendCollectorError::FeedError),
+            AnyRawTrendCollector::X(collector) => collector
+                .collect()
+                .await
+                .map_err(AnyRawTrendCollectorError::XError),
+            AnyRawTrendCollector::HackerNews(collector) => collector
+                .collect()
+                .await
+                .map_err(AnyRawTrendCollectorError::HackerNewsError),
+        }
+    }
+}
 #[derive(Debug)]
+pub enum AnyRawTrendCollectorError {
+    FeedError(feed::RemoteFeedRawTrendCollectorError),
+    XError(x::XRawTrendCollectorError),
+    HackerNewsError(hacker_news::HackerNewsRawTrendCollectorError),
+}
+impl std::fmt::Display for AnyRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnyRawTrendCollectorError::FeedError(e) => write!(f, "FeedError: {}", e),
+            AnyRawTrendCollectorError::XError(e) => write!(f, "XError: {}", e),
+            AnyRawTrendCollectorError::HackerNewsError(e) => write!(f, "HackerNewsError: {}", e),
+        }
+    }
+}
+impl std::error::Error for AnyRawTrendCollectorError {}
+
+#[derive(Debug, Clone)]
 pub struct CollectedRawTrends {
     inner: Vec<RawTrendInfo>,
+    skipped: Vec<SkippedRawTrendInfo>,
+    ttl: Option<Duration>,
+    validators: Option<conditional::FetchValidators>,
+    not_modified: bool,
 }
 impl CollectedRawTrends {
+    pub fn empty() -> Self {
+        Self {
+            inner: vec![],
+            skipped: vec![],
+            ttl: None,
+            validators: None,
+            not_modified: false,
+        }
+    }
+    // the publisher answered `304 Not Modified`, the trends of the last fetch still hold
+    pub fn not_modified() -> Self {
+        Self {
+            not_modified: true,
+            ..Self::empty()
+        }
+    }
+    pub fn is_not_modified(&self) -> bool {
+        self.not_modified
+    }
     pub fn latest(&self) -> Option<&RawTrendInfo> {
         self.inner.first()
     }
     pub fn trends(&self) -> &[RawTrendInfo] {
         &self.inner
     }
-    fn new(inner: Vec<RawTrendInfo>) -> Self {
-        let inner = Self::sort(inner);
-        Self { inner }
+    // items of the feed which could not be turned into RawTrendInfo
+    pub fn skipped(&self) -> &[SkippedRawTrendInfo] {
+        &self.skipped
+    }
+    // the feed has items, but none of them could be used.
+    // this usually means the publisher changed the format of the feed
+    pub fn is_all_skipped(&self) -> bool {
+        self.inner.is_empty() && !self.skipped.is_empty()
+    }
+    // how long the publisher asks us to wait before fetching again, like RSS <ttl>
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+    fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+    // validators of the response the trends were read from, to be kept by the caller
+    // once the trends are processed, so the next fetch is conditional
+    pub fn validators(&self) -> Option<&conditional::FetchValidators> {
+        self.validators.as_ref()
+    }
+    fn with_validators(mut self, validators: Option<conditional::FetchValidators>) -> Self {
+        self.validators = validators;
+        self
+    }
+    // names the source of the skipped items, their index alone is ambiguous once merged
+    fn with_source(mut self, source: &str) -> Self {
+        for skipped in &mut self.skipped {
+            skipped.source.get_or_insert_with(|| source.to_string());
+        }
+        self
+    }
+    // trends from other sources are mixed in, keeping the latest first order
+    pub fn merge(mut self, other: CollectedRawTrends) -> Self {
+        self.inner.extend(other.inner);
+        self.inner = Self::sort(self.inner);
+        self.skipped.extend(other.skipped);
+        self.ttl = self.ttl.max(other.ttl);
+        // validators belong to a single response
+        self.validators = None;
+        self.not_modified = false;
+        self
+    }
+    fn from_results(
+        results: impl IntoIterator<Item = Result<RawTrendInfo, SkippedRawTrendInfo>>,
+    ) -> Self {
+        let mut inner = vec![];
+        let mut skipped = vec![];
+        for result in results {
+            match result {
+                Ok(info) => inner.push(info),
+                Err(e) => skipped.push(e),
+            }
+        }
+        Self {
+            inner: Self::sort(inner),
+            skipped,
+            ttl: None,
+            validators: None,
+            not_modified: false,
+        }
     }
     fn sort(mut inner: Vec<RawTrendInfo>) -> Vec<RawTrendInfo> {
         // Sort by pub_date desc
         // this mean is latest trend is first.
-        inner.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        inner.sort_by_key(|info| std::cmp::Reverse(info.created_at));
         inner
     }
 }
@@ -39,6 +192,9 @@ impl Service {
     pub fn x() -> Self {
         Self("x".to_string())
     }
+    pub fn hacker_news() -> Self {
+        Self("hacker_news".to_string())
+    }
     pub fn to_str(&self) -> &str {
         &self.0
     }
@@ -52,7 +208,10 @@ impl From<String> for Service {
 pub struct RawTrendInfo {
     pub(super) title: String,
     pub(super) link: String,
+    // as written in the feed, usually html
     pub(super) desc: String,
+    pub(super) desc_html: String,
+    pub(super) desc_text: String,
     pub(super) from: Service,
     pub(super) created_at: Date,
 }
@@ -64,14 +223,26 @@ impl RawTrendInfo {
         from: Service,
         created_at: Date,
     ) -> Self {
+        let link = link.into();
+        let desc = desc.into();
         Self {
+            desc_html: description::sanitize_html(&desc, Some(&link)),
+            desc_text: description::plain_text(&desc),
             title: title.into(),
-            link: link.into(),
-            desc: desc.into(),
+            link,
+            desc,
             from,
             created_at,
         }
     }
+    // relative links of the description point under the link of the channel,
+    // they are resolved against the link of the item when it is not known
+    pub fn with_base_link(mut self, base_link: &str) -> Self {
+        if !base_link.is_empty() {
+            self.desc_html = description::sanitize_html(&self.desc, Some(base_link));
+        }
+        self
+    }
     pub fn title(&self) -> &str {
         &self.title
     }
@@ -81,16 +252,95 @@ impl RawTrendInfo {
     pub fn desc(&self) -> &str {
         &self.desc
     }
+    // the description with only the tags which are safe to show
+    pub fn desc_html(&self) -> &str {
+        &self.desc_html
+    }
+    pub fn desc_text(&self) -> &str {
+        &self.desc_text
+    }
     pub fn created_at(&self) -> &Date {
         &self.created_at
     }
     pub fn from(&self) -> &str {
-        &self.from.to_str()
+        self.from.to_str()
     }
 }
 
+#[derive(Debug, Clone)]
 pub enum RawTrendInfoError {
     InvalidDate(String),
+    MissingDate,
+    // the item could not be fetched or read, for sources which fetch items one by one
+    Unreadable(String),
+}
+impl std::fmt::Display for RawTrendInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawTrendInfoError::InvalidDate(s) => write!(f, "InvalidDate: {}", s),
+            RawTrendInfoError::MissingDate => write!(f, "MissingDate"),
+            RawTrendInfoError::Unreadable(s) => write!(f, "Unreadable: {}", s),
+        }
+    }
+}
+impl std::error::Error for RawTrendInfoError {}
+
+#[derive(Debug, Clone)]
+pub struct SkippedRawTrendInfo {
+    // name of the source, known once collected from many sources
+    source: Option<String>,
+    index: usize,
+    guid: Option<String>,
+    link: Option<String>,
+    reason: RawTrendInfoError,
+}
+impl SkippedRawTrendInfo {
+    fn new(
+        index: usize,
+        guid: Option<&str>,
+        link: Option<&str>,
+        reason: RawTrendInfoError,
+    ) -> Self {
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        Self {
+            source: None,
+            index,
+            guid: guid.and_then(non_empty),
+            link: link.and_then(non_empty),
+            reason,
+        }
+    }
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+    // position of the item in the feed, starting from 0
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn guid(&self) -> Option<&str> {
+        self.guid.as_deref()
+    }
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+    pub fn reason(&self) -> &RawTrendInfoError {
+        &self.reason
+    }
+}
+impl std::fmt::Display for SkippedRawTrendInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "{} ", source)?;
+        }
+        write!(f, "item {}", self.index)?;
+        if let Some(guid) = &self.guid {
+            write!(f, " guid={}", guid)?;
+        }
+        if let Some(link) = &self.link {
+            write!(f, " link={}", link)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -99,6 +349,10 @@ pub struct Trend {
     link: String,
     from: String,
     desc: String,
+    #[serde(default)]
+    desc_html: String,
+    #[serde(default)]
+    desc_text: String,
 }
 impl From<CollectedRawTrends> for Vec<Trend> {
     fn from(value: CollectedRawTrends) -> Self {
@@ -109,6 +363,62 @@ impl From<CollectedRawTrends> for Vec<Trend> {
             .collect()
     }
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct CollectionReport {
+    collected: usize,
+    skipped: Vec<SkippedTrend>,
+    #[serde(default)]
+    sources: Vec<SourceReport>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SkippedTrend {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    index: usize,
+    guid: Option<String>,
+    link: Option<String>,
+    reason: String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SourceReport {
+    name: String,
+    collected: Option<usize>,
+    error: Option<String>,
+}
+impl From<&aggregate::AggregatedRawTrends> for CollectionReport {
+    fn from(value: &aggregate::AggregatedRawTrends) -> Self {
+        let mut report = CollectionReport::from(value.trends());
+        report.sources = value
+            .sources()
+            .iter()
+            .map(|(name, result)| SourceReport {
+                name: name.clone(),
+                collected: result.as_ref().ok().copied(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+            })
+            .collect();
+        report
+    }
+}
+impl From<&CollectedRawTrends> for CollectionReport {
+    fn from(value: &CollectedRawTrends) -> Self {
+        Self {
+            collected: value.trends().len(),
+            skipped: value
+                .skipped()
+                .iter()
+                .map(|skipped| SkippedTrend {
+                    source: skipped.source.clone(),
+                    index: skipped.index,
+                    guid: skipped.guid.clone(),
+                    link: skipped.link.clone(),
+                    reason: skipped.reason.to_string(),
+                })
+                .collect(),
+            sources: vec![],
+        }
+    }
+}
 impl From<RawTrendInfo> for Trend {
     fn from(info: RawTrendInfo) -> Self {
         let from = info.from().to_string();
@@ -117,6 +427,8 @@ impl From<RawTrendInfo> for Trend {
             link: info.link,
             from,
             desc: info.desc,
+            desc_html: info.desc_html,
+            desc_text: info.desc_text,
         }
     }
 }
@@ -0,0 +1,381 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    time::Duration,
+};
+
+use futures::StreamExt;
+
+use super::{CollectedRawTrends, RawTrendCollector};
+
+// Collects from many sources at once.
+// A source which fails or does not answer in time is reported, the others are still merged.
+pub struct AggregateRawTrendCollector<C: RawTrendCollector> {
+    collectors: Vec<(String, C)>,
+    concurrency: usize,
+    timeout: Duration,
+}
+impl<C: RawTrendCollector> AggregateRawTrendCollector<C> {
+    const DEFAULT_CONCURRENCY: usize = 8;
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+    // collectors are named, the name is the key of the result of each source,
+    // so two collectors of the same name are rejected
+    pub fn new(collectors: Vec<(String, C)>) -> Result<Self, AggregateRawTrendCollectorError> {
+        let mut names = HashSet::new();
+        for (name, _) in &collectors {
+            if !names.insert(name.as_str()) {
+                return Err(AggregateRawTrendCollectorError::DuplicateName(name.clone()));
+            }
+        }
+        Ok(Self {
+            collectors,
+            concurrency: Self::DEFAULT_CONCURRENCY,
+            timeout: Self::DEFAULT_TIMEOUT,
+        })
+    }
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    pub async fn collect_each(&self) -> AggregatedRawTrends {
+        AggregatedRawTrends::from_results(self.collect_separately().await)
+    }
+    // results of each source, without merging them
+    pub async fn collect_separately(
+        &self,
+    ) -> Vec<(String, Result<CollectedRawTrends, SourceCollectionError>)> {
+        // futures are built up front, a closure inside the stream would make the future of
+        // this method not Send for axum handlers
+        let collecting: Vec<_> = self
+            .collectors
+            .iter()
+            .map(|(name, collector)| Self::collect_one(name, collector, self.timeout))
+            .collect();
+        futures::stream::iter(collecting)
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await
+    }
+    async fn collect_one(
+        name: &str,
+        collector: &C,
+        timeout: Duration,
+    ) -> (String, Result<CollectedRawTrends, SourceCollectionError>) {
+        let result = match tokio::time::timeout(timeout, collector.collect()).await {
+            Ok(Ok(trends)) => Ok(trends),
+            Ok(Err(e)) => Err(SourceCollectionError::CollectError(e.to_string())),
+            Err(_) => Err(SourceCollectionError::Timeout(timeout)),
+        };
+        (name.to_string(), result)
+    }
+}
+
+impl<C: RawTrendCollector> RawTrendCollector for AggregateRawTrendCollector<C> {
+    type Error = AggregateRawTrendCollectorError;
+    // fails only when no source could be collected
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        self.collect_each().await.into_result()
+    }
+}
+
+#[derive(Debug)]
+pub struct AggregatedRawTrends {
+    trends: CollectedRawTrends,
+    sources: BTreeMap<String, Result<usize, SourceCollectionError>>,
+}
+impl AggregatedRawTrends {
+    // merges the results of `collect_separately`
+    pub fn from_results(
+        results: Vec<(String, Result<CollectedRawTrends, SourceCollectionError>)>,
+    ) -> Self {
+        let mut trends = CollectedRawTrends::empty();
+        let mut sources = BTreeMap::new();
+        for (name, result) in results {
+            match result {
+                Ok(collected) => {
+                    sources.insert(name.clone(), Ok(collected.trends().len()));
+                    trends = trends.merge(collected.with_source(&name));
+                }
+                Err(e) => {
+                    sources.insert(name, Err(e));
+                }
+            }
+        }
+        Self { trends, sources }
+    }
+    pub fn trends(&self) -> &CollectedRawTrends {
+        &self.trends
+    }
+    pub fn into_trends(self) -> CollectedRawTrends {
+        self.trends
+    }
+    // the trends, unless no source could be collected
+    pub fn into_result(self) -> Result<CollectedRawTrends, AggregateRawTrendCollectorError> {
+        if !self.sources.is_empty() && self.failures().count() == self.sources.len() {
+            return Err(AggregateRawTrendCollectorError::AllFailed(
+                self.failures()
+                    .map(|(name, e)| (name.to_string(), e.clone()))
+                    .collect(),
+            ));
+        }
+        Ok(self.trends)
+    }
+    // number of collected trends, or the reason of the failure, by the name of the source
+    pub fn sources(&self) -> &BTreeMap<String, Result<usize, SourceCollectionError>> {
+        &self.sources
+    }
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &SourceCollectionError)> {
+        self.sources
+            .iter()
+            .filter_map(|(name, result)| result.as_ref().err().map(|e| (name.as_str(), e)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SourceCollectionError {
+    CollectError(String),
+    Timeout(Duration),
+}
+impl Display for SourceCollectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceCollectionError::CollectError(s) => write!(f, "CollectError: {}", s),
+            SourceCollectionError::Timeout(d) => write!(f, "Timeout: {}ms", d.as_millis()),
+        }
+    }
+}
+impl std::error::Error for SourceCollectionError {}
+
+#[derive(Debug)]
+pub enum AggregateRawTrendCollectorError {
+    DuplicateName(String),
+    AllFailed(Vec<(String, SourceCollectionError)>),
+}
+impl Display for AggregateRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AggregateRawTrendCollectorError::DuplicateName(s) => write!(f, "DuplicateName: {}", s),
+            AggregateRawTrendCollectorError::AllFailed(failures) => {
+                write!(f, "AllFailed:")?;
+                for (name, e) in failures {
+                    write!(f, " {}({})", name, e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+impl std::error::Error for AggregateRawTrendCollectorError {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use date::Date;
+
+    use super::*;
+    use crate::raw::{RawTrendInfo, RawTrendInfoError, Service, SkippedRawTrendInfo};
+
+    struct StubCollector {
+        delay: Duration,
+        result: Result<Vec<RawTrendInfo>, String>,
+        // items without a date, after those of the result
+        skipped: usize,
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+    impl StubCollector {
+        fn new(delay_ms: u64, result: Result<Vec<RawTrendInfo>, String>) -> Self {
+            Self {
+                delay: Duration::from_millis(delay_ms),
+                result,
+                skipped: 0,
+                in_flight: Arc::new(AtomicUsize::new(0)),
+                max_in_flight: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+    #[derive(Debug)]
+    struct StubError(String);
+    impl Display for StubError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+    impl std::error::Error for StubError {}
+    impl RawTrendCollector for StubCollector {
+        type Error = StubError;
+        async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            let skipped = (0..self.skipped).map(|index| {
+                Err(SkippedRawTrendInfo::new(
+                    index,
+                    None,
+                    None,
+                    RawTrendInfoError::MissingDate,
+                ))
+            });
+            self.result
+                .clone()
+                .map(|infos| {
+                    CollectedRawTrends::from_results(infos.into_iter().map(Ok).chain(skipped))
+                })
+                .map_err(StubError)
+        }
+    }
+    fn info(title: &str, date: &str) -> RawTrendInfo {
+        RawTrendInfo::new(
+            title,
+            format!("http://www.example.com/{}", title),
+            "desc",
+            Service::aws_updates(),
+            Date::parse_feed_date(date).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn merge_sources_and_report_failures() {
+        let collector = AggregateRawTrendCollector::new(vec![
+            (
+                "a".to_string(),
+                StubCollector::new(0, Ok(vec![info("a1", "2024-06-21")])),
+            ),
+            (
+                "broken".to_string(),
+                StubCollector::new(0, Err("boom".to_string())),
+            ),
+            (
+                "b".to_string(),
+                StubCollector::new(
+                    0,
+                    Ok(vec![info("b1", "2024-06-22"), info("b2", "2024-06-20")]),
+                ),
+            ),
+        ])
+        .unwrap();
+
+        let aggregated = collector.collect_each().await;
+
+        let titles: Vec<_> = aggregated
+            .trends()
+            .trends()
+            .iter()
+            .map(|info| info.title())
+            .collect();
+        assert_eq!(titles, vec!["b1", "a1", "b2"]);
+        assert_eq!(aggregated.sources()["a"].as_ref().unwrap(), &1);
+        assert_eq!(aggregated.sources()["b"].as_ref().unwrap(), &2);
+        assert!(matches!(
+            aggregated.sources()["broken"],
+            Err(SourceCollectionError::CollectError(_))
+        ));
+    }
+    #[tokio::test]
+    async fn skipped_items_are_reported_with_their_source() {
+        let mut a = StubCollector::new(0, Ok(vec![info("a1", "2024-06-21")]));
+        a.skipped = 1;
+        let mut b = StubCollector::new(0, Ok(vec![]));
+        b.skipped = 1;
+        let collector =
+            AggregateRawTrendCollector::new(vec![("a".to_string(), a), ("b".to_string(), b)])
+                .unwrap();
+
+        let aggregated = collector.collect_each().await;
+
+        let mut skipped: Vec<_> = aggregated
+            .trends()
+            .skipped()
+            .iter()
+            .map(|skipped| skipped.to_string())
+            .collect();
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            vec!["a item 0: MissingDate", "b item 0: MissingDate"]
+        );
+    }
+    #[tokio::test]
+    async fn slow_source_times_out() {
+        let collector = AggregateRawTrendCollector::new(vec![
+            (
+                "fast".to_string(),
+                StubCollector::new(0, Ok(vec![info("fast", "2024-06-21")])),
+            ),
+            (
+                "slow".to_string(),
+                StubCollector::new(10_000, Ok(vec![info("slow", "2024-06-21")])),
+            ),
+        ])
+        .unwrap()
+        .with_timeout(Duration::from_millis(50));
+
+        let trends = collector.collect().await.unwrap();
+
+        assert_eq!(trends.trends().len(), 1);
+        assert_eq!(trends.latest().unwrap().title(), "fast");
+    }
+    #[tokio::test]
+    async fn collect_fails_when_all_sources_fail() {
+        let collector = AggregateRawTrendCollector::new(vec![
+            (
+                "a".to_string(),
+                StubCollector::new(0, Err("boom".to_string())),
+            ),
+            (
+                "b".to_string(),
+                StubCollector::new(0, Err("boom".to_string())),
+            ),
+        ])
+        .unwrap();
+
+        let result = collector.collect().await;
+
+        assert!(matches!(
+            result,
+            Err(AggregateRawTrendCollectorError::AllFailed(failures)) if failures.len() == 2
+        ));
+    }
+    #[tokio::test]
+    async fn collect_with_bounded_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let collectors = (0..6)
+            .map(|i| {
+                let mut collector = StubCollector::new(20, Ok(vec![]));
+                collector.in_flight = in_flight.clone();
+                collector.max_in_flight = max_in_flight.clone();
+                (i.to_string(), collector)
+            })
+            .collect();
+        let collector = AggregateRawTrendCollector::new(collectors)
+            .unwrap()
+            .with_concurrency(2);
+
+        let aggregated = collector.collect_each().await;
+
+        assert_eq!(aggregated.sources().len(), 6);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+    #[test]
+    fn collectors_of_the_same_name_are_rejected() {
+        let result = AggregateRawTrendCollector::new(vec![
+            ("a".to_string(), StubCollector::new(0, Ok(vec![]))),
+            ("a".to_string(), StubCollector::new(0, Ok(vec![]))),
+        ]);
+
+        assert!(matches!(
+            result,
+            Err(AggregateRawTrendCollectorError::DuplicateName(name)) if name == "a"
+        ));
+    }
+}
@@ -0,0 +1,374 @@
+use std::fmt::Display;
+
+use date::Date;
+use quick_xml::{
+    events::{BytesText, Event},
+    Reader, Writer,
+};
+
+use super::{
+    conditional::{self, FetchValidators},
+    public, CollectedRawTrends, RawTrendCollector, RawTrendInfo, RawTrendInfoError, Service,
+    SkippedRawTrendInfo,
+};
+
+pub struct AtomRawTrendCollector<B: AsRef<[u8]>> {
+    service: Service,
+    bytes: B,
+}
+impl<B: AsRef<[u8]>> AtomRawTrendCollector<B> {
+    pub fn new(service: Service, bytes: B) -> Self {
+        Self { service, bytes }
+    }
+    // the feed with the dates of each entry as written
+    async fn to_feed(
+        &self,
+    ) -> Result<(atom_syndication::Feed, Vec<EntryDates>), AtomRawTrendCollectorError> {
+        let (bytes, dates) = read_dates(self.bytes.as_ref())?;
+        let feed = atom_syndication::Feed::read_from(bytes.as_slice())
+            .map_err(AtomRawTrendCollectorError::AtomError)?;
+        Ok((feed, dates))
+    }
+}
+#[derive(Debug)]
+pub enum AtomRawTrendCollectorError {
+    XmlError(quick_xml::Error),
+    AtomError(atom_syndication::Error),
+}
+impl std::fmt::Display for AtomRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtomRawTrendCollectorError::XmlError(e) => write!(f, "XmlError: {}", e),
+            AtomRawTrendCollectorError::AtomError(e) => write!(f, "AtomError: {}", e),
+        }
+    }
+}
+impl std::error::Error for AtomRawTrendCollectorError {}
+
+impl<B: AsRef<[u8]>> RawTrendCollector for AtomRawTrendCollector<B> {
+    type Error = AtomRawTrendCollectorError;
+    async fn collect(&self) -> Result<CollectedRawTrends, AtomRawTrendCollectorError> {
+        let (feed, dates) = self.to_feed().await?;
+        let base_link = alternate_link(feed.links()).unwrap_or_default();
+        Ok(CollectedRawTrends::from_results(
+            feed.entries()
+                .iter()
+                .zip(dates)
+                .enumerate()
+                .map(|(index, (entry, dates))| {
+                    entry_to_trend(entry, dates, self.service.clone())
+                        .map(|info| info.with_base_link(base_link))
+                        .map_err(|reason| {
+                            SkippedRawTrendInfo::new(
+                                index,
+                                Some(entry.id()),
+                                alternate_link(entry.links()),
+                                reason,
+                            )
+                        })
+                }),
+        ))
+    }
+}
+
+fn entry_to_trend(
+    entry: &atom_syndication::Entry,
+    dates: EntryDates,
+    from: Service,
+) -> Result<RawTrendInfo, RawTrendInfoError> {
+    let title = entry.title().as_str().to_string();
+    let link = alternate_link(entry.links())
+        .unwrap_or_default()
+        .to_string();
+    let desc = entry
+        .summary()
+        .map(|summary| summary.as_str().to_string())
+        .unwrap_or_default();
+    let date = dates
+        .updated
+        .or(dates.published)
+        .ok_or(RawTrendInfoError::MissingDate)?;
+    let created_at =
+        Date::parse_feed_date(&date).map_err(|_| RawTrendInfoError::InvalidDate(date.clone()))?;
+    Ok(RawTrendInfo::new(title, link, desc, from, created_at))
+}
+
+// the dates of an entry as written, `None` when left out or empty
+#[derive(Debug, Default, PartialEq, Eq)]
+struct EntryDates {
+    updated: Option<String>,
+    published: Option<String>,
+}
+
+// atom_syndication fails the whole feed on a date it can not parse and reads a missing one as 1970,
+// so the dates are read here and replaced by a valid one before the feed is given to it.
+// Returns the feed with the replaced dates and the dates of each entry in order.
+fn read_dates(bytes: &[u8]) -> Result<(Vec<u8>, Vec<EntryDates>), AtomRawTrendCollectorError> {
+    const REPLACED: &str = "1970-01-01T00:00:00Z";
+    let mut reader = Reader::from_reader(bytes);
+    let mut writer = Writer::new(Vec::with_capacity(bytes.len()));
+    let mut entries = vec![];
+    // local names of the open elements
+    let mut open: Vec<Vec<u8>> = vec![];
+    // text of the open date element
+    let mut date: Option<String> = None;
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(AtomRawTrendCollectorError::XmlError)?;
+        match &event {
+            Event::Start(start) => {
+                let name = start.local_name().as_ref().to_vec();
+                if name.as_slice() == b"entry" {
+                    entries.push(EntryDates::default());
+                }
+                if matches!(name.as_slice(), b"updated" | b"published") {
+                    date = Some(String::new());
+                }
+                open.push(name);
+            }
+            Event::Text(text) if date.is_some() => {
+                date.get_or_insert_with(String::new)
+                    .push_str(&String::from_utf8_lossy(text));
+                continue;
+            }
+            Event::CData(text) if date.is_some() => {
+                date.get_or_insert_with(String::new)
+                    .push_str(&String::from_utf8_lossy(text));
+                continue;
+            }
+            // a reference in a date makes it invalid anyway
+            Event::GeneralRef(reference) if date.is_some() => {
+                date.get_or_insert_with(String::new)
+                    .push_str(&format!("&{};", String::from_utf8_lossy(reference)));
+                continue;
+            }
+            Event::End(_) => {
+                let name = open.pop().unwrap_or_default();
+                if let Some(text) = date.take() {
+                    let text = text.trim().to_string();
+                    let in_entry = open.last().is_some_and(|parent| parent == b"entry");
+                    if let (true, Some(dates)) = (in_entry, entries.last_mut()) {
+                        let written = (!text.is_empty()).then_some(text);
+                        if name.as_slice() == b"updated" {
+                            dates.updated = written;
+                        } else {
+                            dates.published = written;
+                        }
+                    }
+                    writer
+                        .write_event(Event::Text(BytesText::new(REPLACED)))
+                        .map_err(|e| AtomRawTrendCollectorError::XmlError(e.into()))?;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        writer
+            .write_event(event)
+            .map_err(|e| AtomRawTrendCollectorError::XmlError(e.into()))?;
+    }
+    Ok((writer.into_inner(), entries))
+}
+
+// prefer the alternate link, since it points to the html page of the feed or the entry
+fn alternate_link(links: &[atom_syndication::Link]) -> Option<&str> {
+    links
+        .iter()
+        .find(|link| link.rel() == "alternate")
+        .or_else(|| links.first())
+        .map(|link| link.href())
+}
+
+pub struct RemoteAtomRawTrendCollector {
+    url: String,
+    service: Service,
+    validators: Option<FetchValidators>,
+}
+impl RemoteAtomRawTrendCollector {
+    pub fn new(url: impl Into<String>, service: Service) -> Self {
+        Self {
+            url: url.into(),
+            service,
+            validators: None,
+        }
+    }
+    // fetch conditionally with the validators of the last fetch,
+    // a feed which is not modified since then has no trends
+    pub fn with_validators(mut self, validators: FetchValidators) -> Self {
+        self.validators = Some(validators);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum RemoteAtomRawTrendCollectorError {
+    RequestError(reqwest::Error),
+    AtomError(AtomRawTrendCollectorError),
+}
+impl Display for RemoteAtomRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteAtomRawTrendCollectorError::RequestError(e) => {
+                write!(f, "RequestError: {}", e)
+            }
+            RemoteAtomRawTrendCollectorError::AtomError(e) => write!(f, "AtomError: {}", e),
+        }
+    }
+}
+impl std::error::Error for RemoteAtomRawTrendCollectorError {}
+
+impl RawTrendCollector for RemoteAtomRawTrendCollector {
+    type Error = RemoteAtomRawTrendCollectorError;
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        let Some(fetched) =
+            conditional::fetch(&public::client(), &self.url, self.validators.as_ref())
+                .await
+                .map_err(RemoteAtomRawTrendCollectorError::RequestError)?
+        else {
+            return Ok(CollectedRawTrends::not_modified());
+        };
+        let collector = AtomRawTrendCollector::new(self.service.clone(), fetched.bytes);
+        collector
+            .collect()
+            .await
+            .map_err(RemoteAtomRawTrendCollectorError::AtomError)
+            .map(|collected| collected.with_validators(fetched.validators))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Entry 1 is written bottom of the entries, but this entry is newer than others.
+    const DUMMY: &str = r#"
+<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example Atom Feed</title>
+  <link href="http://www.example.com/"/>
+  <updated>2024-06-22T02:22:32Z</updated>
+  <id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id>
+
+  <entry>
+    <title>Example Entry 2</title>
+    <link rel="self" href="http://www.example.com/entry2.atom"/>
+    <link rel="alternate" href="http://www.example.com/entry2"/>
+    <id>http://www.example.com/entry2</id>
+    <updated>2024-06-21T02:22:32Z</updated>
+    <summary>This is the summary for example entry 2.</summary>
+  </entry>
+
+  <entry>
+    <title>Example Entry 3</title>
+    <link href="http://www.example.com/entry3"/>
+    <id>http://www.example.com/entry3</id>
+    <updated>2024-06-21T02:22:32+09:00</updated>
+    <summary>This is the summary for example entry 3.</summary>
+  </entry>
+
+  <entry>
+    <title>Example Entry 1</title>
+    <link href="http://www.example.com/entry1"/>
+    <id>http://www.example.com/entry1</id>
+    <updated>2024-06-22T02:22:32Z</updated>
+    <summary>This is the summary for example entry 1.</summary>
+  </entry>
+</feed>
+"#;
+    #[tokio::test]
+    async fn collect_all_atom_entry() {
+        let collector =
+            AtomRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.trends().len(), 3);
+    }
+    #[tokio::test]
+    async fn collect_atom_to_trend_should_sorted_by_updated() {
+        let collector =
+            AtomRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.latest().unwrap().title(), "Example Entry 1");
+    }
+    #[tokio::test]
+    async fn collect_atom_to_trend_has_summary_and_alternate_link() {
+        let collector =
+            AtomRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
+        let infos = collector.collect().await.unwrap();
+
+        let entry2 = infos
+            .trends()
+            .iter()
+            .find(|info| info.title() == "Example Entry 2")
+            .unwrap();
+        assert_eq!(entry2.link(), "http://www.example.com/entry2");
+        assert_eq!(entry2.desc(), "This is the summary for example entry 2.");
+        assert_eq!(entry2.from(), Service::aws_updates().to_str());
+    }
+    #[tokio::test]
+    async fn entries_with_invalid_or_missing_date_are_skipped() {
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example Atom Feed</title>
+  <updated>yesterday</updated>
+  <id>urn:example</id>
+  <entry>
+    <title>Valid</title>
+    <link rel="alternate" href="http://www.example.com/valid"/>
+    <id>valid</id>
+    <updated>2024-06-21T02:22:32Z</updated>
+  </entry>
+  <entry>
+    <title>Invalid</title>
+    <link rel="self" href="http://www.example.com/invalid.atom"/>
+    <link rel="alternate" href="http://www.example.com/invalid"/>
+    <id>invalid</id>
+    <updated>sometime last week</updated>
+  </entry>
+  <entry>
+    <title>Missing</title>
+    <link href="http://www.example.com/missing"/>
+    <id>missing</id>
+  </entry>
+  <entry>
+    <title>Published only</title>
+    <link href="http://www.example.com/published"/>
+    <id>published</id>
+    <published>Sat, 22 Jun 2024 02:22:32 GMT</published>
+    <source><updated>2024-06-01T00:00:00Z</updated></source>
+  </entry>
+</feed>"#;
+        let collector = AtomRawTrendCollector::new(Service::aws_updates(), atom.as_bytes());
+
+        let infos = collector.collect().await.unwrap();
+
+        let titles: Vec<_> = infos.trends().iter().map(|info| info.title()).collect();
+        assert_eq!(titles, vec!["Published only", "Valid"]);
+        assert_eq!(
+            infos.latest().unwrap().created_at().to_string(),
+            "2024-06-22:00:00:00"
+        );
+        let skipped = infos.skipped();
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0].index(), 1);
+        assert_eq!(skipped[0].link(), Some("http://www.example.com/invalid"));
+        assert!(matches!(
+            skipped[0].reason(),
+            RawTrendInfoError::InvalidDate(date) if date == "sometime last week"
+        ));
+        assert_eq!(skipped[1].index(), 2);
+        assert!(matches!(
+            skipped[1].reason(),
+            RawTrendInfoError::MissingDate
+        ));
+    }
+    #[tokio::test]
+    async fn collect_rss_as_atom_should_fail() {
+        let rss = r#"<?xml version="1.0"?><rss version="2.0"><channel></channel></rss>"#;
+        let collector = AtomRawTrendCollector::new(Service::aws_updates(), rss.as_bytes());
+
+        assert!(collector.collect().await.is_err());
+    }
+}
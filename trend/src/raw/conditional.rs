@@ -0,0 +1,300 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use reqwest::{header, StatusCode};
+use tokio::sync::Mutex;
+
+use super::CollectedRawTrends;
+
+// What the publisher told us about the last response of a url,
+// sent back so it can answer `304 Not Modified` when nothing changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FetchValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+impl FetchValidators {
+    pub fn new(etag: Option<String>, last_modified: Option<String>) -> Self {
+        Self {
+            etag,
+            last_modified,
+        }
+    }
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+    pub fn last_modified(&self) -> Option<&str> {
+        self.last_modified.as_deref()
+    }
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+    fn from_response(response: &reqwest::Response) -> Self {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        Self {
+            etag: header(header::ETAG),
+            last_modified: header(header::LAST_MODIFIED),
+        }
+    }
+}
+
+// Validators by the key of their consumer, e.g. a source of a user collected in background.
+// Each consumer keeps its own, so a feed which one of them saw change is not `304 Not Modified`
+// for the others. They are written to the file on every change when a path is given,
+// so a restart of the server does not download every feed again.
+pub struct FetchValidatorStore {
+    path: Option<PathBuf>,
+    validators: Mutex<HashMap<String, FetchValidators>>,
+}
+impl FetchValidatorStore {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            validators: Mutex::new(HashMap::new()),
+        }
+    }
+    pub async fn load(path: impl Into<PathBuf>) -> Result<Self, FetchValidatorStoreError> {
+        let path = path.into();
+        let validators = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| FetchValidatorStoreError::ConvertError(e.to_string()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(FetchValidatorStoreError::IoError(e.to_string())),
+        };
+        Ok(Self {
+            path: Some(path),
+            validators: Mutex::new(validators),
+        })
+    }
+    pub async fn get(&self, key: &str) -> Option<FetchValidators> {
+        self.validators.lock().await.get(key).cloned()
+    }
+    // called once the body fetched with the validators is processed,
+    // a failure before that must have the feed downloaded again
+    pub async fn update(
+        &self,
+        key: &str,
+        validators: FetchValidators,
+    ) -> Result<(), FetchValidatorStoreError> {
+        let mut all = self.validators.lock().await;
+        if all.get(key) == Some(&validators) {
+            return Ok(());
+        }
+        all.insert(key.to_string(), validators);
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let bytes = serde_json::to_vec_pretty(&*all)
+            .map_err(|e| FetchValidatorStoreError::ConvertError(e.to_string()))?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .map_err(|e| FetchValidatorStoreError::IoError(e.to_string()))?;
+        tokio::fs::rename(&tmp, path)
+            .await
+            .map_err(|e| FetchValidatorStoreError::IoError(e.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum FetchValidatorStoreError {
+    IoError(String),
+    ConvertError(String),
+}
+impl std::fmt::Display for FetchValidatorStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchValidatorStoreError::IoError(s) => write!(f, "IoError: {}", s),
+            FetchValidatorStoreError::ConvertError(s) => write!(f, "ConvertError: {}", s),
+        }
+    }
+}
+impl std::error::Error for FetchValidatorStoreError {}
+
+// The last collection of each source which came with validators, for sources collected on request.
+// A source is fetched with the validators of its last collection,
+// and that collection is used again when the publisher answers `304 Not Modified`.
+#[derive(Default)]
+pub struct CollectionCache {
+    collections: Mutex<HashMap<String, CollectedRawTrends>>,
+}
+impl CollectionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub async fn validators(&self, key: &str) -> Option<FetchValidators> {
+        self.collections
+            .lock()
+            .await
+            .get(key)
+            .and_then(|collected| collected.validators().cloned())
+    }
+    // the trends of a collection fetched with `validators`, from the cache when not modified
+    pub async fn resolve(&self, key: &str, collected: CollectedRawTrends) -> CollectedRawTrends {
+        let mut collections = self.collections.lock().await;
+        if collected.is_not_modified() {
+            return collections.get(key).cloned().unwrap_or(collected);
+        }
+        if collected.validators().is_some() {
+            collections.insert(key.to_string(), collected.clone());
+        } else {
+            collections.remove(key);
+        }
+        collected
+    }
+}
+
+// The body of a response, with the validators to send when fetching the url next time.
+pub(super) struct Fetched {
+    pub(super) bytes: bytes::Bytes,
+    pub(super) validators: Option<FetchValidators>,
+}
+
+// GET the url, conditionally when validators of the last fetch are given.
+// `None` means the publisher answered `304 Not Modified`, there is nothing new to read.
+// Nothing is stored here, the caller keeps the new validators once it used the body.
+pub(super) async fn fetch(
+    client: &reqwest::Client,
+    url: &str,
+    validators: Option<&FetchValidators>,
+) -> Result<Option<Fetched>, reqwest::Error> {
+    let mut request = client.get(url);
+    if let Some(validators) = validators {
+        if let Some(etag) = validators.etag() {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = validators.last_modified() {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let response = response.error_for_status()?;
+    let validators =
+        Some(FetchValidators::from_response(&response)).filter(|validators| !validators.is_empty());
+    Ok(Some(Fetched {
+        bytes: response.bytes().await?,
+        validators,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::get,
+        Router,
+    };
+
+    use date::Date;
+
+    use super::*;
+    use crate::raw::{RawTrendInfo, Service};
+
+    const ETAG: &str = "\"v1\"";
+    const LAST_MODIFIED: &str = "Sat, 22 Jun 2024 02:22:32 GMT";
+
+    // stands in for a publisher which supports conditional requests
+    async fn serve_feed(requests: Arc<AtomicUsize>) -> String {
+        let app = Router::new().route(
+            "/feed",
+            get(move |headers: HeaderMap| {
+                let requests = requests.clone();
+                async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    let not_modified = headers
+                        .get(header::IF_NONE_MATCH)
+                        .is_some_and(|etag| etag == ETAG);
+                    if not_modified {
+                        return (StatusCode::NOT_MODIFIED, HeaderMap::new(), "");
+                    }
+                    let mut response_headers = HeaderMap::new();
+                    response_headers.insert(header::ETAG, ETAG.parse().unwrap());
+                    response_headers.insert(header::LAST_MODIFIED, LAST_MODIFIED.parse().unwrap());
+                    (StatusCode::OK, response_headers, "feed body")
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/feed", addr)
+    }
+
+    #[tokio::test]
+    async fn not_modified_collection_is_served_from_cache() {
+        let cache = CollectionCache::new();
+        let info = RawTrendInfo::new(
+            "title",
+            "http://example.com/1",
+            "desc",
+            Service::aws_updates(),
+            Date::now(),
+        );
+        let collected = CollectedRawTrends::from_results([Ok(info)])
+            .with_validators(Some(FetchValidators::new(Some(ETAG.to_string()), None)));
+
+        cache.resolve("aws", collected).await;
+        let validators = cache.validators("aws").await.unwrap();
+        let resolved = cache
+            .resolve("aws", CollectedRawTrends::not_modified())
+            .await;
+
+        assert_eq!(validators.etag(), Some(ETAG));
+        assert_eq!(resolved.trends().len(), 1);
+        assert!(!resolved.is_not_modified());
+        assert!(cache.validators("other").await.is_none());
+    }
+    #[tokio::test]
+    async fn fetch_with_validators_of_last_fetch_is_not_modified() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = serve_feed(requests.clone()).await;
+        let client = reqwest::Client::new();
+
+        let first = fetch(&client, &url, None).await.unwrap().unwrap();
+        let validators = first.validators.unwrap();
+        let second = fetch(&client, &url, Some(&validators)).await.unwrap();
+
+        assert_eq!(first.bytes.as_ref(), b"feed body");
+        assert_eq!(validators.etag(), Some(ETAG));
+        assert_eq!(validators.last_modified(), Some(LAST_MODIFIED));
+        assert!(second.is_none());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+    #[tokio::test]
+    async fn fetch_without_validators_always_downloads() {
+        let url = serve_feed(Arc::new(AtomicUsize::new(0))).await;
+        let client = reqwest::Client::new();
+
+        assert!(fetch(&client, &url, None).await.unwrap().is_some());
+        assert!(fetch(&client, &url, None).await.unwrap().is_some());
+    }
+    #[tokio::test]
+    async fn validators_survive_reload() {
+        let path = std::env::temp_dir().join(format!(
+            "trend-validators-{}-reload.json",
+            std::process::id()
+        ));
+        let url = "http://www.example.com/feed";
+        let validators = FetchValidators::new(Some(ETAG.to_string()), None);
+
+        let store = FetchValidatorStore::load(&path).await.unwrap();
+        store.update(url, validators.clone()).await.unwrap();
+        let store = FetchValidatorStore::load(&path).await.unwrap();
+
+        assert_eq!(store.get(url).await, Some(validators));
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,573 @@
+// Descriptions of feeds are html written by the publisher.
+// They are made safe to show as html, or turned into plain text,
+// so clients do not have to sanitize them by themselves.
+
+// tags kept in the safe html, any other tag is removed but its text is kept
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "dd",
+    "dl",
+    "dt",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+// attributes kept by tag, any other attribute is removed
+const ALLOWED_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "title"]),
+    ("abbr", &["title"]),
+    ("img", &["src", "alt", "title", "width", "height"]),
+    ("td", &["colspan", "rowspan"]),
+    ("th", &["colspan", "rowspan"]),
+];
+// attributes which are links, they are made absolute
+const LINK_ATTRIBUTES: &[&str] = &["href", "src"];
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto"];
+// tags which have no end tag
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+// the contents of these tags are not text, they are removed with the tag
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "template", "noscript", "svg", "math",
+];
+// script and style may contain `<` which is not a tag
+const RAW_TEXT_TAGS: &[&str] = &["script", "style"];
+// tags which end a line of text
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "br",
+    "div",
+    "li",
+    "ul",
+    "ol",
+    "tr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "hr",
+];
+
+// Html with only the allowed tags and attributes, every tag closed.
+// Relative links are made absolute against `base`, e.g. the link of the channel,
+// and dropped when there is no base to resolve them.
+pub fn sanitize_html(html: &str, base: Option<&str>) -> String {
+    let mut safe = String::new();
+    let mut open: Vec<String> = vec![];
+    let mut dropped = 0;
+    for token in tokenize(html) {
+        match token {
+            Token::Text(text) => {
+                if dropped == 0 {
+                    safe.push_str(&escape(&decode_entities(text), false));
+                }
+            }
+            Token::Start {
+                name,
+                attributes,
+                self_closing,
+            } => {
+                if DROPPED_TAGS.contains(&name.as_str()) {
+                    if !self_closing {
+                        dropped += 1;
+                    }
+                    continue;
+                }
+                if dropped > 0 || !ALLOWED_TAGS.contains(&name.as_str()) {
+                    continue;
+                }
+                safe.push('<');
+                safe.push_str(&name);
+                let mut has_link = false;
+                for (attribute, value) in attributes {
+                    let Some(value) = allowed_attribute(&name, &attribute, &value, base) else {
+                        continue;
+                    };
+                    has_link |= attribute == "href";
+                    safe.push_str(&format!(" {}=\"{}\"", attribute, escape(&value, true)));
+                }
+                // links go to other sites, they must not reach back to the client
+                if name == "a" && has_link {
+                    safe.push_str(" rel=\"noopener noreferrer\"");
+                }
+                safe.push('>');
+                if VOID_TAGS.contains(&name.as_str()) {
+                    continue;
+                }
+                if self_closing {
+                    safe.push_str(&format!("</{}>", name));
+                } else {
+                    open.push(name);
+                }
+            }
+            Token::End(name) => {
+                if DROPPED_TAGS.contains(&name.as_str()) {
+                    dropped = usize::saturating_sub(dropped, 1);
+                    continue;
+                }
+                if dropped > 0 {
+                    continue;
+                }
+                // tags left open inside are closed too, an end tag without start is ignored
+                if let Some(at) = open.iter().rposition(|open| *open == name) {
+                    for name in open.drain(at..).rev() {
+                        safe.push_str(&format!("</{}>", name));
+                    }
+                }
+            }
+        }
+    }
+    for name in open.into_iter().rev() {
+        safe.push_str(&format!("</{}>", name));
+    }
+    safe
+}
+
+// Text of the html, a line for each block.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut dropped = 0;
+    for token in tokenize(html) {
+        match token {
+            Token::Text(s) => {
+                if dropped == 0 {
+                    text.push_str(&decode_entities(s));
+                }
+            }
+            Token::Start {
+                name, self_closing, ..
+            } => {
+                if DROPPED_TAGS.contains(&name.as_str()) {
+                    if !self_closing {
+                        dropped += 1;
+                    }
+                } else if dropped == 0 && BLOCK_TAGS.contains(&name.as_str()) {
+                    text.push('\n');
+                }
+            }
+            Token::End(name) => {
+                if DROPPED_TAGS.contains(&name.as_str()) {
+                    dropped = usize::saturating_sub(dropped, 1);
+                } else if dropped == 0 && BLOCK_TAGS.contains(&name.as_str()) {
+                    text.push('\n');
+                }
+            }
+        }
+    }
+    text
+}
+
+// Text of the html to be read as is, spaces collapsed and without empty lines.
+pub fn plain_text(html: &str) -> String {
+    html_to_text(html)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn allowed_attribute(
+    tag: &str,
+    attribute: &str,
+    value: &str,
+    base: Option<&str>,
+) -> Option<String> {
+    let (_, allowed) = ALLOWED_ATTRIBUTES.iter().find(|(name, _)| *name == tag)?;
+    if !allowed.contains(&attribute) {
+        return None;
+    }
+    if LINK_ATTRIBUTES.contains(&attribute) {
+        return resolve_link(value, base);
+    }
+    Some(value.to_string())
+}
+
+// The link made absolute, `None` when it is not http(s) or mailto, e.g. `javascript:`.
+pub fn resolve_link(link: &str, base: Option<&str>) -> Option<String> {
+    let link = link.trim();
+    if link.is_empty() {
+        return None;
+    }
+    if let Some(scheme) = scheme_of(link) {
+        return ALLOWED_SCHEMES
+            .contains(&scheme.to_ascii_lowercase().as_str())
+            .then(|| link.to_string());
+    }
+    let base = base
+        .filter(|base| scheme_of(base).is_some_and(|scheme| matches!(scheme, "http" | "https")))?;
+    let (scheme, rest) = base.split_once("://")?;
+    if let Some(network_path) = link.strip_prefix("//") {
+        return Some(format!("{}://{}", scheme, network_path));
+    }
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let origin = format!("{}://{}", scheme, &rest[..authority_end]);
+    let path_and_more = &rest[authority_end..];
+    let path = &path_and_more[..path_and_more
+        .find(['?', '#'])
+        .unwrap_or(path_and_more.len())];
+    let path = if path.is_empty() { "/" } else { path };
+    if link.starts_with('#') || link.starts_with('?') {
+        let without = &path_and_more[..path_and_more
+            .find(if link.starts_with('#') { '#' } else { '?' })
+            .unwrap_or(path_and_more.len())];
+        let without = if without.is_empty() { "/" } else { without };
+        return Some(format!("{}{}{}", origin, without, link));
+    }
+    let joined = if link.starts_with('/') {
+        link.to_string()
+    } else {
+        // relative to the directory of the base
+        format!(
+            "{}{}",
+            &path[..path.rfind('/').map_or(0, |at| at + 1)],
+            link
+        )
+    };
+    Some(format!("{}{}", origin, remove_dot_segments(&joined)))
+}
+
+fn scheme_of(link: &str) -> Option<&str> {
+    let (scheme, _) = link.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+// `/a/./b/../c?q` is `/a/c?q`
+fn remove_dot_segments(path: &str) -> String {
+    let query_at = path.find(['?', '#']).unwrap_or(path.len());
+    let (path, query) = path.split_at(query_at);
+    let mut segments: Vec<&str> = vec![];
+    let parts: Vec<&str> = path.split('/').skip(1).collect();
+    for (i, part) in parts.iter().enumerate() {
+        let is_last = i + 1 == parts.len();
+        match *part {
+            "." => {
+                if is_last {
+                    segments.push("");
+                }
+            }
+            ".." => {
+                segments.pop();
+                if is_last {
+                    segments.push("");
+                }
+            }
+            part => segments.push(part),
+        }
+    }
+    format!("/{}{}", segments.join("/"), query)
+}
+
+fn escape(text: &str, in_attribute: bool) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if in_attribute => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            // a bare `&` or an entity we do not know is kept as is
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    // entities are not decoded yet
+    Text(&'a str),
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    End(String),
+}
+
+// Good enough for the html of feeds, comments and doctypes are left out.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+        let is_end = rest[1..].starts_with('/');
+        let name_at = if is_end { 2 } else { 1 };
+        let name: String = rest[name_at..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        // `a < b` is text, not a tag
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            tokens.push(Token::Text(&rest[..1]));
+            rest = &rest[1..];
+            continue;
+        }
+        let inner = &rest[name_at + name.len()..];
+        let Some(end) = tag_end(inner) else {
+            // a tag cut in the middle is not text either
+            rest = "";
+            break;
+        };
+        let inside = &inner[..end];
+        rest = &inner[end + 1..];
+        if is_end {
+            tokens.push(Token::End(name));
+            continue;
+        }
+        let self_closing = inside.trim_end().ends_with('/');
+        if RAW_TEXT_TAGS.contains(&name.as_str()) && !self_closing {
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(at) => rest[at..].find('>').map_or("", |end| &rest[at + end + 1..]),
+                None => "",
+            };
+            tokens.push(Token::Start {
+                name: name.clone(),
+                attributes: vec![],
+                self_closing: false,
+            });
+            tokens.push(Token::End(name));
+            continue;
+        }
+        tokens.push(Token::Start {
+            name,
+            attributes: parse_attributes(inside),
+            self_closing,
+        });
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    tokens
+}
+
+// position of the `>` which ends the tag, `>` in quoted values does not
+fn tag_end(inner: &str) -> Option<usize> {
+    let mut quote = None;
+    for (at, c) in inner.char_indices() {
+        match (quote, c) {
+            (None, '>') => return Some(at),
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_attributes(inside: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    let mut rest = inside;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            break;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        if name_end == 0 {
+            // a stray `=`
+            rest = &rest[1..];
+            continue;
+        }
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let quoted = &after[1..];
+                        let end = quoted.find(quote).unwrap_or(quoted.len());
+                        rest = quoted.get(end + 1..).unwrap_or("");
+                        &quoted[..end]
+                    }
+                    _ => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        rest = &after[end..];
+                        &after[..end]
+                    }
+                }
+            }
+            None => "",
+        };
+        attributes.push((name, decode_entities(value)));
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Option<&str> = Some("https://aws.amazon.com/jp/about-aws/whats-new/");
+
+    #[test]
+    fn html_is_turned_into_text() {
+        let html = r#"<p>Amazon S3 &amp; Lambda&nbsp;now support it.<br>See the <a href="/docs">docs</a>.</p><script>alert("x")</script><!-- note -->1 &lt; 2 &#x2713;"#;
+
+        assert_eq!(
+            html_to_text(html),
+            "\nAmazon S3 & Lambda now support it.\nSee the docs.\n1 < 2 ✓"
+        );
+        assert_eq!(
+            plain_text(html),
+            "Amazon S3 & Lambda now support it.\nSee the docs.\n1 < 2 ✓"
+        );
+    }
+
+    #[test]
+    fn only_allowed_tags_and_attributes_are_kept() {
+        let html = r#"<div class="x"><p onclick="steal()">Now <b>faster</b>&nbsp;&amp; <span style="color:red">cheaper</span>.<br/></p><script>alert("<p>")</script><iframe src="https://evil.example.com"><p>fallback</p></iframe><img src="x.png" onerror="steal()"></div>"#;
+
+        assert_eq!(
+            sanitize_html(html, BASE),
+            r#"<p>Now <b>faster</b> &amp; cheaper.<br></p><img src="https://aws.amazon.com/jp/about-aws/whats-new/x.png">"#
+        );
+    }
+
+    #[test]
+    fn unsafe_links_are_dropped_and_tags_are_closed() {
+        let html = r#"<a href="javascript:alert(1)">click</a> <a href=" data:text/html,x" title='a "b"'>data</a><ul><li><em>open"#;
+
+        assert_eq!(
+            sanitize_html(html, BASE),
+            r#"<a>click</a> <a title="a &quot;b&quot;">data</a><ul><li><em>open</em></li></ul>"#
+        );
+        assert_eq!(
+            sanitize_html("a</p> &lt;b&gt; 1 < 2", BASE),
+            "a &lt;b&gt; 1 &lt; 2"
+        );
+    }
+
+    #[test]
+    fn relative_links_are_resolved_against_the_base() {
+        let html = r#"<a href="/blogs/aws/">blog</a>"#;
+
+        assert_eq!(
+            sanitize_html(html, BASE),
+            r#"<a href="https://aws.amazon.com/blogs/aws/" rel="noopener noreferrer">blog</a>"#
+        );
+        assert_eq!(sanitize_html(html, None), "<a>blog</a>");
+
+        let resolve = |link| resolve_link(link, BASE);
+        assert_eq!(
+            resolve("https://example.com/a").unwrap(),
+            "https://example.com/a"
+        );
+        assert_eq!(resolve("//example.com/a").unwrap(), "https://example.com/a");
+        assert_eq!(
+            resolve("../../ja/s3/?p=1#top").unwrap(),
+            "https://aws.amazon.com/jp/ja/s3/?p=1#top"
+        );
+        assert_eq!(
+            resolve("./2024/item.html").unwrap(),
+            "https://aws.amazon.com/jp/about-aws/whats-new/2024/item.html"
+        );
+        assert_eq!(
+            resolve("#top").unwrap(),
+            "https://aws.amazon.com/jp/about-aws/whats-new/#top"
+        );
+        assert_eq!(
+            resolve_link("docs", Some("https://example.com")).unwrap(),
+            "https://example.com/docs"
+        );
+        assert_eq!(
+            resolve("mailto:aws@example.com").unwrap(),
+            "mailto:aws@example.com"
+        );
+        assert!(resolve("javascript:alert(1)").is_none());
+        assert!(resolve_link("docs", Some("ftp://example.com/")).is_none());
+    }
+}
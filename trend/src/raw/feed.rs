@@ -0,0 +1,421 @@
+use std::fmt::Display;
+
+use super::{
+    atom::{AtomRawTrendCollector, AtomRawTrendCollectorError},
+    conditional::{self, FetchValidators},
+    json_feed::{JsonFeedRawTrendCollector, JsonFeedRawTrendCollectorError},
+    public,
+    rss::{RssRawTrendCollector, RssRawTrendCollectorError},
+    CollectedRawTrends, RawTrendCollector, Service,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedFormat {
+    Rss,
+    Rdf,
+    Atom,
+    JsonFeed,
+}
+impl FeedFormat {
+    // Only looks at the root of the document, the body is left to the parser of the format.
+    // A document of no known format is reported with what was found at its root.
+    pub fn detect(bytes: &[u8]) -> Result<Self, FeedRawTrendCollectorError> {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+        let detected = if text.is_empty() {
+            Err("empty document".to_string())
+        } else if text.starts_with('{') {
+            Self::detect_json(text)
+        } else {
+            Self::detect_xml(text)
+        };
+        detected.map_err(FeedRawTrendCollectorError::UnsupportedFormat)
+    }
+    pub fn to_str(&self) -> &str {
+        match self {
+            FeedFormat::Rss => "RSS 2.0",
+            FeedFormat::Rdf => "RSS 1.0 (RDF)",
+            FeedFormat::Atom => "Atom 1.0",
+            FeedFormat::JsonFeed => "JSON Feed",
+        }
+    }
+    fn detect_json(text: &str) -> Result<Self, String> {
+        let value: serde_json::Value =
+            serde_json::from_str(text).map_err(|_| "invalid JSON".to_string())?;
+        match value.get("version").and_then(|v| v.as_str()) {
+            Some(version) if version.starts_with("https://jsonfeed.org/version/") => {
+                Ok(FeedFormat::JsonFeed)
+            }
+            Some(version) => Err(format!("JSON with version {}", version)),
+            None => Err("JSON without version".to_string()),
+        }
+    }
+    fn detect_xml(text: &str) -> Result<Self, String> {
+        let broken = || "broken XML".to_string();
+        let mut rest = text;
+        loop {
+            rest = rest.trim_start();
+            if !rest.starts_with('<') {
+                return Err("not XML".to_string());
+            }
+            // skip xml declaration, processing instructions, comments and doctype
+            if let Some(after) = rest.strip_prefix("<?") {
+                rest = &after[after.find("?>").ok_or_else(broken)? + 2..];
+                continue;
+            }
+            if let Some(after) = rest.strip_prefix("<!--") {
+                rest = &after[after.find("-->").ok_or_else(broken)? + 3..];
+                continue;
+            }
+            if let Some(after) = rest.strip_prefix("<!") {
+                rest = &after[after.find('>').ok_or_else(broken)? + 1..];
+                continue;
+            }
+            let root = rest[1..]
+                .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .next()
+                .unwrap_or_default();
+            // root element may be prefixed like `atom:feed`
+            let local_name = root.rsplit(':').next().unwrap_or_default();
+            return match local_name {
+                "rss" => Ok(FeedFormat::Rss),
+                "RDF" => Ok(FeedFormat::Rdf),
+                "feed" => Ok(FeedFormat::Atom),
+                "" => Err(broken()),
+                _ => Err(root.to_string()),
+            };
+        }
+    }
+}
+impl std::str::FromStr for FeedFormat {
+    type Err = FeedFormatError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rss" => Ok(FeedFormat::Rss),
+            "rdf" => Ok(FeedFormat::Rdf),
+            "atom" => Ok(FeedFormat::Atom),
+            "json_feed" => Ok(FeedFormat::JsonFeed),
+            _ => Err(FeedFormatError::InvalidFormat(s.to_string())),
+        }
+    }
+}
+impl Display for FeedFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum FeedFormatError {
+    InvalidFormat(String),
+}
+impl Display for FeedFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeedFormatError::InvalidFormat(s) => write!(f, "InvalidFormat: {}", s),
+        }
+    }
+}
+impl std::error::Error for FeedFormatError {}
+
+pub struct FeedRawTrendCollector<B: AsRef<[u8]>> {
+    service: Service,
+    bytes: B,
+    format: Option<FeedFormat>,
+}
+impl<B: AsRef<[u8]>> FeedRawTrendCollector<B> {
+    pub fn new(service: Service, bytes: B) -> Self {
+        Self {
+            service,
+            bytes,
+            format: None,
+        }
+    }
+    // skip the detection when the format of the source is known
+    pub fn with_format(mut self, format: FeedFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+}
+#[derive(Debug)]
+pub enum FeedRawTrendCollectorError {
+    // what was found instead of a feed, e.g. the root element name
+    UnsupportedFormat(String),
+    // the format is the detected or configured one the document failed to parse as
+    RssError(FeedFormat, RssRawTrendCollectorError),
+    AtomError(FeedFormat, AtomRawTrendCollectorError),
+    JsonFeedError(FeedFormat, JsonFeedRawTrendCollectorError),
+}
+impl Display for FeedRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeedRawTrendCollectorError::UnsupportedFormat(s) => {
+                write!(f, "UnsupportedFormat: {}", s)
+            }
+            FeedRawTrendCollectorError::RssError(format, e) => write!(f, "{}: {}", format, e),
+            FeedRawTrendCollectorError::AtomError(format, e) => write!(f, "{}: {}", format, e),
+            FeedRawTrendCollectorError::JsonFeedError(format, e) => {
+                write!(f, "{}: {}", format, e)
+            }
+        }
+    }
+}
+impl std::error::Error for FeedRawTrendCollectorError {}
+
+impl<B: AsRef<[u8]>> RawTrendCollector for FeedRawTrendCollector<B> {
+    type Error = FeedRawTrendCollectorError;
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        let bytes = self.bytes.as_ref();
+        let format = match self.format {
+            Some(format) => format,
+            None => FeedFormat::detect(bytes)?,
+        };
+        match format {
+            // rss crate reads both RSS 2.0 and RSS 1.0 (RDF) documents
+            FeedFormat::Rss | FeedFormat::Rdf => {
+                RssRawTrendCollector::new(self.service.clone(), bytes)
+                    .collect()
+                    .await
+                    .map_err(|e| FeedRawTrendCollectorError::RssError(format, e))
+            }
+            FeedFormat::Atom => AtomRawTrendCollector::new(self.service.clone(), bytes)
+                .collect()
+                .await
+                .map_err(|e| FeedRawTrendCollectorError::AtomError(format, e)),
+            FeedFormat::JsonFeed => JsonFeedRawTrendCollector::new(self.service.clone(), bytes)
+                .collect()
+                .await
+                .map_err(|e| FeedRawTrendCollectorError::JsonFeedError(format, e)),
+        }
+    }
+}
+
+pub struct RemoteFeedRawTrendCollector {
+    url: String,
+    service: Service,
+    validators: Option<FetchValidators>,
+    format: Option<FeedFormat>,
+    client: reqwest::Client,
+}
+impl RemoteFeedRawTrendCollector {
+    pub fn new(url: impl Into<String>, service: Service) -> Self {
+        Self {
+            url: url.into(),
+            service,
+            validators: None,
+            format: None,
+            client: public::client(),
+        }
+    }
+    // fetch conditionally with the validators of the last fetch,
+    // a feed which is not modified since then has no trends
+    pub fn with_validators(mut self, validators: FetchValidators) -> Self {
+        self.validators = Some(validators);
+        self
+    }
+    pub fn with_format(mut self, format: FeedFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+    // the client only reaches public addresses by default, see `public`
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum RemoteFeedRawTrendCollectorError {
+    RequestError(reqwest::Error),
+    FeedError(FeedRawTrendCollectorError),
+}
+impl Display for RemoteFeedRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteFeedRawTrendCollectorError::RequestError(e) => {
+                write!(f, "RequestError: {}", e)
+            }
+            RemoteFeedRawTrendCollectorError::FeedError(e) => write!(f, "FeedError: {}", e),
+        }
+    }
+}
+impl std::error::Error for RemoteFeedRawTrendCollectorError {}
+
+impl RawTrendCollector for RemoteFeedRawTrendCollector {
+    type Error = RemoteFeedRawTrendCollectorError;
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        let Some(fetched) = conditional::fetch(&self.client, &self.url, self.validators.as_ref())
+            .await
+            .map_err(RemoteFeedRawTrendCollectorError::RequestError)?
+        else {
+            return Ok(CollectedRawTrends::not_modified());
+        };
+        let mut collector = FeedRawTrendCollector::new(self.service.clone(), fetched.bytes);
+        if let Some(format) = self.format {
+            collector = collector.with_format(format);
+        }
+        collector
+            .collect()
+            .await
+            .map_err(RemoteFeedRawTrendCollectorError::FeedError)
+            .map(|collected| collected.with_validators(fetched.validators))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = r#"
+<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0">
+  <channel>
+    <title>Example RSS Feed</title>
+    <link>http://www.example.com</link>
+    <description>This is an example of an RSS feed</description>
+    <item>
+      <title>Example Item 1</title>
+      <link>http://www.example.com/item1</link>
+      <pubDate>Sat, 22 Jun 2024 02:22:32 +0000</pubDate>
+    </item>
+  </channel>
+</rss>
+"#;
+    const RDF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- RSS 1.0 -->
+<rdf:RDF
+  xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+  xmlns="http://purl.org/rss/1.0/"
+  xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel rdf:about="http://www.example.com/">
+    <title>Example RDF Feed</title>
+    <link>http://www.example.com/</link>
+    <description>This is an example of an RSS 1.0 feed</description>
+  </channel>
+  <item rdf:about="http://www.example.com/item1">
+    <title>Example Item 1</title>
+    <link>http://www.example.com/item1</link>
+    <description>This is the description for example item 1.</description>
+    <dc:date>2024-06-22T02:22:32+09:00</dc:date>
+  </item>
+</rdf:RDF>
+"#;
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example Atom Feed</title>
+  <id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id>
+  <updated>2024-06-22T02:22:32Z</updated>
+  <entry>
+    <title>Example Entry 1</title>
+    <link href="http://www.example.com/entry1"/>
+    <id>http://www.example.com/entry1</id>
+    <updated>2024-06-22T02:22:32Z</updated>
+  </entry>
+</feed>
+"#;
+    const JSON_FEED: &str = r#"
+{
+  "version": "https://jsonfeed.org/version/1.1",
+  "title": "Example JSON Feed",
+  "items": [
+    {
+      "id": "1",
+      "title": "Example Item 1",
+      "url": "http://www.example.com/item1",
+      "date_published": "2024-06-22T02:22:32Z"
+    }
+  ]
+}
+"#;
+
+    #[test]
+    fn detect_feed_format() {
+        assert_eq!(FeedFormat::detect(RSS.as_bytes()).unwrap(), FeedFormat::Rss);
+        assert_eq!(FeedFormat::detect(RDF.as_bytes()).unwrap(), FeedFormat::Rdf);
+        assert_eq!(
+            FeedFormat::detect(ATOM.as_bytes()).unwrap(),
+            FeedFormat::Atom
+        );
+        assert_eq!(
+            FeedFormat::detect(JSON_FEED.as_bytes()).unwrap(),
+            FeedFormat::JsonFeed
+        );
+    }
+    #[test]
+    fn detect_reports_what_was_found() {
+        let unsupported = |bytes: &[u8]| match FeedFormat::detect(bytes) {
+            Err(FeedRawTrendCollectorError::UnsupportedFormat(s)) => s,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!(
+            unsupported(b"<!DOCTYPE html><html><body></body></html>"),
+            "html"
+        );
+        assert_eq!(
+            unsupported(br#"{"title": "not a feed"}"#),
+            "JSON without version"
+        );
+        assert_eq!(
+            unsupported(br#"{"version": "1.0"}"#),
+            "JSON with version 1.0"
+        );
+        assert_eq!(unsupported(b"plain text"), "not XML");
+    }
+    #[tokio::test]
+    async fn collect_rss_feed() {
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), RSS.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.latest().unwrap().title(), "Example Item 1");
+    }
+    #[tokio::test]
+    async fn collect_rdf_feed_with_dc_date() {
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), RDF.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.trends().len(), 1);
+        assert_eq!(
+            infos.latest().unwrap().link(),
+            "http://www.example.com/item1"
+        );
+    }
+    #[tokio::test]
+    async fn collect_atom_feed() {
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), ATOM.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.latest().unwrap().title(), "Example Entry 1");
+    }
+    #[tokio::test]
+    async fn collect_json_feed() {
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), JSON_FEED.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.latest().unwrap().title(), "Example Item 1");
+    }
+    #[tokio::test]
+    async fn collect_with_format_skips_detection() {
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), RSS.as_bytes())
+            .with_format(FeedFormat::Atom);
+        let err = collector.collect().await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            FeedRawTrendCollectorError::AtomError(FeedFormat::Atom, _)
+        ));
+        assert!(err.to_string().starts_with("Atom 1.0: "));
+    }
+    #[tokio::test]
+    async fn collect_unknown_body_should_fail() {
+        let collector =
+            FeedRawTrendCollector::new(Service::aws_updates(), "<html></html>".as_bytes());
+        let err = collector.collect().await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            FeedRawTrendCollectorError::UnsupportedFormat(ref s) if s == "html"
+        ));
+    }
+}
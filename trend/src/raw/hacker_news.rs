@@ -0,0 +1,386 @@
+use std::fmt::Display;
+
+use date::Date;
+use futures::StreamExt;
+
+use super::{
+    CollectedRawTrends, RawTrendCollector, RawTrendInfo, RawTrendInfoError, Service,
+    SkippedRawTrendInfo,
+};
+
+// The lists of stories the Hacker News API ranks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoryList {
+    Top,
+    Best,
+    New,
+}
+impl StoryList {
+    // the last segment of the url of the list
+    pub fn file_name(&self) -> &str {
+        match self {
+            StoryList::Top => "topstories.json",
+            StoryList::Best => "beststories.json",
+            StoryList::New => "newstories.json",
+        }
+    }
+}
+
+// Collects stories of a list through the Hacker News API,
+// https://github.com/HackerNews/API
+// The list only has ids, each story is fetched by itself, a few at a time.
+// Stories below the score or without any of the keywords are left out.
+pub struct HackerNewsRawTrendCollector {
+    base_url: String,
+    list: StoryList,
+    service: Service,
+    min_score: Option<u32>,
+    // lowercase, matched as whole words in the title, the url and the text
+    keywords: Vec<String>,
+    limit: usize,
+    client: reqwest::Client,
+}
+impl HackerNewsRawTrendCollector {
+    pub const BASE_URL: &'static str = "https://hacker-news.firebaseio.com/v0";
+    // the front page
+    const DEFAULT_LIMIT: usize = 30;
+    const CONCURRENCY: usize = 8;
+    pub fn new(list: StoryList, service: Service) -> Self {
+        Self {
+            base_url: Self::BASE_URL.to_string(),
+            list,
+            service,
+            min_score: None,
+            keywords: vec![],
+            limit: Self::DEFAULT_LIMIT,
+            client: reqwest::Client::new(),
+        }
+    }
+    // the collector of a list url like `https://hacker-news.firebaseio.com/v0/topstories.json`,
+    // `None` when the url is not a list of the API
+    pub fn from_url(url: &str, service: Service) -> Option<Self> {
+        let (base_url, file_name) = url.rsplit_once('/')?;
+        if !base_url.starts_with(Self::BASE_URL) {
+            return None;
+        }
+        let list = [StoryList::Top, StoryList::Best, StoryList::New]
+            .into_iter()
+            .find(|list| list.file_name() == file_name)?;
+        Some(Self::new(list, service).with_base_url(base_url))
+    }
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+    pub fn with_min_score(mut self, min_score: u32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+    pub fn with_keywords<S: AsRef<str>>(mut self, keywords: impl IntoIterator<Item = S>) -> Self {
+        self.keywords = keywords
+            .into_iter()
+            .map(|keyword| words(keyword.as_ref()))
+            .filter(|keyword| !keyword.trim().is_empty())
+            .collect();
+        self
+    }
+    // how many stories from the head of the list are fetched
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+    fn is_wanted(&self, story: &Item) -> bool {
+        if story.score.unwrap_or(0) < self.min_score.unwrap_or(0) {
+            return false;
+        }
+        if self.keywords.is_empty() {
+            return true;
+        }
+        let haystack = words(&format!(
+            "{} {} {}",
+            story.title.as_deref().unwrap_or_default(),
+            story.url.as_deref().unwrap_or_default(),
+            story.text.as_deref().unwrap_or_default()
+        ));
+        self.keywords
+            .iter()
+            .any(|keyword| haystack.contains(keyword.as_str()))
+    }
+    async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        url: String,
+    ) -> Result<T, HackerNewsRawTrendCollectorError> {
+        let bytes = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| HackerNewsRawTrendCollectorError::RequestError(e.to_string()))?
+            .bytes()
+            .await
+            .map_err(|e| HackerNewsRawTrendCollectorError::RequestError(e.to_string()))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| HackerNewsRawTrendCollectorError::JsonError(e.to_string()))
+    }
+    // `None` for a deleted item
+    async fn item(&self, id: u64) -> Result<Option<Item>, HackerNewsRawTrendCollectorError> {
+        self.get_json(format!("{}/item/{}.json", self.base_url, id))
+            .await
+    }
+}
+
+// ` word word `, lowercase and padded, so `contains` matches whole words only
+fn words(text: &str) -> String {
+    let words: Vec<String> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect();
+    format!(" {} ", words.join(" "))
+}
+
+impl RawTrendCollector for HackerNewsRawTrendCollector {
+    type Error = HackerNewsRawTrendCollectorError;
+    // fails only when the list can not be read, a story which can not be read is skipped
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        let ids: Vec<u64> = self
+            .get_json(format!("{}/{}", self.base_url, self.list.file_name()))
+            .await?;
+        let fetching: Vec<_> = ids
+            .iter()
+            .take(self.limit)
+            .map(|id| self.item(*id))
+            .collect();
+        let items: Vec<_> = futures::stream::iter(fetching)
+            .buffered(Self::CONCURRENCY)
+            .collect()
+            .await;
+        Ok(CollectedRawTrends::from_results(
+            ids.iter()
+                .zip(items)
+                .enumerate()
+                .filter_map(|(index, (id, item))| {
+                    let id = id.to_string();
+                    let discussion = discussion_link(&id);
+                    let skipped = |reason| {
+                        SkippedRawTrendInfo::new(index, Some(&id), Some(&discussion), reason)
+                    };
+                    match item {
+                        Ok(Some(item)) if item.is_story() && self.is_wanted(&item) => {
+                            Some(item_to_trend(item, self.service.clone()).map_err(skipped))
+                        }
+                        // jobs, polls, dead stories and the ones filtered out
+                        Ok(_) => None,
+                        Err(e) => Some(Err(skipped(RawTrendInfoError::Unreadable(e.to_string())))),
+                    }
+                }),
+        ))
+    }
+}
+
+// https://github.com/HackerNews/API#items
+// only the fields needed to build RawTrendInfo are read
+#[derive(serde::Deserialize)]
+struct Item {
+    id: u64,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    by: Option<String>,
+    // unix time
+    time: Option<i64>,
+    title: Option<String>,
+    // left out for Ask HN, which has text instead
+    url: Option<String>,
+    // html
+    text: Option<String>,
+    score: Option<u32>,
+    // number of comments
+    descendants: Option<u32>,
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
+    dead: bool,
+}
+impl Item {
+    fn is_story(&self) -> bool {
+        self.kind.as_deref() == Some("story") && !self.deleted && !self.dead
+    }
+}
+
+fn discussion_link(id: &str) -> String {
+    format!("https://news.ycombinator.com/item?id={}", id)
+}
+
+fn item_to_trend(item: Item, from: Service) -> Result<RawTrendInfo, RawTrendInfoError> {
+    let time = item.time.ok_or(RawTrendInfoError::MissingDate)?;
+    let created_at = Date::from_unix_timestamp(time)
+        .map_err(|_| RawTrendInfoError::InvalidDate(time.to_string()))?;
+    let discussion = discussion_link(&item.id.to_string());
+    // the points and the comments are where the story stood when it was collected
+    let mut desc = format!(
+        "<p>{} points by {} | <a href=\"{}\">{} comments</a></p>",
+        item.score.unwrap_or(0),
+        item.by.as_deref().unwrap_or("unknown"),
+        discussion,
+        item.descendants.unwrap_or(0)
+    );
+    if let Some(text) = &item.text {
+        desc.push_str(text);
+    }
+    Ok(RawTrendInfo::new(
+        item.title.unwrap_or_default(),
+        item.url.unwrap_or(discussion),
+        desc,
+        from,
+        created_at,
+    ))
+}
+
+#[derive(Debug)]
+pub enum HackerNewsRawTrendCollectorError {
+    RequestError(String),
+    JsonError(String),
+}
+impl Display for HackerNewsRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HackerNewsRawTrendCollectorError::RequestError(s) => write!(f, "RequestError: {}", s),
+            HackerNewsRawTrendCollectorError::JsonError(s) => write!(f, "JsonError: {}", s),
+        }
+    }
+}
+impl std::error::Error for HackerNewsRawTrendCollectorError {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{extract::Path, http::StatusCode, routing::get, Router};
+
+    use super::*;
+
+    // recorded items, 5 is deleted and 7 is missing
+    fn recorded(id: &str) -> Option<&'static str> {
+        match id {
+            "1" => Some(
+                r#"{"id":1,"type":"story","by":"jeff","time":1719022952,"title":"AWS Lambda now supports SnapStart for Python","url":"https://aws.amazon.com/blogs/aws/lambda-snapstart-python/","score":230,"descendants":87}"#,
+            ),
+            "2" => Some(
+                r#"{"id":2,"type":"story","by":"newbie","time":1719022952,"title":"My AWS bill","url":"https://example.com/bill","score":12,"descendants":3}"#,
+            ),
+            "3" => Some(
+                r#"{"id":3,"type":"job","by":"yc","time":1719022952,"title":"Startup is hiring AWS engineers","url":"https://example.com/jobs","score":1}"#,
+            ),
+            "4" => Some(
+                r#"{"id":4,"type":"story","by":"physicist","time":1719022952,"title":"Laws of physics","url":"https://example.com/laws","score":300,"descendants":120}"#,
+            ),
+            "5" => Some("null"),
+            "6" => Some(
+                r#"{"id":6,"type":"story","by":"asker","time":1718936552,"title":"Ask HN: How do you back up S3?","text":"<p>We keep <i>petabytes</i> in S3.","score":150,"descendants":42}"#,
+            ),
+            _ => None,
+        }
+    }
+
+    // stands in for the Hacker News API
+    async fn serve_api(requests: Arc<AtomicUsize>) -> String {
+        let app = Router::new()
+            .route(
+                "/v0/topstories.json",
+                get(|| async { "[1, 2, 3, 4, 5, 6, 7]" }),
+            )
+            .route(
+                "/v0/item/:file",
+                get(move |Path(file): Path<String>| {
+                    let requests = requests.clone();
+                    async move {
+                        requests.fetch_add(1, Ordering::SeqCst);
+                        match recorded(file.trim_end_matches(".json")) {
+                            Some(body) => (StatusCode::OK, body),
+                            None => (StatusCode::NOT_FOUND, "not found"),
+                        }
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/v0", addr)
+    }
+
+    #[tokio::test]
+    async fn stories_over_the_score_with_keywords_are_collected() {
+        let base_url = serve_api(Arc::new(AtomicUsize::new(0))).await;
+        let collector = HackerNewsRawTrendCollector::new(StoryList::Top, Service::hacker_news())
+            .with_base_url(base_url)
+            .with_min_score(100)
+            .with_keywords(["AWS", "s3"]);
+
+        let infos = collector.collect().await.unwrap();
+
+        let titles: Vec<_> = infos.trends().iter().map(|info| info.title()).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "AWS Lambda now supports SnapStart for Python",
+                "Ask HN: How do you back up S3?"
+            ]
+        );
+        let lambda = &infos.trends()[0];
+        assert_eq!(
+            lambda.link(),
+            "https://aws.amazon.com/blogs/aws/lambda-snapstart-python/"
+        );
+        assert_eq!(lambda.from(), "hacker_news");
+        assert_eq!(lambda.desc_text(), "230 points by jeff | 87 comments");
+        let ask = &infos.trends()[1];
+        assert_eq!(ask.link(), "https://news.ycombinator.com/item?id=6");
+        assert_eq!(
+            ask.desc_text(),
+            "150 points by asker | 42 comments\nWe keep petabytes in S3."
+        );
+        assert_eq!(infos.skipped().len(), 1);
+        assert_eq!(infos.skipped()[0].guid(), Some("7"));
+    }
+
+    #[tokio::test]
+    async fn only_the_head_of_the_list_is_fetched() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let base_url = serve_api(requests.clone()).await;
+        let collector = HackerNewsRawTrendCollector::new(StoryList::Top, Service::hacker_news())
+            .with_base_url(base_url)
+            .with_limit(2);
+
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(infos.trends().len(), 2);
+    }
+
+    #[test]
+    fn collector_is_made_from_list_url() {
+        let collector = HackerNewsRawTrendCollector::from_url(
+            "https://hacker-news.firebaseio.com/v0/beststories.json",
+            Service::hacker_news(),
+        )
+        .unwrap();
+
+        assert_eq!(collector.list, StoryList::Best);
+        assert_eq!(collector.base_url, HackerNewsRawTrendCollector::BASE_URL);
+        assert!(HackerNewsRawTrendCollector::from_url(
+            "https://hacker-news.firebaseio.com/v0/item/1.json",
+            Service::hacker_news()
+        )
+        .is_none());
+        assert!(HackerNewsRawTrendCollector::from_url(
+            "https://example.com/v0/topstories.json",
+            Service::hacker_news()
+        )
+        .is_none());
+    }
+}
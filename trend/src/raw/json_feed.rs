@@ -0,0 +1,181 @@
+use date::Date;
+
+use super::{
+    CollectedRawTrends, RawTrendCollector, RawTrendInfo, RawTrendInfoError, Service,
+    SkippedRawTrendInfo,
+};
+
+pub struct JsonFeedRawTrendCollector<B: AsRef<[u8]>> {
+    service: Service,
+    bytes: B,
+}
+impl<B: AsRef<[u8]>> JsonFeedRawTrendCollector<B> {
+    pub fn new(service: Service, bytes: B) -> Self {
+        Self { service, bytes }
+    }
+    async fn to_feed(&self) -> Result<JsonFeed, JsonFeedRawTrendCollectorError> {
+        let bytes = self.bytes.as_ref();
+        serde_json::from_slice(bytes).map_err(JsonFeedRawTrendCollectorError::JsonError)
+    }
+}
+#[derive(Debug)]
+pub enum JsonFeedRawTrendCollectorError {
+    JsonError(serde_json::Error),
+}
+impl std::fmt::Display for JsonFeedRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonFeedRawTrendCollectorError::JsonError(e) => write!(f, "JsonError: {}", e),
+        }
+    }
+}
+impl std::error::Error for JsonFeedRawTrendCollectorError {}
+
+impl<B: AsRef<[u8]>> RawTrendCollector for JsonFeedRawTrendCollector<B> {
+    type Error = JsonFeedRawTrendCollectorError;
+    async fn collect(&self) -> Result<CollectedRawTrends, JsonFeedRawTrendCollectorError> {
+        let feed = self.to_feed().await?;
+        let base_link = feed.home_page_url.unwrap_or_default();
+        Ok(CollectedRawTrends::from_results(
+            feed.items.into_iter().enumerate().map(|(index, item)| {
+                let id = item.id.as_ref().map(|id| match id {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                });
+                let url = item.url.clone();
+                item_to_trend(item, self.service.clone())
+                    .map(|info| info.with_base_link(&base_link))
+                    .map_err(|reason| {
+                        SkippedRawTrendInfo::new(index, id.as_deref(), url.as_deref(), reason)
+                    })
+            }),
+        ))
+    }
+}
+
+// https://www.jsonfeed.org/version/1.1/
+// only the fields needed to build RawTrendInfo are read
+#[derive(serde::Deserialize)]
+struct JsonFeed {
+    home_page_url: Option<String>,
+    #[serde(default)]
+    items: Vec<JsonFeedItem>,
+}
+#[derive(serde::Deserialize)]
+struct JsonFeedItem {
+    // spec says string, but some feeds write numbers
+    id: Option<serde_json::Value>,
+    title: Option<String>,
+    url: Option<String>,
+    content_text: Option<String>,
+    summary: Option<String>,
+    date_published: Option<String>,
+    date_modified: Option<String>,
+}
+
+fn item_to_trend(item: JsonFeedItem, from: Service) -> Result<RawTrendInfo, RawTrendInfoError> {
+    let title = item.title.unwrap_or_default();
+    let link = item.url.unwrap_or_default();
+    let desc = item.content_text.or(item.summary).unwrap_or_default();
+    let date_published = item
+        .date_published
+        .or(item.date_modified)
+        .ok_or(RawTrendInfoError::MissingDate)?;
+    let created_at = Date::parse_feed_date(&date_published)
+        .map_err(|_| RawTrendInfoError::InvalidDate(date_published.clone()))?;
+    Ok(RawTrendInfo::new(title, link, desc, from, created_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Item 1 is written bottom of the items, but this item is newer than others.
+    const DUMMY: &str = r#"
+{
+  "version": "https://jsonfeed.org/version/1.1",
+  "title": "Example JSON Feed",
+  "home_page_url": "http://www.example.com/",
+  "items": [
+    {
+      "id": "2",
+      "title": "Example Item 2",
+      "url": "http://www.example.com/item2",
+      "content_text": "This is the text for example item 2.",
+      "date_published": "2024-06-21T02:22:32Z"
+    },
+    {
+      "id": "3",
+      "title": "Example Item 3",
+      "url": "http://www.example.com/item3",
+      "content_html": "<p>This is the html for example item 3.</p>",
+      "summary": "This is the summary for example item 3.",
+      "date_published": "2024-06-21T02:22:32+09:00"
+    },
+    {
+      "id": "1",
+      "title": "Example Item 1",
+      "url": "http://www.example.com/item1",
+      "content_text": "This is the text for example item 1.",
+      "date_published": "2024-06-22T02:22:32Z"
+    }
+  ]
+}
+"#;
+    #[tokio::test]
+    async fn collect_all_json_feed_item() {
+        let collector =
+            JsonFeedRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.trends().len(), 3);
+    }
+    #[tokio::test]
+    async fn collect_json_feed_to_trend_should_sorted_by_date_published() {
+        let collector =
+            JsonFeedRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
+        let infos = collector.collect().await.unwrap();
+
+        let latest = infos.latest().unwrap();
+        assert_eq!(latest.title(), "Example Item 1");
+        assert_eq!(latest.link(), "http://www.example.com/item1");
+        assert_eq!(latest.desc(), "This is the text for example item 1.");
+    }
+    #[tokio::test]
+    async fn collect_json_feed_item_without_content_text_uses_summary() {
+        let collector =
+            JsonFeedRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
+        let infos = collector.collect().await.unwrap();
+
+        let item3 = infos
+            .trends()
+            .iter()
+            .find(|info| info.title() == "Example Item 3")
+            .unwrap();
+        assert_eq!(item3.desc(), "This is the summary for example item 3.");
+    }
+    #[tokio::test]
+    async fn collect_json_feed_reports_skipped_item() {
+        let json = r#"
+{
+  "version": "https://jsonfeed.org/version/1.1",
+  "title": "Example JSON Feed",
+  "items": [
+    { "id": "1", "title": "Example Item 1", "date_published": "2024-06-22T02:22:32Z" },
+    { "id": 2, "url": "http://www.example.com/item2", "date_published": "someday" }
+  ]
+}
+"#;
+        let collector = JsonFeedRawTrendCollector::new(Service::aws_updates(), json.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        let skipped = &infos.skipped()[0];
+        assert_eq!(skipped.index(), 1);
+        assert_eq!(skipped.guid(), Some("2"));
+        assert_eq!(skipped.link(), Some("http://www.example.com/item2"));
+        assert!(matches!(
+            skipped.reason(),
+            RawTrendInfoError::InvalidDate(_)
+        ));
+    }
+}
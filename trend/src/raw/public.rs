@@ -0,0 +1,159 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, OnceLock},
+};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+// Urls of feeds are given by users, so fetching them must not reach the network of the server,
+// e.g. an admin api on localhost or the metadata endpoint of the cloud.
+// The client resolves hosts to public addresses only and follows redirects to public hosts only,
+// which also covers a host which resolves to a public address when added and to a private one later.
+pub fn client() -> reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::Client::builder()
+                .dns_resolver(Arc::new(PublicResolver))
+                .redirect(reqwest::redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= 10 {
+                        attempt.error("too many redirects")
+                    } else if literal_ip(attempt.url().host_str()).is_some_and(|ip| !is_public(ip))
+                    {
+                        let host = attempt.url().host_str().unwrap_or_default().to_string();
+                        attempt.error(NotPublicError(host))
+                    } else {
+                        attempt.follow()
+                    }
+                }))
+                .build()
+                .expect("failed to build the http client")
+        })
+        .clone()
+}
+
+// Whether every address of the host is public, resolving it when it is a name.
+pub async fn is_public_host(host: &str) -> bool {
+    if let Some(ip) = literal_ip(Some(host)) {
+        return is_public(ip);
+    }
+    match tokio::net::lookup_host((host, 80)).await {
+        Ok(addrs) => {
+            let addrs: Vec<_> = addrs.collect();
+            !addrs.is_empty() && addrs.iter().all(|addr| is_public(addr.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+// the address of a host written as an ip, like `127.0.0.1` or `[::1]`
+pub fn literal_ip(host: Option<&str>) -> Option<IpAddr> {
+    host?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // this network, shared address space of carriers, protocol assignments,
+        // benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // unique local, link local and documentation
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+struct PublicResolver;
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(NotPublicError(name.as_str().to_string()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct NotPublicError(String);
+impl std::fmt::Display for NotPublicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NotPublic: {}", self.0)
+    }
+}
+impl std::error::Error for NotPublicError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_addresses_are_public() {
+        for ip in ["93.184.215.14", "2606:2800:21f:cb07:6820:80da:af6b:8b2c"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+    #[tokio::test]
+    async fn client_does_not_connect_to_local_hosts() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let result = client()
+            .get(format!("http://localhost:{}/", port))
+            .send()
+            .await;
+
+        assert!(result.is_err());
+        assert!(!is_public_host("localhost").await);
+        assert!(!is_public_host("[::1]").await);
+    }
+}
@@ -1,8 +1,12 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
 use date::Date;
 
-use super::{CollectedRawTrends, RawTrendCollector, RawTrendInfo, RawTrendInfoError, Service};
+use super::{
+    conditional::{self, FetchValidators},
+    public, CollectedRawTrends, RawTrendCollector, RawTrendInfo, RawTrendInfoError, Service,
+    SkippedRawTrendInfo,
+};
 
 pub struct RssRawTrendCollector<B: AsRef<[u8]>> {
     service: Service,
@@ -14,7 +18,7 @@ impl<B: AsRef<[u8]>> RssRawTrendCollector<B> {
     }
     async fn to_channel(&self) -> Result<rss::Channel, RssRawTrendCollectorError> {
         let bytes = self.bytes.as_ref();
-        rss::Channel::read_from(bytes).map_err(|e| RssRawTrendCollectorError::RssError(e))
+        rss::Channel::read_from(bytes).map_err(RssRawTrendCollectorError::RssError)
     }
 }
 #[derive(Debug)]
@@ -24,7 +28,7 @@ pub enum RssRawTrendCollectorError {
 impl std::fmt::Display for RssRawTrendCollectorError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RssRawTrendCollectorError::RssError(e) => write!(f, "RssError: {:?}", e),
+            RssRawTrendCollectorError::RssError(e) => write!(f, "RssError: {}", e),
         }
     }
 }
@@ -34,34 +38,76 @@ impl<B: AsRef<[u8]>> RawTrendCollector for RssRawTrendCollector<B> {
     type Error = RssRawTrendCollectorError;
     async fn collect(&self) -> Result<CollectedRawTrends, RssRawTrendCollectorError> {
         let channel = self.to_channel().await?;
-        Ok(CollectedRawTrends::new(
-            channel
-                .items()
-                .iter()
-                .filter_map(|item| item_to_trend(item, self.service.clone()).ok())
-                .collect(),
-        ))
+        // items without any date are regarded as published when the channel was built
+        let fallback_date = channel.last_build_date().or(channel.pub_date());
+        let base_link = channel.link();
+        // <ttl> is written in minutes, a feed can not have us wait longer than a week
+        const MAX_TTL_MINUTES: u64 = 7 * 24 * 60;
+        let ttl = channel
+            .ttl()
+            .and_then(|ttl| ttl.trim().parse::<u64>().ok())
+            .map(|minutes| Duration::from_secs(minutes.min(MAX_TTL_MINUTES) * 60));
+        Ok(
+            CollectedRawTrends::from_results(channel.items().iter().enumerate().map(
+                |(index, item)| {
+                    item_to_trend(item, self.service.clone(), fallback_date)
+                        .map(|info| info.with_base_link(base_link))
+                        .map_err(|reason| {
+                            SkippedRawTrendInfo::new(
+                                index,
+                                item.guid().map(|guid| guid.value()),
+                                item.link(),
+                                reason,
+                            )
+                        })
+                },
+            ))
+            .with_ttl(ttl),
+        )
     }
 }
 
-fn item_to_trend(item: &rss::Item, from: Service) -> Result<RawTrendInfo, RawTrendInfoError> {
-    const DATE_FORMAT: &'static str = "%a, %d %b %Y %H:%M:%S %z";
+fn item_to_trend(
+    item: &rss::Item,
+    from: Service,
+    fallback_date: Option<&str>,
+) -> Result<RawTrendInfo, RawTrendInfoError> {
     let title = item.title().unwrap_or_default().to_string();
     let link = item.link().unwrap_or_default().to_string();
     let desc = item.description().unwrap_or_default().to_string();
-    let pub_date = item.pub_date().unwrap_or_default();
-    let created_at = Date::parse_from_str(pub_date, DATE_FORMAT)
-        .map_err(|_| RawTrendInfoError::InvalidDate(pub_date.to_string()))?;
+    // RSS 1.0 (RDF) items have no pubDate, they carry a dc:date instead
+    let dc_date = item
+        .dublin_core_ext()
+        .and_then(|dc| dc.dates().first())
+        .map(|date| date.as_str());
+    let date = item
+        .pub_date()
+        .or(dc_date)
+        .or(fallback_date)
+        .ok_or(RawTrendInfoError::MissingDate)?;
+    let created_at = Date::parse_feed_date(date)
+        .map_err(|_| RawTrendInfoError::InvalidDate(date.to_string()))?;
     Ok(RawTrendInfo::new(title, link, desc, from, created_at))
 }
 
 pub struct RemoteRssRawTrendCollector {
-    url: &'static str,
+    url: String,
     service: Service,
+    validators: Option<FetchValidators>,
 }
 impl RemoteRssRawTrendCollector {
-    pub fn new(url: &'static str, service: Service) -> Self {
-        Self { url, service }
+    pub fn new(url: impl Into<String>, service: Service) -> Self {
+        Self {
+            url: url.into(),
+            service,
+            validators: None,
+        }
+    }
+    // fetch conditionally with the validators of the last fetch,
+    // a feed which is not modified since then has no trends
+    pub fn with_validators(mut self, validators: FetchValidators) -> Self {
+        self.validators = Some(validators);
+        self
     }
     pub fn aws_updates() -> Self {
         Self::new(
@@ -79,8 +125,8 @@ pub enum RemoteRssRawTrendCollectorError {
 impl Display for RemoteRssRawTrendCollectorError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RemoteRssRawTrendCollectorError::RequestError(e) => write!(f, "RequestError: {:?}", e),
-            RemoteRssRawTrendCollectorError::RssError(e) => write!(f, "RssError: {:?}", e),
+            RemoteRssRawTrendCollectorError::RequestError(e) => write!(f, "RequestError: {}", e),
+            RemoteRssRawTrendCollectorError::RssError(e) => write!(f, "RssError: {}", e),
         }
     }
 }
@@ -89,17 +135,19 @@ impl std::error::Error for RemoteRssRawTrendCollectorError {}
 impl RawTrendCollector for RemoteRssRawTrendCollector {
     type Error = RemoteRssRawTrendCollectorError;
     async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
-        let bytes = reqwest::get(self.url)
-            .await
-            .map_err(|e| RemoteRssRawTrendCollectorError::RequestError(e))?
-            .bytes()
-            .await
-            .map_err(|e| RemoteRssRawTrendCollectorError::RequestError(e))?;
-        let collector = RssRawTrendCollector::new(self.service.clone(), bytes);
+        let Some(fetched) =
+            conditional::fetch(&public::client(), &self.url, self.validators.as_ref())
+                .await
+                .map_err(RemoteRssRawTrendCollectorError::RequestError)?
+        else {
+            return Ok(CollectedRawTrends::not_modified());
+        };
+        let collector = RssRawTrendCollector::new(self.service.clone(), fetched.bytes);
         collector
             .collect()
             .await
-            .map_err(|e| RemoteRssRawTrendCollectorError::RssError(e))
+            .map_err(RemoteRssRawTrendCollectorError::RssError)
+            .map(|collected| collected.with_validators(fetched.validators))
     }
 }
 
@@ -110,7 +158,7 @@ mod tests {
     use super::*;
 
     // Example 1 is written bottom of the items, but this item earlier than others.
-    const DUMMY: &'static str = r#"
+    const DUMMY: &str = r#"
 <?xml version="1.0" encoding="UTF-8" ?>
 <rss version="2.0">
   <channel>
@@ -160,6 +208,22 @@ mod tests {
         assert_eq!(infos.trends().len(), 3);
     }
     #[tokio::test]
+    async fn collect_rss_ttl() {
+        let collector =
+            RssRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.ttl(), Some(Duration::from_secs(1800 * 60)));
+    }
+    #[tokio::test]
+    async fn collect_rss_with_huge_ttl_waits_a_week_at_most() {
+        let rss = DUMMY.replace("<ttl>1800</ttl>", &format!("<ttl>{}</ttl>", u64::MAX));
+        let collector = RssRawTrendCollector::new(Service::aws_updates(), rss.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(infos.ttl(), Some(Duration::from_secs(7 * 24 * 60 * 60)));
+    }
+    #[tokio::test]
     async fn collect_rss_to_trend_should_sorted_by_pub_date() {
         let collector =
             RssRawTrendCollector::new(Service::aws_updates(), DUMMY.as_bytes().to_vec());
@@ -179,6 +243,90 @@ mod tests {
         );
     }
     #[tokio::test]
+    async fn collect_rss_with_loose_pub_date() {
+        let rss = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0">
+  <channel>
+    <title>Example RSS Feed</title>
+    <lastBuildDate>Sun, 23 Jun 2024 02:22:32 GMT</lastBuildDate>
+    <item>
+      <title>Named Zone</title>
+      <pubDate>Fri, 21 Jun 2024 02:22:32 PDT</pubDate>
+    </item>
+    <item>
+      <title>No Seconds</title>
+      <pubDate>Sat, 22 Jun 2024 02:22 +0000</pubDate>
+    </item>
+    <item>
+      <title>No Date</title>
+    </item>
+    <item>
+      <title>Broken Date</title>
+      <pubDate>yesterday</pubDate>
+    </item>
+  </channel>
+</rss>"#;
+        let collector = RssRawTrendCollector::new(Service::aws_updates(), rss.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        let titles: Vec<_> = infos.trends().iter().map(|info| info.title()).collect();
+        // item without pubDate falls back to lastBuildDate
+        assert_eq!(titles, vec!["No Date", "No Seconds", "Named Zone"]);
+        assert_eq!(infos.skipped().len(), 1);
+        assert!(matches!(
+            infos.skipped()[0].reason(),
+            RawTrendInfoError::InvalidDate(date) if date == "yesterday"
+        ));
+    }
+    #[tokio::test]
+    async fn collect_rss_item_without_any_date_is_skipped() {
+        let rss = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0">
+  <channel>
+    <title>Example RSS Feed</title>
+    <item>
+      <title>No Date</title>
+      <link>http://www.example.com/item1</link>
+      <guid>item1</guid>
+    </item>
+  </channel>
+</rss>"#;
+        let collector = RssRawTrendCollector::new(Service::aws_updates(), rss.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        assert!(infos.is_all_skipped());
+        let skipped = &infos.skipped()[0];
+        assert_eq!(skipped.index(), 0);
+        assert_eq!(skipped.guid(), Some("item1"));
+        assert_eq!(skipped.link(), Some("http://www.example.com/item1"));
+        assert!(matches!(skipped.reason(), RawTrendInfoError::MissingDate));
+    }
+    #[tokio::test]
+    async fn collect_rss_description_as_safe_html_and_text() {
+        let rss = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0">
+  <channel>
+    <title>Example RSS Feed</title>
+    <link>http://www.example.com/news/</link>
+    <item>
+      <title>Relative Link</title>
+      <link>http://www.example.com/news/item1</link>
+      <description><![CDATA[<p onclick="x()">See <a href="docs/item1.html">the docs</a>.</p><script>x()</script><p>Thanks!</p>]]></description>
+      <pubDate>Sat, 22 Jun 2024 02:22:32 +0000</pubDate>
+    </item>
+  </channel>
+</rss>"#;
+        let collector = RssRawTrendCollector::new(Service::aws_updates(), rss.as_bytes());
+        let infos = collector.collect().await.unwrap();
+
+        let latest = infos.latest().unwrap();
+        assert_eq!(
+            latest.desc_html(),
+            r#"<p>See <a href="http://www.example.com/news/docs/item1.html" rel="noopener noreferrer">the docs</a>.</p><p>Thanks!</p>"#
+        );
+        assert_eq!(latest.desc_text(), "See the docs.\nThanks!");
+    }
+    #[tokio::test]
     async fn collect_aws_rss_to_trend() {
         let mut reader =
             tokio::io::BufReader::new(tokio::fs::File::open("../tests/aws.rss").await.unwrap());
@@ -0,0 +1,608 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use date::Date;
+use reqwest::{header::HeaderMap, StatusCode};
+
+use super::{
+    description, CollectedRawTrends, RawTrendCollector, RawTrendInfo, RawTrendInfoError, Service,
+    SkippedRawTrendInfo,
+};
+
+// Collects recent posts of accounts and of search queries through the X API v2.
+// Every account or query is a search, so they share the rate limit of the search endpoint.
+// When the limit is used up, nothing is requested until it is reset,
+// and the trends collected so far carry the wait as their ttl.
+pub struct XRawTrendCollector {
+    base_url: String,
+    bearer_token: String,
+    accounts: Vec<String>,
+    queries: Vec<String>,
+    max_results: u32,
+    client: reqwest::Client,
+    limited_until: Mutex<Option<SystemTime>>,
+}
+impl XRawTrendCollector {
+    const DEFAULT_BASE_URL: &'static str = "https://api.x.com/2";
+    // the search endpoint answers between 10 and 100 posts
+    const DEFAULT_MAX_RESULTS: u32 = 10;
+    // used when a `429 Too Many Requests` does not say when the limit is reset
+    const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(15 * 60);
+    const MAX_TITLE_CHARS: usize = 80;
+    pub fn new(bearer_token: impl Into<String>) -> Self {
+        Self {
+            base_url: Self::DEFAULT_BASE_URL.to_string(),
+            bearer_token: bearer_token.into(),
+            accounts: vec![],
+            queries: vec![],
+            max_results: Self::DEFAULT_MAX_RESULTS,
+            client: reqwest::Client::new(),
+            limited_until: Mutex::new(None),
+        }
+    }
+    // posts of the account, the name without `@`
+    pub fn with_account(mut self, account: impl Into<String>) -> Self {
+        self.accounts
+            .push(account.into().trim_start_matches('@').to_string());
+        self
+    }
+    // https://docs.x.com/x-api/posts/search/integrate/build-a-query
+    pub fn with_query(mut self, query: impl Into<String>) -> Self {
+        self.queries.push(query.into());
+        self
+    }
+    pub fn with_max_results(mut self, max_results: u32) -> Self {
+        self.max_results = max_results.clamp(10, 100);
+        self
+    }
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+    // config file is chosen by its extension, `.toml` or `.json`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, XConfigError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(XConfigError::IoError)?;
+        let config: XConfig = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content).map_err(XConfigError::TomlError)?,
+            Some("json") => serde_json::from_str(&content).map_err(XConfigError::JsonError)?,
+            _ => return Err(XConfigError::UnknownExtension(path.display().to_string())),
+        };
+        config.try_into()
+    }
+    // the searches made on each collection
+    fn searches(&self) -> impl Iterator<Item = String> + '_ {
+        self.accounts
+            .iter()
+            .map(|account| format!("from:{}", account))
+            .chain(self.queries.iter().cloned())
+    }
+    // how long to wait until the rate limit is reset, `None` when requests can be made
+    fn rate_limit_wait(&self) -> Option<Duration> {
+        let limited_until = *self.limited_until.lock().unwrap();
+        limited_until.and_then(|until| until.duration_since(SystemTime::now()).ok())
+    }
+    fn update_rate_limit(&self, headers: &HeaderMap, status: StatusCode) {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+        // a reset too far to be a time falls back to the default wait
+        let reset = header("x-rate-limit-reset")
+            .and_then(|reset| UNIX_EPOCH.checked_add(Duration::from_secs(reset)));
+        let used_up =
+            status == StatusCode::TOO_MANY_REQUESTS || header("x-rate-limit-remaining") == Some(0);
+        let mut limited_until = self.limited_until.lock().unwrap();
+        *limited_until = match (used_up, reset) {
+            (false, _) => None,
+            (true, Some(reset)) => Some(reset),
+            (true, None) => Some(SystemTime::now() + Self::DEFAULT_RATE_LIMIT_WAIT),
+        };
+    }
+    async fn search(&self, query: &str) -> Result<CollectedRawTrends, XRawTrendCollectorError> {
+        if let Some(wait) = self.rate_limit_wait() {
+            return Err(XRawTrendCollectorError::RateLimited(wait));
+        }
+        let response = self
+            .client
+            .get(format!("{}/tweets/search/recent", self.base_url))
+            .bearer_auth(&self.bearer_token)
+            .query(&[
+                ("query", query),
+                ("max_results", &self.max_results.to_string()),
+                ("tweet.fields", "created_at,author_id"),
+                ("expansions", "author_id"),
+                ("user.fields", "username"),
+            ])
+            .send()
+            .await
+            .map_err(|e| XRawTrendCollectorError::RequestError(e.to_string()))?;
+        let status = response.status();
+        self.update_rate_limit(response.headers(), status);
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(XRawTrendCollectorError::RateLimited(
+                self.rate_limit_wait().unwrap_or_default(),
+            ));
+        }
+        if !status.is_success() {
+            return Err(XRawTrendCollectorError::StatusError(status.as_u16()));
+        }
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| XRawTrendCollectorError::RequestError(e.to_string()))?;
+        let search: SearchResponse = serde_json::from_slice(&bytes)
+            .map_err(|e| XRawTrendCollectorError::JsonError(e.to_string()))?;
+        Ok(search.into_trends())
+    }
+}
+
+impl RawTrendCollector for XRawTrendCollector {
+    type Error = XRawTrendCollectorError;
+    // fails when the first search fails, a later failure keeps what was collected
+    async fn collect(&self) -> Result<CollectedRawTrends, Self::Error> {
+        let mut collected: Option<CollectedRawTrends> = None;
+        let mut failure = None;
+        for query in self.searches() {
+            match self.search(&query).await {
+                Ok(trends) => {
+                    collected = Some(match collected {
+                        Some(collected) => collected.merge(trends),
+                        None => trends,
+                    });
+                }
+                // the searches left would be refused too
+                Err(e @ XRawTrendCollectorError::RateLimited(_)) => {
+                    println!("failed to search {} on X: {}", query, e);
+                    failure = Some(e);
+                    break;
+                }
+                // e.g. a malformed query, the others are still searched
+                Err(e) => {
+                    println!("failed to search {} on X: {}", query, e);
+                    failure = Some(e);
+                }
+            }
+        }
+        // fails only when no search succeeded
+        let collected = match (collected, failure) {
+            (Some(collected), _) => collected,
+            (None, Some(e)) => return Err(e),
+            (None, None) => CollectedRawTrends::empty(),
+        };
+        // not collected again until the rate limit is reset
+        let wait = self.rate_limit_wait();
+        Ok(collected.with_ttl(wait))
+    }
+}
+
+// https://docs.x.com/x-api/posts/recent-search
+// only the fields needed to build RawTrendInfo are read
+#[derive(serde::Deserialize)]
+struct SearchResponse {
+    // left out when nothing matches
+    #[serde(default)]
+    data: Vec<Post>,
+    #[serde(default)]
+    includes: Includes,
+}
+#[derive(serde::Deserialize, Default)]
+struct Includes {
+    #[serde(default)]
+    users: Vec<User>,
+}
+#[derive(serde::Deserialize)]
+struct Post {
+    id: String,
+    // html entities of `&`, `<` and `>` are escaped by X
+    text: String,
+    author_id: Option<String>,
+    created_at: Option<String>,
+}
+#[derive(serde::Deserialize)]
+struct User {
+    id: String,
+    username: String,
+}
+impl SearchResponse {
+    fn into_trends(self) -> CollectedRawTrends {
+        let usernames: HashMap<String, String> = self
+            .includes
+            .users
+            .into_iter()
+            .map(|user| (user.id, user.username))
+            .collect();
+        CollectedRawTrends::from_results(self.data.into_iter().enumerate().map(|(index, post)| {
+            let username = post
+                .author_id
+                .as_ref()
+                .and_then(|author_id| usernames.get(author_id));
+            let link = permalink(username.map(|username| username.as_str()), &post.id);
+            post_to_trend(&post, username, &link).map_err(|reason| {
+                SkippedRawTrendInfo::new(index, Some(&post.id), Some(&link), reason)
+            })
+        }))
+    }
+}
+
+fn permalink(username: Option<&str>, id: &str) -> String {
+    match username {
+        Some(username) => format!("https://x.com/{}/status/{}", username, id),
+        None => format!("https://x.com/i/web/status/{}", id),
+    }
+}
+
+fn post_to_trend(
+    post: &Post,
+    username: Option<&String>,
+    link: &str,
+) -> Result<RawTrendInfo, RawTrendInfoError> {
+    let created_at = post
+        .created_at
+        .as_deref()
+        .ok_or(RawTrendInfoError::MissingDate)?;
+    let created_at = Date::parse_feed_date(created_at)
+        .map_err(|_| RawTrendInfoError::InvalidDate(created_at.to_string()))?;
+    // posts have no title, the first line stands in for it
+    let text = description::plain_text(&post.text);
+    let first_line = text.lines().next().unwrap_or_default();
+    let mut title: String = first_line
+        .chars()
+        .take(XRawTrendCollector::MAX_TITLE_CHARS)
+        .collect();
+    if title.len() < first_line.len() {
+        title.push('…');
+    }
+    if let Some(username) = username {
+        title = format!("@{}: {}", username, title);
+    }
+    Ok(RawTrendInfo::new(
+        title,
+        link,
+        post.text.clone(),
+        Service::x(),
+        created_at,
+    ))
+}
+
+#[derive(Debug)]
+pub enum XRawTrendCollectorError {
+    RequestError(String),
+    StatusError(u16),
+    JsonError(String),
+    // the rate limit is used up, and is reset after the wait
+    RateLimited(Duration),
+}
+impl Display for XRawTrendCollectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XRawTrendCollectorError::RequestError(s) => write!(f, "RequestError: {}", s),
+            XRawTrendCollectorError::StatusError(status) => write!(f, "StatusError: {}", status),
+            XRawTrendCollectorError::JsonError(s) => write!(f, "JsonError: {}", s),
+            XRawTrendCollectorError::RateLimited(wait) => {
+                write!(f, "RateLimited: reset in {}s", wait.as_secs())
+            }
+        }
+    }
+}
+impl std::error::Error for XRawTrendCollectorError {}
+
+// as written in the config file
+#[derive(serde::Deserialize)]
+struct XConfig {
+    bearer_token: String,
+    #[serde(default)]
+    accounts: Vec<String>,
+    #[serde(default)]
+    queries: Vec<String>,
+    max_results: Option<u32>,
+    base_url: Option<String>,
+}
+impl TryFrom<XConfig> for XRawTrendCollector {
+    type Error = XConfigError;
+    fn try_from(config: XConfig) -> Result<Self, Self::Error> {
+        if config.bearer_token.trim().is_empty() {
+            return Err(XConfigError::EmptyBearerToken);
+        }
+        if config.accounts.is_empty() && config.queries.is_empty() {
+            return Err(XConfigError::NothingToCollect);
+        }
+        let mut collector = XRawTrendCollector::new(config.bearer_token.trim());
+        for account in config.accounts {
+            collector = collector.with_account(account);
+        }
+        for query in config.queries {
+            collector = collector.with_query(query);
+        }
+        if let Some(max_results) = config.max_results {
+            collector = collector.with_max_results(max_results);
+        }
+        if let Some(base_url) = config.base_url {
+            collector = collector.with_base_url(base_url);
+        }
+        Ok(collector)
+    }
+}
+
+#[derive(Debug)]
+pub enum XConfigError {
+    IoError(std::io::Error),
+    TomlError(toml::de::Error),
+    JsonError(serde_json::Error),
+    UnknownExtension(String),
+    EmptyBearerToken,
+    NothingToCollect,
+}
+impl Display for XConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XConfigError::IoError(e) => write!(f, "IoError: {}", e),
+            XConfigError::TomlError(e) => write!(f, "TomlError: {}", e),
+            XConfigError::JsonError(e) => write!(f, "JsonError: {}", e),
+            XConfigError::UnknownExtension(s) => write!(f, "UnknownExtension: {}", s),
+            XConfigError::EmptyBearerToken => write!(f, "EmptyBearerToken"),
+            XConfigError::NothingToCollect => write!(f, "NothingToCollect"),
+        }
+    }
+}
+impl std::error::Error for XConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{
+        extract::Query,
+        http::{header, HeaderMap as AxumHeaderMap, StatusCode as AxumStatusCode},
+        routing::get,
+        Router,
+    };
+
+    use super::*;
+
+    // a recorded answer of the search endpoint
+    const RECORDED: &str = r#"{
+  "data": [
+    {
+      "id": "1804000000000000001",
+      "text": "Amazon S3 now answers in half the time &amp; costs less\nhttps://t.co/abc",
+      "author_id": "100",
+      "created_at": "2024-06-21T02:22:32.000Z"
+    },
+    {
+      "id": "1804000000000000002",
+      "text": "Rust 1.79 is out",
+      "author_id": "200",
+      "created_at": "2024-06-22T02:22:32.000Z"
+    },
+    {
+      "id": "1804000000000000003",
+      "text": "no date",
+      "author_id": "100"
+    }
+  ],
+  "includes": {
+    "users": [
+      { "id": "100", "name": "Amazon Web Services", "username": "awscloud" },
+      { "id": "200", "name": "Rust Language", "username": "rustlang" }
+    ]
+  },
+  "meta": { "result_count": 3 }
+}"#;
+
+    struct Answer {
+        status: AxumStatusCode,
+        remaining: &'static str,
+        body: &'static str,
+    }
+
+    // stands in for the X API, answers the searches in order, the last one repeatedly
+    async fn serve_search(answers: Vec<Answer>, queries: Arc<Mutex<Vec<String>>>) -> String {
+        let answers = Arc::new(answers);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let reset = (SystemTime::now() + Duration::from_secs(600))
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            .to_string();
+        let app = Router::new().route(
+            "/2/tweets/search/recent",
+            get(
+                move |headers: AxumHeaderMap, Query(params): Query<HashMap<String, String>>| {
+                    let answers = answers.clone();
+                    let requests = requests.clone();
+                    let queries = queries.clone();
+                    let reset = reset.clone();
+                    async move {
+                        assert_eq!(headers[header::AUTHORIZATION], "Bearer token");
+                        assert_eq!(params["expansions"], "author_id");
+                        queries.lock().unwrap().push(params["query"].clone());
+                        let at = requests.fetch_add(1, Ordering::SeqCst);
+                        let answer = &answers[at.min(answers.len() - 1)];
+                        let mut response_headers = AxumHeaderMap::new();
+                        response_headers
+                            .insert("x-rate-limit-remaining", answer.remaining.parse().unwrap());
+                        response_headers.insert("x-rate-limit-reset", reset.parse().unwrap());
+                        (answer.status, response_headers, answer.body)
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/2", addr)
+    }
+
+    fn ok(remaining: &'static str, body: &'static str) -> Answer {
+        Answer {
+            status: AxumStatusCode::OK,
+            remaining,
+            body,
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_of_accounts_and_queries_are_collected() {
+        let queries = Arc::new(Mutex::new(vec![]));
+        let base_url = serve_search(
+            vec![
+                ok("179", RECORDED),
+                ok("178", r#"{"meta":{"result_count":0}}"#),
+            ],
+            queries.clone(),
+        )
+        .await;
+        let collector = XRawTrendCollector::new("token")
+            .with_base_url(base_url)
+            .with_account("@awscloud")
+            .with_query("#rustlang -is:retweet");
+
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(
+            *queries.lock().unwrap(),
+            vec!["from:awscloud", "#rustlang -is:retweet"]
+        );
+        assert_eq!(infos.trends().len(), 2);
+        assert_eq!(infos.skipped().len(), 1);
+        assert_eq!(infos.ttl(), None);
+        let latest = infos.latest().unwrap();
+        assert_eq!(latest.title(), "@rustlang: Rust 1.79 is out");
+        assert_eq!(
+            latest.link(),
+            "https://x.com/rustlang/status/1804000000000000002"
+        );
+        assert_eq!(latest.from(), Service::x().to_str());
+        let aws = &infos.trends()[1];
+        assert_eq!(
+            aws.title(),
+            "@awscloud: Amazon S3 now answers in half the time & costs less"
+        );
+        assert_eq!(
+            aws.desc_text(),
+            "Amazon S3 now answers in half the time & costs less\nhttps://t.co/abc"
+        );
+    }
+
+    #[tokio::test]
+    async fn used_up_rate_limit_stops_the_searches_until_reset() {
+        let queries = Arc::new(Mutex::new(vec![]));
+        let base_url = serve_search(vec![ok("0", RECORDED)], queries.clone()).await;
+        let collector = XRawTrendCollector::new("token")
+            .with_base_url(base_url)
+            .with_account("awscloud")
+            .with_account("rustlang");
+
+        let first = collector.collect().await.unwrap();
+        let second = collector.collect().await;
+
+        // the second account waits for the reset
+        assert_eq!(queries.lock().unwrap().len(), 1);
+        assert_eq!(first.trends().len(), 2);
+        assert!(first.ttl().unwrap() > Duration::from_secs(500));
+        assert!(matches!(
+            second,
+            Err(XRawTrendCollectorError::RateLimited(wait)) if wait > Duration::from_secs(500)
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_search_does_not_stop_the_others() {
+        let queries = Arc::new(Mutex::new(vec![]));
+        let bad_request = || Answer {
+            status: AxumStatusCode::BAD_REQUEST,
+            remaining: "179",
+            body: r#"{"title":"Invalid Request"}"#,
+        };
+        let base_url =
+            serve_search(vec![bad_request(), ok("178", RECORDED)], queries.clone()).await;
+        let collector = XRawTrendCollector::new("token")
+            .with_base_url(base_url)
+            .with_query("(")
+            .with_query("rust");
+
+        let infos = collector.collect().await.unwrap();
+
+        assert_eq!(*queries.lock().unwrap(), vec!["(", "rust"]);
+        assert_eq!(infos.trends().len(), 2);
+
+        let all_failed = XRawTrendCollector::new("token")
+            .with_base_url(serve_search(vec![bad_request()], queries.clone()).await)
+            .with_query("(")
+            .with_query(")");
+        assert!(matches!(
+            all_failed.collect().await,
+            Err(XRawTrendCollectorError::StatusError(400))
+        ));
+    }
+
+    #[tokio::test]
+    async fn too_many_requests_is_rate_limited() {
+        let queries = Arc::new(Mutex::new(vec![]));
+        let base_url = serve_search(
+            vec![Answer {
+                status: AxumStatusCode::TOO_MANY_REQUESTS,
+                remaining: "0",
+                body: r#"{"title":"Too Many Requests"}"#,
+            }],
+            queries.clone(),
+        )
+        .await;
+        let collector = XRawTrendCollector::new("token")
+            .with_base_url(base_url)
+            .with_query("rust");
+
+        let result = collector.collect().await;
+
+        assert!(matches!(
+            result,
+            Err(XRawTrendCollectorError::RateLimited(_))
+        ));
+    }
+
+    #[test]
+    fn reset_out_of_range_waits_the_default_time() {
+        let collector = XRawTrendCollector::new("token");
+        let mut headers = HeaderMap::new();
+        headers.insert("x-rate-limit-remaining", "0".parse().unwrap());
+        headers.insert("x-rate-limit-reset", u64::MAX.to_string().parse().unwrap());
+
+        collector.update_rate_limit(&headers, StatusCode::OK);
+
+        let wait = collector.rate_limit_wait().unwrap();
+        assert!(wait <= XRawTrendCollector::DEFAULT_RATE_LIMIT_WAIT);
+        assert!(wait > Duration::from_secs(60));
+    }
+
+    #[test]
+    fn load_config_without_token_or_searches_should_fail() {
+        let without_token = toml::from_str::<XConfig>(
+            r#"
+bearer_token = " "
+accounts = ["awscloud"]
+"#,
+        )
+        .unwrap();
+        let without_searches = toml::from_str::<XConfig>(r#"bearer_token = "token""#).unwrap();
+
+        assert!(matches!(
+            XRawTrendCollector::try_from(without_token),
+            Err(XConfigError::EmptyBearerToken)
+        ));
+        assert!(matches!(
+            XRawTrendCollector::try_from(without_searches),
+            Err(XConfigError::NothingToCollect)
+        ));
+    }
+}
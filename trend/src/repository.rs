@@ -1,14 +1,33 @@
 // trend life cycle
 // Collect Raw Trend Info ->User can see New Raw Trend Info and have been checked Trend Infos -> User can check Raw Trend Info -> User can update check status
 
-use std::fmt::Display;
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+pub mod migration;
+pub mod postgres;
+pub mod sqlite;
 
-use date::Date;
+use date::{Date, DateTime};
+use user::UserId;
 
 use crate::{
-    domain::{Status, UserTrendInfo, UserTrendInfoId},
+    domain::{
+        Status, Tag, TrendEvent, TrendHistoryEntry, TrendHistoryRecord, UserTrendInfo,
+        UserTrendInfoId,
+    },
     raw::{RawTrendInfo, Service},
-    use_case::SaveNewTrendInfo,
+    use_case::{
+        SaveNewTrendInfo, TrendHistoryQuery, TrendPage, TrendQuery, UserTrendInfoRepository,
+        UserTrendInfoRepositoryError,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -20,6 +39,7 @@ pub struct InitTrendInfoEntity {
     pub memo: String,
     pub from: String,
     pub status: String,
+    pub tags: Vec<String>,
     pub created_at: String,
 }
 impl InitTrendInfoEntity {
@@ -32,6 +52,11 @@ impl InitTrendInfoEntity {
             memo: user_trend.memo,
             from: user_trend.raw_trend.from.to_str().to_string(),
             status: user_trend.status.to_str().to_string(),
+            tags: user_trend
+                .tags
+                .iter()
+                .map(|tag| tag.as_str().to_string())
+                .collect(),
             created_at: user_trend.raw_trend.created_at.to_string(),
         }
     }
@@ -46,6 +71,7 @@ pub struct TrendInfoEntity {
     pub memo: String,
     pub from: String,
     pub status: String,
+    pub tags: Vec<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -65,111 +91,437 @@ impl TryInto<UserTrendInfo> for TrendInfoEntity {
                 .map_err(|_| TrendInfoEntityError::InvalidDate(created_at))?,
         );
         let mut result = UserTrendInfo::new(id, raw_info);
-        // TODO: remove unwrap
-        result.change_memo(self.memo).unwrap();
-        let new_status = Status::from_str(self.status.as_str())
-            .map_err(|_| TrendInfoEntityError::InvalidStatus(self.status.clone()))?;
         result
-            .change_status(new_status)
-            .map_err(|e| TrendInfoEntityError::InvalidStatusChange(e.to_string()))?;
-        Ok(result)
+            .change_memo(self.memo)
+            .map_err(|e| TrendInfoEntityError::InvalidMemo(e.to_string()))?;
+        let status = Status::from_str(self.status.as_str())
+            .map_err(|_| TrendInfoEntityError::InvalidStatus(self.status.clone()))?;
+        // a status of an earlier workflow is kept as is
+        result.restore_status(status);
+        for tag in self.tags {
+            result
+                .add_tag(&tag)
+                .map_err(|_| TrendInfoEntityError::InvalidTag(tag))?;
+        }
+        // memo and status are restored, not changed, the history is read apart
+        Ok(result.with_history(vec![]))
+    }
+}
+
+// the memo, status and tags a trend is saved with are its first changes
+fn initial_history(user_trend: &SaveNewTrendInfo) -> Vec<TrendHistoryEntry> {
+    let mut history = vec![];
+    if !user_trend.memo.is_empty() {
+        history.push(TrendHistoryEntry::new(
+            DateTime::now(),
+            TrendEvent::MemoChanged(user_trend.memo.clone()),
+        ));
+    }
+    if user_trend.status != Status::NEW {
+        history.push(TrendHistoryEntry::new(
+            DateTime::now(),
+            TrendEvent::StatusChanged {
+                from: Status::NEW,
+                to: user_trend.status.clone(),
+            },
+        ));
+    }
+    if !user_trend.tags.is_empty() {
+        let tags: BTreeSet<Tag> = user_trend.tags.iter().cloned().collect();
+        history.push(TrendHistoryEntry::new(
+            DateTime::now(),
+            TrendEvent::TagsChanged(tags.into_iter().collect()),
+        ));
+    }
+    history
+}
+
+#[derive(Debug, Clone)]
+pub struct TrendHistoryEntity {
+    pub trend_id: String,
+    // `status_changed`, `memo_changed` or `tags_changed`
+    pub event: String,
+    pub from_status: Option<String>,
+    pub to_status: Option<String>,
+    pub memo: Option<String>,
+    // joined by `,`
+    pub tags: Option<String>,
+    pub changed_at: String,
+}
+impl TrendHistoryEntity {
+    pub fn new(trend_id: &UserTrendInfoId, entry: &TrendHistoryEntry) -> Self {
+        let mut entity = Self {
+            trend_id: trend_id.0.clone(),
+            event: String::new(),
+            from_status: None,
+            to_status: None,
+            memo: None,
+            tags: None,
+            changed_at: entry.at().to_string(),
+        };
+        match entry.event() {
+            TrendEvent::StatusChanged { from, to } => {
+                entity.event = "status_changed".to_string();
+                entity.from_status = Some(from.to_str().to_string());
+                entity.to_status = Some(to.to_str().to_string());
+            }
+            TrendEvent::MemoChanged(memo) => {
+                entity.event = "memo_changed".to_string();
+                entity.memo = Some(memo.clone());
+            }
+            TrendEvent::TagsChanged(tags) => {
+                entity.event = "tags_changed".to_string();
+                let tags: Vec<_> = tags.iter().map(|tag| tag.as_str()).collect();
+                entity.tags = Some(tags.join(","));
+            }
+        }
+        entity
+    }
+}
+impl TryInto<TrendHistoryRecord> for TrendHistoryEntity {
+    type Error = TrendInfoEntityError;
+    fn try_into(self) -> Result<TrendHistoryRecord, Self::Error> {
+        let at = DateTime::from_str(&self.changed_at)
+            .map_err(|_| TrendInfoEntityError::InvalidDate(self.changed_at.clone()))?;
+        let status = |status: Option<String>| {
+            let status = status.unwrap_or_default();
+            Status::from_str(&status).map_err(|_| TrendInfoEntityError::InvalidStatus(status))
+        };
+        let event = match self.event.as_str() {
+            "status_changed" => TrendEvent::StatusChanged {
+                from: status(self.from_status)?,
+                to: status(self.to_status)?,
+            },
+            "memo_changed" => TrendEvent::MemoChanged(self.memo.unwrap_or_default()),
+            "tags_changed" => TrendEvent::TagsChanged(
+                split_tags(&self.tags.unwrap_or_default())
+                    .into_iter()
+                    .map(|tag| {
+                        Tag::from_str(&tag).map_err(|_| TrendInfoEntityError::InvalidTag(tag))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => return Err(TrendInfoEntityError::InvalidEvent(self.event)),
+        };
+        Ok(TrendHistoryRecord::new(
+            UserTrendInfoId(self.trend_id),
+            TrendHistoryEntry::new(at, event),
+        ))
     }
 }
 #[derive(Debug)]
 pub enum TrendInfoEntityError {
     InvalidStatus(String),
     InvalidDate(String),
-    InvalidStatusChange(String),
+    InvalidMemo(String),
+    InvalidTag(String),
+    InvalidEvent(String),
 }
 impl Display for TrendInfoEntityError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TrendInfoEntityError::InvalidStatus(s) => write!(f, "InvalidStatus: {}", s),
             TrendInfoEntityError::InvalidDate(s) => write!(f, "InvalidDate: {}", s),
-            TrendInfoEntityError::InvalidStatusChange(s) => write!(f, "InvalidStatusChange: {}", s),
+            TrendInfoEntityError::InvalidMemo(s) => write!(f, "InvalidMemo: {}", s),
+            TrendInfoEntityError::InvalidTag(s) => write!(f, "InvalidTag: {}", s),
+            TrendInfoEntityError::InvalidEvent(s) => write!(f, "InvalidEvent: {}", s),
         }
     }
 }
 impl std::error::Error for TrendInfoEntityError {}
 
-#[cfg(test)]
-pub mod fake {
-    use std::cell::RefCell;
-
-    use date::Date;
+fn tag_names(user_trend: &UserTrendInfo) -> Vec<String> {
+    user_trend
+        .tags()
+        .map(|tag| tag.as_str().to_string())
+        .collect()
+}
 
-    use crate::{
-        domain::UserTrendInfo,
-        use_case::{SaveNewTrendInfo, UserTrendInfoRepository, UserTrendInfoRepositoryError},
-    };
+// tags as read joined by `,` from the database
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_string())
+        .collect()
+}
 
-    use super::{InitTrendInfoEntity, TrendInfoEntity, TrendInfoEntityError};
+// `%text%` for LIKE, with the wildcards in the text escaped by `\`
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
 
-    pub struct FakeUserTrendInfoRepository {
-        infos: RefCell<Vec<TrendInfoEntity>>,
+// Keeps trends in memory, lost on restart.
+// Safe to share between the server and the scheduler.
+#[derive(Default)]
+pub struct InMemoryUserTrendInfoRepository {
+    infos: Mutex<Vec<TrendInfoEntity>>,
+    history: Mutex<Vec<TrendHistoryRecord>>,
+    next_id: AtomicUsize,
+}
+impl InMemoryUserTrendInfoRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl UserTrendInfoRepository for InMemoryUserTrendInfoRepository {
+    async fn save(
+        &self,
+        user_trend: SaveNewTrendInfo,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let mut infos = self.infos.lock().unwrap();
+        if infos.iter().any(|i| {
+            i.link == user_trend.raw_trend.link() && user_trend.user_id.is_same(&i.user_id)
+        }) {
+            return Err(UserTrendInfoRepositoryError::AlreadyExists(
+                user_trend.raw_trend.link().to_string(),
+            ));
+        }
+        let initial = initial_history(&user_trend);
+        let entity = InitTrendInfoEntity::new(user_trend);
+        let entity = TrendInfoEntity {
+            id: format!("id-{}", self.next_id.fetch_add(1, Ordering::SeqCst)),
+            user_id: entity.user_id,
+            link: entity.link,
+            title: entity.title,
+            desc: entity.desc,
+            memo: entity.memo,
+            from: entity.from,
+            status: entity.status,
+            tags: entity.tags,
+            created_at: entity.created_at,
+            updated_at: Date::now().to_string(),
+        };
+        infos.push(entity.clone());
+        let result: UserTrendInfo = entity.try_into().map_err(|e: TrendInfoEntityError| {
+            UserTrendInfoRepositoryError::ConvertError(e.to_string())
+        })?;
+        self.history.lock().unwrap().extend(
+            initial
+                .iter()
+                .map(|entry| TrendHistoryRecord::new(result.id().clone(), entry.clone())),
+        );
+        Ok(result.with_history(initial))
+    }
+    async fn update(
+        &self,
+        user_id: UserId,
+        user_trend: UserTrendInfo,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let mut infos = self.infos.lock().unwrap();
+        let entity = infos
+            .iter_mut()
+            .find(|i| i.id == user_trend.id().0 && user_id.is_same(&i.user_id))
+            .ok_or_else(|| {
+                UserTrendInfoRepositoryError::NotFoundError(user_trend.id().0.clone())
+            })?;
+        entity.memo = user_trend.memo().to_string();
+        entity.status = user_trend.status().to_str().to_string();
+        entity.tags = tag_names(&user_trend);
+        entity.updated_at = Date::now().to_string();
+        self.history.lock().unwrap().extend(
+            user_trend
+                .unrecorded_history()
+                .iter()
+                .map(|entry| TrendHistoryRecord::new(user_trend.id().clone(), entry.clone())),
+        );
+        let history = user_trend.history().to_vec();
+        Ok(user_trend.with_history(history))
+    }
+    async fn list(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<UserTrendInfo>, UserTrendInfoRepositoryError> {
+        self.infos
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|i| user_id.is_same(&i.user_id))
+            .map(|i| i.clone().try_into())
+            .collect::<Result<_, TrendInfoEntityError>>()
+            .map_err(|e| UserTrendInfoRepositoryError::ConvertError(e.to_string()))
+    }
+    async fn search(&self, query: &TrendQuery) -> Result<TrendPage, UserTrendInfoRepositoryError> {
+        let user_trends = self.list(query.user_id.clone()).await?;
+        Ok(query.page(user_trends))
+    }
+    async fn get(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let infos = self.infos.lock().unwrap();
+        let entity = infos
+            .iter()
+            .find(|i| i.id == id.0 && user_id.is_same(&i.user_id))
+            .ok_or_else(|| UserTrendInfoRepositoryError::NotFoundError(id.0.clone()))?;
+        let result: UserTrendInfo =
+            entity
+                .clone()
+                .try_into()
+                .map_err(|e: TrendInfoEntityError| {
+                    UserTrendInfoRepositoryError::ConvertError(e.to_string())
+                })?;
+        let history = self
+            .history
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| record.trend_id() == id)
+            .map(|record| record.entry().clone())
+            .collect();
+        Ok(result.with_history(history))
     }
-    impl FakeUserTrendInfoRepository {
-        pub fn new() -> Self {
-            Self {
-                infos: RefCell::new(vec![]),
+    async fn delete(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let mut infos = self.infos.lock().unwrap();
+        let index = infos
+            .iter()
+            .position(|i| i.id == id.0 && user_id.is_same(&i.user_id))
+            .ok_or_else(|| UserTrendInfoRepositoryError::NotFoundError(id.0.clone()))?;
+        self.history
+            .lock()
+            .unwrap()
+            .retain(|record| record.trend_id() != id);
+        infos
+            .remove(index)
+            .try_into()
+            .map_err(|e: TrendInfoEntityError| {
+                UserTrendInfoRepositoryError::ConvertError(e.to_string())
+            })
+    }
+    async fn history(
+        &self,
+        query: &TrendHistoryQuery,
+    ) -> Result<Vec<TrendHistoryRecord>, UserTrendInfoRepositoryError> {
+        let infos = self.infos.lock().unwrap();
+        let owned = |record: &TrendHistoryRecord| {
+            infos
+                .iter()
+                .any(|i| i.id == record.trend_id().0 && query.user_id.is_same(&i.user_id))
+        };
+        let mut records: Vec<_> = self
+            .history
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| owned(record) && query.matches(record))
+            .cloned()
+            .collect();
+        // kept in the order of the changes, sorting is stable
+        records.sort_by_key(|record| record.entry().at());
+        Ok(records)
+    }
+}
+
+// The repository chosen at startup, so the server does not need to be generic over it.
+pub enum AnyUserTrendInfoRepository {
+    Postgres(postgres::PostgresUserTrendInfoRepository),
+    Sqlite(sqlite::SqliteUserTrendInfoRepository),
+    InMemory(InMemoryUserTrendInfoRepository),
+}
+impl AnyUserTrendInfoRepository {
+    // `postgres://...` for Postgres, `sqlite:<path>` or `sqlite::memory:` for SQLite,
+    // in memory when no url is given
+    pub async fn connect(database_url: Option<&str>) -> Result<Self, migration::MigrationError> {
+        match database_url {
+            Some("sqlite::memory:") => Ok(Self::Sqlite(
+                sqlite::SqliteUserTrendInfoRepository::in_memory().await?,
+            )),
+            Some(url) if url.starts_with("sqlite:") => {
+                let path = url.trim_start_matches("sqlite:").trim_start_matches("//");
+                Ok(Self::Sqlite(
+                    sqlite::SqliteUserTrendInfoRepository::open(path).await?,
+                ))
             }
+            Some(url) => Ok(Self::Postgres(
+                postgres::PostgresUserTrendInfoRepository::connect(url).await?,
+            )),
+            None => Ok(Self::InMemory(InMemoryUserTrendInfoRepository::new())),
         }
     }
-    impl UserTrendInfoRepository for FakeUserTrendInfoRepository {
-        async fn update(
-            &self,
-            user_trend: UserTrendInfo,
-        ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
-            let mut infos = self.infos.borrow_mut();
-            let entity = infos.iter_mut().find(|i| i.id == user_trend.id().0).ok_or(
-                UserTrendInfoRepositoryError::NotFoundError("not found".to_string()),
-            )?;
-            entity.memo = user_trend.memo().to_string();
-            entity.status = user_trend.status().to_str().to_string();
-            entity.updated_at = Date::now().to_string();
-            Ok(user_trend)
+}
+impl UserTrendInfoRepository for AnyUserTrendInfoRepository {
+    async fn save(
+        &self,
+        user_trend: SaveNewTrendInfo,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        match self {
+            Self::Postgres(repository) => repository.save(user_trend).await,
+            Self::Sqlite(repository) => repository.save(user_trend).await,
+            Self::InMemory(repository) => repository.save(user_trend).await,
         }
-        async fn list(
-            &self,
-            user_id: user::UserId,
-        ) -> Result<Vec<UserTrendInfo>, UserTrendInfoRepositoryError> {
-            let infos = self.infos.borrow();
-            let result: Result<Vec<UserTrendInfo>, TrendInfoEntityError> = infos
-                .iter()
-                .filter(|i| i.user_id == user_id.to_string())
-                .map(|i| i.clone().try_into())
-                .collect();
-            Ok(result.map_err(|e| UserTrendInfoRepositoryError::ConvertError(e.to_string()))?)
+    }
+    async fn update(
+        &self,
+        user_id: UserId,
+        user_trend: UserTrendInfo,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        match self {
+            Self::Postgres(repository) => repository.update(user_id, user_trend).await,
+            Self::Sqlite(repository) => repository.update(user_id, user_trend).await,
+            Self::InMemory(repository) => repository.update(user_id, user_trend).await,
         }
-        async fn save(
-            &self,
-            user_trend: SaveNewTrendInfo,
-        ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
-            if self.infos.borrow().iter().any(|i| {
-                i.link == user_trend.raw_trend.link() && user_trend.user_id.is_same(&i.user_id)
-            }) {
-                return Err(UserTrendInfoRepositoryError::AlreadyExists(
-                    "already saved".to_string(),
-                ));
-            }
-            let entity = InitTrendInfoEntity::new(user_trend.clone());
-            // fake to save in db
-            let entity = TrendInfoEntity {
-                id: format!("id-{}", self.infos.borrow().len()),
-                user_id: entity.user_id,
-                link: entity.link,
-                title: entity.title,
-                desc: entity.desc,
-                memo: entity.memo,
-                from: entity.from,
-                status: entity.status,
-                created_at: entity.created_at,
-                updated_at: Date::now().to_string(),
-            };
-            self.infos.borrow_mut().push(entity.clone());
-
-            let result: Result<UserTrendInfo, TrendInfoEntityError> = entity.try_into();
-            result.map_err(|e| UserTrendInfoRepositoryError::ConvertError(e.to_string()))
+    }
+    async fn list(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<UserTrendInfo>, UserTrendInfoRepositoryError> {
+        match self {
+            Self::Postgres(repository) => repository.list(user_id).await,
+            Self::Sqlite(repository) => repository.list(user_id).await,
+            Self::InMemory(repository) => repository.list(user_id).await,
+        }
+    }
+    async fn search(&self, query: &TrendQuery) -> Result<TrendPage, UserTrendInfoRepositoryError> {
+        match self {
+            Self::Postgres(repository) => repository.search(query).await,
+            Self::Sqlite(repository) => repository.search(query).await,
+            Self::InMemory(repository) => repository.search(query).await,
+        }
+    }
+    async fn get(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        match self {
+            Self::Postgres(repository) => repository.get(user_id, id).await,
+            Self::Sqlite(repository) => repository.get(user_id, id).await,
+            Self::InMemory(repository) => repository.get(user_id, id).await,
+        }
+    }
+    async fn delete(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        match self {
+            Self::Postgres(repository) => repository.delete(user_id, id).await,
+            Self::Sqlite(repository) => repository.delete(user_id, id).await,
+            Self::InMemory(repository) => repository.delete(user_id, id).await,
+        }
+    }
+    async fn history(
+        &self,
+        query: &TrendHistoryQuery,
+    ) -> Result<Vec<TrendHistoryRecord>, UserTrendInfoRepositoryError> {
+        match self {
+            Self::Postgres(repository) => repository.history(query).await,
+            Self::Sqlite(repository) => repository.history(query).await,
+            Self::InMemory(repository) => repository.history(query).await,
         }
     }
 }
+
+#[cfg(test)]
+pub mod fake {
+    // the in-memory repository serves as the fake of the use cases
+    pub type FakeUserTrendInfoRepository = super::InMemoryUserTrendInfoRepository;
+}
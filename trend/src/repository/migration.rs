@@ -0,0 +1,233 @@
+use std::fmt::Display;
+
+use sqlx::{Database, Executor, IntoArguments, Pool};
+
+// A numbered change of the schema, applied once and in order.
+// Applied versions are recorded in `schema_migrations`.
+pub struct Migration {
+    version: i64,
+    name: &'static str,
+    sql: &'static str,
+}
+impl Migration {
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+    pub fn name(&self) -> &str {
+        self.name
+    }
+}
+
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "init",
+        sql: include_str!("../../migrations/postgres/0001_init.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "history",
+        sql: include_str!("../../migrations/postgres/0002_history.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "tags",
+        sql: include_str!("../../migrations/postgres/0003_tags.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "history_tags",
+        sql: include_str!("../../migrations/postgres/0004_history_tags.sql"),
+    },
+];
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "init",
+        sql: include_str!("../../migrations/sqlite/0001_init.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "history",
+        sql: include_str!("../../migrations/sqlite/0002_history.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "tags",
+        sql: include_str!("../../migrations/sqlite/0003_tags.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "history_tags",
+        sql: include_str!("../../migrations/sqlite/0004_history_tags.sql"),
+    },
+];
+
+const CREATE_SCHEMA_MIGRATIONS: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    version BIGINT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL
+)";
+
+// Applies the migrations newer than the schema, returns how many were applied.
+// A schema newer than the last known migration was written by a newer build,
+// running against it could break the data, so it is refused.
+pub async fn migrate<DB>(pool: &Pool<DB>, migrations: &[Migration]) -> Result<usize, MigrationError>
+where
+    DB: Database,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'c> &'c Pool<DB>: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    i64: sqlx::Type<DB> + for<'r> sqlx::Decode<'r, DB>,
+    usize: sqlx::ColumnIndex<DB::Row>,
+{
+    let latest = migrations.last().map_or(0, |m| m.version);
+    sqlx::query(CREATE_SCHEMA_MIGRATIONS)
+        .execute(pool)
+        .await
+        .map_err(MigrationError::DatabaseError)?;
+    let current: i64 =
+        sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+            .fetch_one(pool)
+            .await
+            .map_err(MigrationError::DatabaseError)?;
+    if current > latest {
+        return Err(MigrationError::UnknownVersion { current, latest });
+    }
+
+    let mut applied = 0;
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await.map_err(MigrationError::DatabaseError)?;
+        sqlx::raw_sql(migration.sql)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MigrationError::MigrationFailed(migration.version, e))?;
+        // version and name are ours, not user input
+        sqlx::raw_sql(&format!(
+            "INSERT INTO schema_migrations (version, name) VALUES ({}, '{}')",
+            migration.version, migration.name
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MigrationError::MigrationFailed(migration.version, e))?;
+        tx.commit().await.map_err(MigrationError::DatabaseError)?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    DatabaseError(sqlx::Error),
+    MigrationFailed(i64, sqlx::Error),
+    UnknownVersion { current: i64, latest: i64 },
+}
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::DatabaseError(e) => write!(f, "DatabaseError: {}", e),
+            MigrationError::MigrationFailed(version, e) => {
+                write!(f, "MigrationFailed: version {}: {}", version, e)
+            }
+            MigrationError::UnknownVersion { current, latest } => write!(
+                f,
+                "UnknownVersion: schema is at version {} but this build knows up to {}",
+                current, latest
+            ),
+        }
+    }
+}
+impl std::error::Error for MigrationError {}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+
+    use super::*;
+
+    async fn pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::new().in_memory(true))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn migrations_are_applied_once() {
+        let pool = pool().await;
+
+        assert_eq!(
+            migrate(&pool, SQLITE_MIGRATIONS).await.unwrap(),
+            SQLITE_MIGRATIONS.len()
+        );
+        assert_eq!(migrate(&pool, SQLITE_MIGRATIONS).await.unwrap(), 0);
+
+        let versions: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_migrations")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(versions, vec![1, 2, 3, 4]);
+    }
+    #[tokio::test]
+    async fn newer_migrations_are_applied_in_order() {
+        let pool = pool().await;
+        migrate(&pool, &SQLITE_MIGRATIONS[..1]).await.unwrap();
+        let migrations = [
+            Migration {
+                version: 1,
+                name: "init",
+                sql: SQLITE_MIGRATIONS[0].sql,
+            },
+            Migration {
+                version: 2,
+                name: "add_column",
+                sql: "ALTER TABLE trend_info ADD COLUMN tag TEXT",
+            },
+            Migration {
+                version: 3,
+                name: "use_column",
+                sql: "UPDATE trend_info SET tag = ''",
+            },
+        ];
+
+        assert_eq!(migrate(&pool, &migrations).await.unwrap(), 2);
+    }
+    #[tokio::test]
+    async fn refuse_schema_newer_than_known() {
+        let pool = pool().await;
+        migrate(&pool, SQLITE_MIGRATIONS).await.unwrap();
+        sqlx::query("INSERT INTO schema_migrations (version, name) VALUES (99, 'future')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let result = migrate(&pool, SQLITE_MIGRATIONS).await;
+
+        assert!(matches!(
+            result,
+            Err(MigrationError::UnknownVersion {
+                current: 99,
+                latest: 4
+            })
+        ));
+    }
+    #[tokio::test]
+    async fn failed_migration_is_not_recorded() {
+        let pool = pool().await;
+        let migrations = [Migration {
+            version: 1,
+            name: "broken",
+            sql: "CREATE TABLE broken (",
+        }];
+
+        let result = migrate(&pool, &migrations).await;
+
+        assert!(matches!(result, Err(MigrationError::MigrationFailed(1, _))));
+        let current: i64 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(current, 0);
+    }
+}
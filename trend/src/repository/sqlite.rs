@@ -0,0 +1,864 @@
+use date::Date;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions, SqliteRow},
+    QueryBuilder, Row, Sqlite,
+};
+use user::UserId;
+
+use crate::{
+    domain::{TrendHistoryEntry, TrendHistoryRecord, UserTrendInfo, UserTrendInfoId},
+    use_case::{
+        SaveNewTrendInfo, SortOrder, TrendHistoryQuery, TrendPage, TrendQuery,
+        UserTrendInfoRepository, UserTrendInfoRepositoryError,
+    },
+};
+
+use super::{
+    initial_history, like_pattern,
+    migration::{migrate, MigrationError, SQLITE_MIGRATIONS},
+    split_tags, tag_names, InitTrendInfoEntity, TrendHistoryEntity, TrendInfoEntity,
+    TrendInfoEntityError,
+};
+
+const COLUMNS: &str =
+    "id, user_id, link, title, description, memo, service, status, created_at, updated_at";
+// tags of the trend joined by `,`, which a tag can not contain
+const TAGS: &str = "(SELECT group_concat(tag, ',') FROM trend_tag
+    WHERE trend_tag.trend_id = trend_info.id) AS tags";
+const HISTORY_COLUMNS: &str =
+    "h.trend_id, h.event, h.from_status, h.to_status, h.memo, h.tags, h.changed_at";
+
+// Stores trends in a single SQLite file, for instances without a database server.
+pub struct SqliteUserTrendInfoRepository {
+    pool: SqlitePool,
+}
+impl SqliteUserTrendInfoRepository {
+    // the file is created when it does not exist
+    pub async fn open(path: &str) -> Result<Self, MigrationError> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await
+            .map_err(MigrationError::DatabaseError)?;
+        Self::init(pool).await
+    }
+    // every connection to `:memory:` is a database of its own, so only one is kept
+    pub async fn in_memory() -> Result<Self, MigrationError> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(SqliteConnectOptions::new().in_memory(true))
+            .await
+            .map_err(MigrationError::DatabaseError)?;
+        Self::init(pool).await
+    }
+    async fn init(pool: SqlitePool) -> Result<Self, MigrationError> {
+        migrate(&pool, SQLITE_MIGRATIONS).await?;
+        Ok(Self { pool })
+    }
+}
+impl UserTrendInfoRepository for SqliteUserTrendInfoRepository {
+    async fn save(
+        &self,
+        user_trend: SaveNewTrendInfo,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let initial = initial_history(&user_trend);
+        let entity = InitTrendInfoEntity::new(user_trend);
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        let query = format!(
+            "INSERT INTO trend_info
+                (id, user_id, link, title, description, memo, service, status, created_at, updated_at)
+            VALUES
+                (lower(hex(randomblob(16))), ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            RETURNING {}, ?10 AS tags",
+            COLUMNS
+        );
+        let row = sqlx::query(&query)
+            .bind(&entity.user_id)
+            .bind(&entity.link)
+            .bind(&entity.title)
+            .bind(&entity.desc)
+            .bind(&entity.memo)
+            .bind(&entity.from)
+            .bind(&entity.status)
+            .bind(&entity.created_at)
+            .bind(Date::now().to_string())
+            // the tags are inserted below
+            .bind(entity.tags.join(","))
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| match e {
+                // (user_id, link) is unique
+                sqlx::Error::Database(db) if db.is_unique_violation() => {
+                    UserTrendInfoRepositoryError::AlreadyExists(entity.link.clone())
+                }
+                e => UserTrendInfoRepositoryError::SaveError(e.to_string()),
+            })?;
+        let result = to_user_trend(&row)?;
+        insert_tags(&mut tx, result.id(), &entity.tags)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        insert_history(&mut tx, result.id(), &initial)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        tx.commit()
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        Ok(result.with_history(initial))
+    }
+    async fn update(
+        &self,
+        user_id: UserId,
+        user_trend: UserTrendInfo,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        let result = sqlx::query(
+            "UPDATE trend_info SET memo = ?2, status = ?3, updated_at = ?4
+            WHERE id = ?1 AND user_id = ?5",
+        )
+        .bind(&user_trend.id().0)
+        .bind(user_trend.memo())
+        .bind(user_trend.status().to_str())
+        .bind(Date::now().to_string())
+        .bind(user_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(UserTrendInfoRepositoryError::NotFoundError(
+                user_trend.id().0.clone(),
+            ));
+        }
+        sqlx::query("DELETE FROM trend_tag WHERE trend_id = ?1")
+            .bind(&user_trend.id().0)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        insert_tags(&mut tx, user_trend.id(), &tag_names(&user_trend))
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        insert_history(&mut tx, user_trend.id(), user_trend.unrecorded_history())
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        tx.commit()
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        let history = user_trend.history().to_vec();
+        Ok(user_trend.with_history(history))
+    }
+    async fn list(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<UserTrendInfo>, UserTrendInfoRepositoryError> {
+        let query = format!(
+            "SELECT {}, {} FROM trend_info WHERE user_id = ?1 ORDER BY created_at DESC",
+            COLUMNS, TAGS
+        );
+        let rows = sqlx::query(&query)
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        rows.iter().map(to_user_trend).collect()
+    }
+    async fn search(&self, query: &TrendQuery) -> Result<TrendPage, UserTrendInfoRepositoryError> {
+        let mut builder = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {}, {} FROM trend_info WHERE user_id = ",
+            COLUMNS, TAGS
+        ));
+        builder.push_bind(query.user_id.to_string());
+        if !query.statuses.is_empty() {
+            builder.push(" AND status IN (");
+            let mut statuses = builder.separated(", ");
+            for status in &query.statuses {
+                statuses.push_bind(status.to_str().to_string());
+            }
+            builder.push(")");
+        }
+        if let Some(service) = &query.service {
+            builder
+                .push(" AND service = ")
+                .push_bind(service.to_str().to_string());
+        }
+        for tag in &query.tags {
+            builder
+                .push(
+                    " AND EXISTS (SELECT 1 FROM trend_tag
+                    WHERE trend_tag.trend_id = trend_info.id AND trend_tag.tag = ",
+                )
+                .push_bind(tag.as_str().to_string())
+                .push(")");
+        }
+        if let Some(since) = query.created_since {
+            builder.push(" AND created_at >= ");
+            builder.push_bind(since.to_string());
+        }
+        if let Some(until) = query.created_until {
+            builder.push(" AND created_at <= ");
+            builder.push_bind(until.to_string());
+        }
+        if let Some(text) = &query.text {
+            let pattern = like_pattern(text);
+            builder.push(" AND (title LIKE ").push_bind(pattern.clone());
+            builder
+                .push(" ESCAPE '\\' OR description LIKE ")
+                .push_bind(pattern.clone());
+            builder
+                .push(" ESCAPE '\\' OR memo LIKE ")
+                .push_bind(pattern);
+            builder.push(" ESCAPE '\\')");
+        }
+        let (compare, direction) = match query.order {
+            SortOrder::NewestFirst => ("<", "DESC"),
+            SortOrder::OldestFirst => (">", "ASC"),
+        };
+        if let Some(after) = &query.after {
+            builder.push(format!(" AND (created_at, id) {} (", compare));
+            builder.push_bind(after.created_at.to_string());
+            builder.push(", ").push_bind(after.id.0.clone()).push(")");
+        }
+        builder.push(format!(
+            " ORDER BY created_at {0}, id {0} LIMIT ",
+            direction
+        ));
+        // one more than the limit tells whether a next page exists
+        builder.push_bind(query.limit as i64 + 1);
+        let rows = builder
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        let user_trends = rows
+            .iter()
+            .map(to_user_trend)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TrendPage::new(user_trends, query.limit))
+    }
+    async fn get(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let query = format!(
+            "SELECT {}, {} FROM trend_info WHERE id = ?1 AND user_id = ?2",
+            COLUMNS, TAGS
+        );
+        let row = sqlx::query(&query)
+            .bind(&id.0)
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?
+            .ok_or_else(|| UserTrendInfoRepositoryError::NotFoundError(id.0.clone()))?;
+        let result = to_user_trend(&row)?;
+        let history = self
+            .history(&TrendHistoryQuery::new(user_id).with_trend(id.clone()))
+            .await?
+            .into_iter()
+            .map(|record| record.entry().clone())
+            .collect();
+        Ok(result.with_history(history))
+    }
+    async fn delete(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        // read before deleting, the tags are gone with the trend
+        let query = format!(
+            "SELECT {}, {} FROM trend_info WHERE id = ?1 AND user_id = ?2",
+            COLUMNS, TAGS
+        );
+        let row = sqlx::query(&query)
+            .bind(&id.0)
+            .bind(user_id.to_string())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?
+            .ok_or_else(|| UserTrendInfoRepositoryError::NotFoundError(id.0.clone()))?;
+        // tags and history go with the trend, ON DELETE CASCADE
+        sqlx::query("DELETE FROM trend_info WHERE id = ?1")
+            .bind(&id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        tx.commit()
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        to_user_trend(&row)
+    }
+    async fn history(
+        &self,
+        query: &TrendHistoryQuery,
+    ) -> Result<Vec<TrendHistoryRecord>, UserTrendInfoRepositoryError> {
+        let mut builder = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {} FROM trend_history h JOIN trend_info t ON t.id = h.trend_id
+            WHERE t.user_id = ",
+            HISTORY_COLUMNS
+        ));
+        builder.push_bind(query.user_id.to_string());
+        if let Some(trend_id) = &query.trend_id {
+            builder
+                .push(" AND h.trend_id = ")
+                .push_bind(trend_id.0.clone());
+        }
+        if let Some(since) = query.since {
+            builder
+                .push(" AND h.changed_at >= ")
+                .push_bind(since.start_of_day().to_string());
+        }
+        if let Some(until) = query.until {
+            builder
+                .push(" AND h.changed_at <= ")
+                .push_bind(until.end_of_day().to_string());
+        }
+        builder.push(" ORDER BY h.changed_at, h.id");
+        let rows = builder
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| UserTrendInfoRepositoryError::SaveError(e.to_string()))?;
+        rows.iter().map(to_history_record).collect()
+    }
+}
+
+async fn insert_tags(
+    conn: &mut SqliteConnection,
+    trend_id: &UserTrendInfoId,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    for tag in tags {
+        sqlx::query("INSERT INTO trend_tag (trend_id, tag) VALUES (?1, ?2)")
+            .bind(&trend_id.0)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+async fn insert_history(
+    conn: &mut SqliteConnection,
+    trend_id: &UserTrendInfoId,
+    entries: &[TrendHistoryEntry],
+) -> Result<(), sqlx::Error> {
+    for entry in entries {
+        let entity = TrendHistoryEntity::new(trend_id, entry);
+        sqlx::query(
+            "INSERT INTO trend_history
+                (trend_id, event, from_status, to_status, memo, tags, changed_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(&entity.trend_id)
+        .bind(&entity.event)
+        .bind(&entity.from_status)
+        .bind(&entity.to_status)
+        .bind(&entity.memo)
+        .bind(&entity.tags)
+        .bind(&entity.changed_at)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+fn to_history_record(row: &SqliteRow) -> Result<TrendHistoryRecord, UserTrendInfoRepositoryError> {
+    let entity = to_history_entity(row)
+        .map_err(|e| UserTrendInfoRepositoryError::ConvertError(e.to_string()))?;
+    entity.try_into().map_err(|e: TrendInfoEntityError| {
+        UserTrendInfoRepositoryError::ConvertError(e.to_string())
+    })
+}
+fn to_history_entity(row: &SqliteRow) -> Result<TrendHistoryEntity, sqlx::Error> {
+    Ok(TrendHistoryEntity {
+        trend_id: row.try_get("trend_id")?,
+        event: row.try_get("event")?,
+        from_status: row.try_get("from_status")?,
+        to_status: row.try_get("to_status")?,
+        memo: row.try_get("memo")?,
+        tags: row.try_get("tags")?,
+        changed_at: row.try_get("changed_at")?,
+    })
+}
+
+fn to_user_trend(row: &SqliteRow) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+    let entity =
+        to_entity(row).map_err(|e| UserTrendInfoRepositoryError::ConvertError(e.to_string()))?;
+    entity.try_into().map_err(|e: TrendInfoEntityError| {
+        UserTrendInfoRepositoryError::ConvertError(e.to_string())
+    })
+}
+fn to_entity(row: &SqliteRow) -> Result<TrendInfoEntity, sqlx::Error> {
+    // nullable columns are read as empty
+    let text = |column: &str| -> Result<String, sqlx::Error> {
+        Ok(row
+            .try_get::<Option<String>, _>(column)?
+            .unwrap_or_default())
+    };
+    Ok(TrendInfoEntity {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        link: row.try_get("link")?,
+        title: text("title")?,
+        desc: text("description")?,
+        memo: text("memo")?,
+        from: text("service")?,
+        status: text("status")?,
+        tags: split_tags(&text("tags")?),
+        created_at: text("created_at")?,
+        updated_at: text("updated_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::{Status, TrendEvent},
+        raw::{RawTrendInfo, Service},
+        use_case::{SaveNewTrendInfoBuilder, TrendQueryBuilder},
+        workflow::Workflow,
+    };
+
+    fn save_info(user_id: &str, link: &str, date: &str) -> SaveNewTrendInfo {
+        let raw_trend = RawTrendInfo::new(
+            "title",
+            link,
+            "desc",
+            Service::aws_updates(),
+            Date::parse_from_str(date, "%Y-%m-%d").unwrap(),
+        );
+        SaveNewTrendInfoBuilder::new(UserId::new(user_id), raw_trend).build()
+    }
+
+    #[tokio::test]
+    async fn save_and_list_trends() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        repository
+            .save(save_info("user_id", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+        repository
+            .save(save_info("user_id", "http://example.com/2", "2024-06-22"))
+            .await
+            .unwrap();
+        repository
+            .save(save_info("other", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+
+        let trends = repository.list(UserId::new("user_id")).await.unwrap();
+
+        assert_eq!(trends.len(), 2);
+        assert_eq!(trends[0].link(), "http://example.com/2");
+        assert_eq!(trends[1].created_at().to_string(), "2024-06-21:00:00:00");
+    }
+    #[tokio::test]
+    async fn same_link_for_same_user_already_exists() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        repository
+            .save(save_info("user_id", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+
+        let result = repository
+            .save(save_info("user_id", "http://example.com/1", "2024-06-21"))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(UserTrendInfoRepositoryError::AlreadyExists(_))
+        ));
+    }
+    #[tokio::test]
+    async fn tags_are_stored_and_filtered() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        let tagged = SaveNewTrendInfoBuilder::new(
+            UserId::new("user_id"),
+            RawTrendInfo::new(
+                "title",
+                "http://example.com/1",
+                "desc",
+                Service::aws_updates(),
+                Date::now(),
+            ),
+        )
+        .tags(["lambda".parse().unwrap(), "cost".parse().unwrap()])
+        .build();
+        let mut user_trend = repository.save(tagged).await.unwrap();
+        repository
+            .save(save_info("user_id", "http://example.com/2", "2024-06-21"))
+            .await
+            .unwrap();
+        user_trend.change_tags(["cost"], ["security"]).unwrap();
+        let user_trend = repository
+            .update(UserId::new("user_id"), user_trend)
+            .await
+            .unwrap();
+
+        let history = repository
+            .get(UserId::new("user_id"), user_trend.id())
+            .await
+            .unwrap()
+            .history()
+            .iter()
+            .map(|entry| entry.event().clone())
+            .collect::<Vec<_>>();
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.parse().unwrap()).collect();
+        assert_eq!(
+            history,
+            vec![
+                TrendEvent::TagsChanged(tags(&["cost", "lambda"])),
+                TrendEvent::TagsChanged(tags(&["lambda", "security"])),
+            ]
+        );
+        let query = TrendQueryBuilder::new(UserId::new("user_id"))
+            .tags(["security".parse().unwrap(), "lambda".parse().unwrap()])
+            .build();
+        let page = repository.search(&query).await.unwrap();
+        assert_eq!(page.trends().len(), 1);
+        let deleted = repository
+            .delete(UserId::new("user_id"), page.trends()[0].id())
+            .await
+            .unwrap();
+
+        let tags: Vec<_> = deleted.tags().map(|tag| tag.as_str()).collect();
+        assert_eq!(tags, vec!["lambda", "security"]);
+        let query = TrendQueryBuilder::new(UserId::new("user_id"))
+            .tags(["cost".parse().unwrap()])
+            .build();
+        assert!(repository.search(&query).await.unwrap().trends().is_empty());
+    }
+    #[tokio::test]
+    async fn update_memo_and_status() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        let mut user_trend = repository
+            .save(save_info("user_id", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+        user_trend.change_memo("read later".to_string()).unwrap();
+        user_trend
+            .change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+
+        repository
+            .update(UserId::new("user_id"), user_trend)
+            .await
+            .unwrap();
+
+        let trends = repository.list(UserId::new("user_id")).await.unwrap();
+        assert_eq!(trends[0].memo(), "read later");
+        assert_eq!(trends[0].status(), Status::DONE);
+    }
+    #[tokio::test]
+    async fn status_of_custom_workflow_is_stored() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        let workflow = Workflow::from_toml(
+            r#"
+[[statuses]]
+name = "New"
+next = ["Archived"]
+
+[[statuses]]
+name = "Archived"
+"#,
+        )
+        .unwrap();
+        let archived = workflow.status("Archived").unwrap();
+        let mut user_trend = repository
+            .save(save_info("user_id", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+        user_trend
+            .change_status(archived.clone(), &workflow)
+            .unwrap();
+
+        repository
+            .update(UserId::new("user_id"), user_trend)
+            .await
+            .unwrap();
+
+        let trends = repository.list(UserId::new("user_id")).await.unwrap();
+        assert_eq!(trends[0].status(), archived);
+    }
+    #[tokio::test]
+    async fn history_is_kept_until_the_trend_is_deleted() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        let save_info = SaveNewTrendInfoBuilder::new(
+            UserId::new("user_id"),
+            RawTrendInfo::new(
+                "title",
+                "http://example.com/1",
+                "desc",
+                Service::aws_updates(),
+                Date::now(),
+            ),
+        )
+        .status(Status::TO_DO)
+        .build();
+        let mut user_trend = repository.save(save_info).await.unwrap();
+        user_trend
+            .change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+        let mut user_trend = repository
+            .update(UserId::new("user_id"), user_trend)
+            .await
+            .unwrap();
+        user_trend.change_memo("good".to_string()).unwrap();
+        let user_trend = repository
+            .update(UserId::new("user_id"), user_trend)
+            .await
+            .unwrap();
+
+        let user_trend = repository
+            .get(UserId::new("user_id"), user_trend.id())
+            .await
+            .unwrap();
+        let events: Vec<_> = user_trend
+            .history()
+            .iter()
+            .map(|entry| entry.event().clone())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                TrendEvent::StatusChanged {
+                    from: Status::NEW,
+                    to: Status::TO_DO
+                },
+                TrendEvent::StatusChanged {
+                    from: Status::TO_DO,
+                    to: Status::DONE
+                },
+                TrendEvent::MemoChanged("good".to_string()),
+            ]
+        );
+
+        repository
+            .delete(UserId::new("user_id"), user_trend.id())
+            .await
+            .unwrap();
+        let history = repository
+            .history(&TrendHistoryQuery::new(UserId::new("user_id")))
+            .await
+            .unwrap();
+        assert!(history.is_empty());
+    }
+    #[tokio::test]
+    async fn trend_from_list_has_no_history_but_its_changes_are_stored() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        let mut user_trend = repository
+            .save(save_info("user_id", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+        user_trend.change_memo("first".to_string()).unwrap();
+        repository
+            .update(UserId::new("user_id"), user_trend)
+            .await
+            .unwrap();
+
+        let mut user_trend = repository
+            .list(UserId::new("user_id"))
+            .await
+            .unwrap()
+            .remove(0);
+        assert!(user_trend.history().is_empty());
+        user_trend.change_memo("second".to_string()).unwrap();
+        let user_trend = repository
+            .update(UserId::new("user_id"), user_trend)
+            .await
+            .unwrap();
+
+        let user_trend = repository
+            .get(UserId::new("user_id"), user_trend.id())
+            .await
+            .unwrap();
+        let events: Vec<_> = user_trend
+            .history()
+            .iter()
+            .map(|entry| entry.event().clone())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                TrendEvent::MemoChanged("first".to_string()),
+                TrendEvent::MemoChanged("second".to_string()),
+            ]
+        );
+    }
+    #[tokio::test]
+    async fn history_of_user_in_period() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        for (user_id, link) in [
+            ("user_id", "http://example.com/1"),
+            ("other", "http://example.com/1"),
+        ] {
+            let mut user_trend = repository
+                .save(save_info(user_id, link, "2024-06-21"))
+                .await
+                .unwrap();
+            user_trend
+                .change_status(Status::READING, &Workflow::default())
+                .unwrap();
+            repository
+                .update(UserId::new(user_id), user_trend)
+                .await
+                .unwrap();
+        }
+        let query = TrendHistoryQuery::new(UserId::new("user_id"));
+
+        let today = repository
+            .history(
+                &query
+                    .clone()
+                    .with_since(Date::now())
+                    .with_until(Date::now()),
+            )
+            .await
+            .unwrap();
+        let long_ago = repository
+            .history(&query.with_until(Date::parse_from_str("2000-01-01", "%Y-%m-%d").unwrap()))
+            .await
+            .unwrap();
+
+        assert_eq!(today.len(), 1);
+        assert_eq!(
+            today[0].entry().event(),
+            &TrendEvent::StatusChanged {
+                from: Status::NEW,
+                to: Status::READING
+            }
+        );
+        assert!(long_ago.is_empty());
+    }
+    #[tokio::test]
+    async fn get_update_and_delete_only_own_trend() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        let saved = repository
+            .save(save_info("owner", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            repository.get(UserId::new("other"), saved.id()).await,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+        let mut changed = saved.clone();
+        changed.change_memo("not mine".to_string()).unwrap();
+        assert!(matches!(
+            repository.update(UserId::new("other"), changed).await,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+        assert!(matches!(
+            repository.delete(UserId::new("other"), saved.id()).await,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+        let got = repository
+            .get(UserId::new("owner"), saved.id())
+            .await
+            .unwrap();
+        assert_eq!(got.link(), "http://example.com/1");
+        assert_eq!(got.memo(), "");
+
+        repository
+            .delete(UserId::new("owner"), saved.id())
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository.get(UserId::new("owner"), saved.id()).await,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+    }
+    #[tokio::test]
+    async fn search_with_filters_and_pages() {
+        let repository = SqliteUserTrendInfoRepository::in_memory().await.unwrap();
+        for (link, date) in [
+            ("http://example.com/100%", "2024-06-20"),
+            ("http://example.com/a", "2024-06-21"),
+            ("http://example.com/b", "2024-06-22"),
+            ("http://example.com/c", "2024-06-22"),
+            ("http://example.com/d", "2024-06-23"),
+        ] {
+            repository
+                .save(save_info("user_id", link, date))
+                .await
+                .unwrap();
+        }
+        let mut done = repository
+            .save(save_info(
+                "user_id",
+                "http://example.com/done",
+                "2024-06-22",
+            ))
+            .await
+            .unwrap();
+        done.change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+        repository
+            .update(UserId::new("user_id"), done)
+            .await
+            .unwrap();
+        let user_id = UserId::new("user_id");
+
+        let mut links = vec![];
+        let mut builder = TrendQueryBuilder::new(user_id.clone())
+            .statuses([Status::NEW])
+            .created_since(Date::parse_from_str("2024-06-21", "%Y-%m-%d").unwrap())
+            .limit(2);
+        loop {
+            let page = repository.search(&builder.build()).await.unwrap();
+            links.extend(page.trends().iter().map(|t| t.link().to_string()));
+            let Some(next) = page.next() else {
+                break;
+            };
+            builder = TrendQueryBuilder::new(user_id.clone())
+                .statuses([Status::NEW])
+                .created_since(Date::parse_from_str("2024-06-21", "%Y-%m-%d").unwrap())
+                .limit(2)
+                .after(next.clone());
+        }
+        assert_eq!(links.len(), 4);
+        assert_eq!(links[0], "http://example.com/d");
+        assert_eq!(links[3], "http://example.com/a");
+
+        // wildcards in the text are matched as they are
+        let query = TrendQueryBuilder::new(user_id).text("%").build();
+        let page = repository.search(&query).await.unwrap();
+        assert!(page.trends().is_empty());
+    }
+    #[tokio::test]
+    async fn trends_survive_reopening_the_file() {
+        let path = std::env::temp_dir().join(format!("trend-{}-reopen.db", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let repository = SqliteUserTrendInfoRepository::open(path).await.unwrap();
+        repository
+            .save(save_info("user_id", "http://example.com/1", "2024-06-21"))
+            .await
+            .unwrap();
+        repository.pool.close().await;
+        let repository = SqliteUserTrendInfoRepository::open(path).await.unwrap();
+
+        assert_eq!(
+            repository.list(UserId::new("user_id")).await.unwrap().len(),
+            1
+        );
+        repository.pool.close().await;
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,593 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::time::{Instant, MissedTickBehavior};
+use user::UserId;
+
+use crate::{
+    raw::{
+        aggregate::AggregateRawTrendCollector,
+        conditional::{FetchValidatorStore, FetchValidators},
+        RawTrendCollector,
+    },
+    source::{repository::TrendSourceRepository, TrendSource},
+    use_case::{
+        save_new_trend, SaveNewTrendInfoBuilder, UserTrendInfoRepository,
+        UserTrendInfoRepositoryError,
+    },
+};
+
+// Collects the sources of every user in background and saves new trends for them.
+// The shared sources of the deployment are collected the same way for each of their subscribers.
+// A source is fetched once even when many users subscribe to it the same way, i.e. with the same
+// url, format, service and filters, and not again until its interval, or the <ttl> of the feed
+// when that is longer, has passed.
+// Each user keeps the validators of their sources, which are updated only once the trends
+// are saved, so a user who subscribes later or a failed save still gets the whole feed.
+// Shared collectors which are not sources, like the one of X, are run for the subscribers
+// every default interval, or once their <ttl> has passed when that is longer.
+pub struct TrendCollectionScheduler<T, S, F, C> {
+    trends: Arc<T>,
+    sources: Arc<S>,
+    make_collector: F,
+    shared_sources: Vec<TrendSource>,
+    subscribers: Vec<UserId>,
+    // by name
+    shared_collectors: Vec<(String, C)>,
+    validators: Arc<FetchValidatorStore>,
+    default_interval: Duration,
+    next_due: HashMap<String, Instant>,
+}
+impl<T, S, F, C> TrendCollectionScheduler<T, S, F, C>
+where
+    T: UserTrendInfoRepository,
+    S: TrendSourceRepository,
+    // the collector fetches conditionally with the validators when they are given
+    F: Fn(&TrendSource, Option<FetchValidators>) -> C,
+    C: RawTrendCollector,
+{
+    const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+    pub fn new(trends: Arc<T>, sources: Arc<S>, make_collector: F) -> Self {
+        Self {
+            trends,
+            sources,
+            make_collector,
+            shared_sources: vec![],
+            subscribers: vec![],
+            shared_collectors: vec![],
+            validators: Arc::new(FetchValidatorStore::in_memory()),
+            default_interval: Self::DEFAULT_INTERVAL,
+            next_due: HashMap::new(),
+        }
+    }
+    // used for sources without their own interval
+    pub fn with_default_interval(mut self, interval: Duration) -> Self {
+        self.default_interval = interval;
+        self
+    }
+    // sources every subscriber gets the trends of, e.g. those of the registry
+    pub fn with_shared_sources(
+        mut self,
+        sources: Vec<TrendSource>,
+        subscribers: Vec<UserId>,
+    ) -> Self {
+        self.shared_sources = sources;
+        self.subscribers = subscribers;
+        self
+    }
+    // collected for the subscribers of the shared sources too
+    pub fn with_shared_collector(mut self, name: impl Into<String>, collector: C) -> Self {
+        self.shared_collectors.push((name.into(), collector));
+        self
+    }
+    pub fn with_validators(mut self, validators: Arc<FetchValidatorStore>) -> Self {
+        self.validators = validators;
+        self
+    }
+    pub async fn run(mut self, tick: Duration) {
+        let mut ticks = tokio::time::interval(tick);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let now = ticks.tick().await;
+            let report = self.run_once(now).await;
+            if report.collected > 0 {
+                println!(
+                    "collected {} sources, saved {} trends",
+                    report.collected, report.saved
+                );
+            }
+            for (name, e) in report.failures {
+                println!("failed to collect or save {}: {}", name, e);
+            }
+        }
+    }
+    // collects every source due at `now`
+    pub async fn run_once(&mut self, now: Instant) -> ScheduledCollectionReport {
+        let mut report = ScheduledCollectionReport::default();
+        let mut subscriptions = match self.sources.list_all().await {
+            Ok(subscriptions) => subscriptions,
+            Err(e) => {
+                report.failures.push(("sources".to_string(), e.to_string()));
+                return report;
+            }
+        };
+        for source in &self.shared_sources {
+            for user_id in &self.subscribers {
+                subscriptions.push((user_id.clone(), source.clone()));
+            }
+        }
+        // sources nobody subscribes to any more are forgotten, so one subscribed again is due at once
+        let keys: HashSet<_> = subscriptions
+            .iter()
+            .map(|(_, source)| collection_key(source))
+            .chain(
+                self.shared_collectors
+                    .iter()
+                    .map(|(name, _)| collector_key(name)),
+            )
+            .collect();
+        self.next_due.retain(|key, _| keys.contains(key));
+        let due = self.due_sources(subscriptions, now);
+        if due.is_empty() {
+            return report;
+        }
+
+        let mut made = vec![];
+        for (key, due) in &due {
+            if let Some(source) = &due.source {
+                let validators = self.shared_validators(due).await;
+                made.push((key.clone(), (self.make_collector)(source, validators)));
+            }
+        }
+        let mut collectors: Vec<_> = made
+            .iter()
+            .map(|(key, collector)| (key.clone(), collector))
+            .collect();
+        for (name, collector) in &self.shared_collectors {
+            let key = collector_key(name);
+            if due.contains_key(&key) {
+                collectors.push((key, collector));
+            }
+        }
+        // the keys of the due sources are unique
+        let results = AggregateRawTrendCollector::new(collectors)
+            .expect("keys are unique")
+            .collect_separately()
+            .await;
+        for (key, result) in results {
+            let due = &due[&key];
+            let collected = match result {
+                Ok(collected) => collected,
+                Err(e) => {
+                    report.failures.push((due.name.clone(), e.to_string()));
+                    self.next_due.insert(key, now + due.interval);
+                    continue;
+                }
+            };
+            report.collected += 1;
+            let wait = collected
+                .ttl()
+                .map_or(due.interval, |ttl| ttl.max(due.interval));
+            self.next_due.insert(key, now + wait);
+            let mut all_saved = true;
+            for info in collected.trends() {
+                for user_id in &due.users {
+                    let save_info =
+                        SaveNewTrendInfoBuilder::new(user_id.clone(), info.clone()).build();
+                    match save_new_trend(self.trends.as_ref(), save_info).await {
+                        Ok(_) => report.saved += 1,
+                        // seen on an earlier run, not a failure
+                        Err(UserTrendInfoRepositoryError::AlreadyExists(_)) => {
+                            report.already_saved += 1
+                        }
+                        Err(e) => {
+                            all_saved = false;
+                            report.failures.push((due.name.clone(), e.to_string()))
+                        }
+                    }
+                }
+            }
+            // kept only now, a feed whose trends were not all saved is downloaded again
+            if let (true, Some(validators), Some(source)) =
+                (all_saved, collected.validators(), &due.source)
+            {
+                for user_id in &due.users {
+                    let key = validator_key(user_id, source);
+                    // failing to remember only costs a full download next time
+                    if let Err(e) = self.validators.update(&key, validators.clone()).await {
+                        println!("failed to store validators of {}: {}", key, e);
+                    }
+                }
+            }
+        }
+        report
+    }
+    // the validators every subscriber has, a subscriber without them has not seen the feed yet
+    async fn shared_validators(&self, due: &DueSource) -> Option<FetchValidators> {
+        let source = due.source.as_ref()?;
+        let mut shared = None;
+        for user_id in &due.users {
+            let validators = self.validators.get(&validator_key(user_id, source)).await?;
+            match &shared {
+                Some(shared) if *shared != validators => return None,
+                _ => shared = Some(validators),
+            }
+        }
+        shared
+    }
+    fn due_sources(
+        &self,
+        subscriptions: Vec<(UserId, TrendSource)>,
+        now: Instant,
+    ) -> HashMap<String, DueSource> {
+        let mut due: HashMap<String, DueSource> = HashMap::new();
+        for (user_id, source) in subscriptions {
+            let key = collection_key(&source);
+            if self.next_due.get(&key).is_some_and(|next| *next > now) {
+                continue;
+            }
+            let interval = source.interval().unwrap_or(self.default_interval);
+            match due.get_mut(&key) {
+                // the shortest interval among the subscribers wins
+                Some(due) => {
+                    due.users.push(user_id);
+                    due.interval = due.interval.min(interval);
+                }
+                None => {
+                    due.insert(
+                        key,
+                        DueSource {
+                            name: source.name().to_string(),
+                            source: Some(source),
+                            users: vec![user_id],
+                            interval,
+                        },
+                    );
+                }
+            }
+        }
+        if self.subscribers.is_empty() {
+            return due;
+        }
+        for (name, _) in &self.shared_collectors {
+            let key = collector_key(name);
+            if self.next_due.get(&key).is_some_and(|next| *next > now) {
+                continue;
+            }
+            due.insert(
+                key,
+                DueSource {
+                    name: name.clone(),
+                    source: None,
+                    users: self.subscribers.clone(),
+                    interval: self.default_interval,
+                },
+            );
+        }
+        due
+    }
+}
+
+// what the trends of a source depend on, its name and interval are up to each subscriber
+fn collection_key(source: &TrendSource) -> String {
+    format!(
+        "{} {:?} {} {:?} {:?}",
+        source.url(),
+        source.format(),
+        source.service().to_str(),
+        source.min_score(),
+        source.keywords()
+    )
+}
+
+// apart from the keys of sources, which start with their url
+fn collector_key(name: &str) -> String {
+    format!("collector {}", name)
+}
+
+fn validator_key(user_id: &UserId, source: &TrendSource) -> String {
+    format!("{} {} {}", user_id, source.name(), source.url())
+}
+
+struct DueSource {
+    name: String,
+    // `None` for a shared collector
+    source: Option<TrendSource>,
+    users: Vec<UserId>,
+    interval: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct ScheduledCollectionReport {
+    pub collected: usize,
+    pub saved: usize,
+    pub already_saved: usize,
+    // name of the source and the reason
+    pub failures: Vec<(String, String)>,
+}
+
+#[cfg(test)]
+// the fake source repository is only used on the test thread
+#[allow(clippy::arc_with_non_send_sync)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::{
+        http::{header, HeaderMap, StatusCode},
+        routing::get,
+        Router,
+    };
+
+    use super::*;
+    use crate::{
+        raw::{
+            feed::{FeedRawTrendCollector, RemoteFeedRawTrendCollector},
+            Service,
+        },
+        repository::InMemoryUserTrendInfoRepository,
+        source::repository::fake::FakeTrendSourceRepository,
+    };
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0">
+  <channel>
+    <title>Example RSS Feed</title>
+    <item>
+      <title>Example Item 1</title>
+      <link>http://www.example.com/item1</link>
+      <pubDate>Sat, 22 Jun 2024 02:22:32 +0000</pubDate>
+    </item>
+    <item>
+      <title>Example Item 2</title>
+      <link>http://www.example.com/item2</link>
+      <pubDate>Sat, 22 Jun 2024 03:22:32 +0000</pubDate>
+    </item>
+  </channel>
+</rss>"#;
+    const RSS_WITH_TTL: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0">
+  <channel>
+    <title>Example RSS Feed</title>
+    <ttl>120</ttl>
+    <item>
+      <title>Example Item 1</title>
+      <link>http://www.example.com/item1</link>
+      <pubDate>Sat, 22 Jun 2024 02:22:32 +0000</pubDate>
+    </item>
+  </channel>
+</rss>"#;
+
+    async fn subscribe(sources: &FakeTrendSourceRepository, user_id: &str, source: TrendSource) {
+        sources.save(UserId::new(user_id), source).await.unwrap();
+    }
+
+    // a publisher which supports conditional requests,
+    // the requests are recorded as whether they were conditional
+    async fn serve_feed(body: &'static str, conditional: Arc<Mutex<Vec<bool>>>) -> String {
+        let app = Router::new().route(
+            "/feed",
+            get(move |headers: HeaderMap| {
+                let conditional = conditional.clone();
+                async move {
+                    let if_none_match = headers.get(header::IF_NONE_MATCH).cloned();
+                    conditional.lock().unwrap().push(if_none_match.is_some());
+                    if if_none_match.is_some_and(|etag| etag == "\"v1\"") {
+                        return (StatusCode::NOT_MODIFIED, [(header::ETAG, "\"v1\"")], "");
+                    }
+                    (StatusCode::OK, [(header::ETAG, "\"v1\"")], body)
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/feed", addr)
+    }
+    // every source is fetched from the url, the local test server is not public
+    fn remote_collector(
+        url: String,
+    ) -> impl Fn(&TrendSource, Option<FetchValidators>) -> RemoteFeedRawTrendCollector {
+        move |source, validators| {
+            let collector = RemoteFeedRawTrendCollector::new(url.clone(), source.service().clone())
+                .with_client(reqwest::Client::new());
+            match validators {
+                Some(validators) => collector.with_validators(validators),
+                None => collector,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn save_trends_for_each_subscribed_user() {
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        subscribe(&sources, "bob", TrendSource::aws_updates()).await;
+        let mut scheduler = TrendCollectionScheduler::new(trends.clone(), sources, |source, _| {
+            FeedRawTrendCollector::new(source.service().clone(), RSS.as_bytes())
+        });
+
+        let report = scheduler.run_once(Instant::now()).await;
+
+        // the url is fetched once for both users
+        assert_eq!(report.collected, 1);
+        assert_eq!(report.saved, 4);
+        assert!(report.failures.is_empty());
+        assert_eq!(trends.list(UserId::new("alice")).await.unwrap().len(), 2);
+        assert_eq!(trends.list(UserId::new("bob")).await.unwrap().len(), 2);
+    }
+    #[tokio::test]
+    async fn same_url_subscribed_differently_is_collected_for_each_way() {
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        let aws = TrendSource::aws_updates();
+        let renamed =
+            TrendSource::new("aws", aws.url(), None, Service::from("aws".to_string())).unwrap();
+        subscribe(&sources, "bob", renamed).await;
+        let mut scheduler = TrendCollectionScheduler::new(trends.clone(), sources, |source, _| {
+            FeedRawTrendCollector::new(source.service().clone(), RSS.as_bytes())
+        });
+
+        let report = scheduler.run_once(Instant::now()).await;
+
+        assert_eq!(report.collected, 2);
+        let alice = trends.list(UserId::new("alice")).await.unwrap();
+        let bob = trends.list(UserId::new("bob")).await.unwrap();
+        assert!(alice.iter().all(|trend| trend.from() == "aws_updates"));
+        assert!(bob.iter().all(|trend| trend.from() == "aws"));
+    }
+    #[tokio::test]
+    async fn already_saved_trends_are_not_failures() {
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        let mut scheduler = TrendCollectionScheduler::new(trends.clone(), sources, |source, _| {
+            FeedRawTrendCollector::new(source.service().clone(), RSS.as_bytes())
+        })
+        .with_default_interval(Duration::from_secs(60));
+        let start = Instant::now();
+
+        scheduler.run_once(start).await;
+        let report = scheduler.run_once(start + Duration::from_secs(60)).await;
+
+        assert_eq!(report.collected, 1);
+        assert_eq!(report.saved, 0);
+        assert_eq!(report.already_saved, 2);
+        assert!(report.failures.is_empty());
+    }
+    #[tokio::test]
+    async fn source_is_not_collected_before_interval_or_ttl() {
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(
+            &sources,
+            "alice",
+            TrendSource::aws_updates().with_interval(Duration::from_secs(60)),
+        )
+        .await;
+        let mut scheduler = TrendCollectionScheduler::new(trends, sources, |source, _| {
+            FeedRawTrendCollector::new(source.service().clone(), RSS_WITH_TTL.as_bytes())
+        });
+        let start = Instant::now();
+
+        assert_eq!(scheduler.run_once(start).await.collected, 1);
+        // the interval is a minute, but the feed asks for two hours
+        let report = scheduler
+            .run_once(start + Duration::from_secs(60 * 60))
+            .await;
+        assert_eq!(report.collected, 0);
+        let report = scheduler
+            .run_once(start + Duration::from_secs(2 * 60 * 60))
+            .await;
+        assert_eq!(report.collected, 1);
+    }
+    #[tokio::test]
+    async fn shared_sources_are_saved_for_each_subscriber() {
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        let mut scheduler = TrendCollectionScheduler::new(trends.clone(), sources, |source, _| {
+            FeedRawTrendCollector::new(source.service().clone(), RSS.as_bytes())
+        })
+        .with_shared_sources(
+            vec![TrendSource::aws_updates()],
+            vec![UserId::new("alice"), UserId::new("bob")],
+        );
+
+        let report = scheduler.run_once(Instant::now()).await;
+
+        // alice subscribes to it herself too, it is still fetched and saved once
+        assert_eq!(report.collected, 1);
+        assert_eq!(report.saved, 4);
+        assert_eq!(trends.list(UserId::new("bob")).await.unwrap().len(), 2);
+    }
+    #[tokio::test]
+    async fn shared_collector_is_run_for_subscribers() {
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        let mut scheduler = TrendCollectionScheduler::new(trends.clone(), sources, |source, _| {
+            FeedRawTrendCollector::new(source.service().clone(), RSS.as_bytes())
+        })
+        .with_shared_sources(vec![], vec![UserId::new("alice")])
+        .with_shared_collector(
+            "x",
+            FeedRawTrendCollector::new(Service::x(), RSS.as_bytes()),
+        )
+        .with_default_interval(Duration::from_secs(60));
+        let start = Instant::now();
+
+        let first = scheduler.run_once(start).await;
+        let early = scheduler.run_once(start + Duration::from_secs(30)).await;
+
+        assert_eq!(first.collected, 1);
+        assert_eq!(first.saved, 2);
+        assert_eq!(early.collected, 0);
+        let alice = trends.list(UserId::new("alice")).await.unwrap();
+        assert!(alice.iter().all(|trend| trend.from() == "x"));
+    }
+    #[tokio::test]
+    async fn source_subscribed_again_is_collected_at_once() {
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        let mut scheduler =
+            TrendCollectionScheduler::new(trends, sources.clone(), |source: &TrendSource, _| {
+                FeedRawTrendCollector::new(source.service().clone(), RSS.as_bytes())
+            });
+        let start = Instant::now();
+
+        scheduler.run_once(start).await;
+        sources
+            .delete(UserId::new("alice"), TrendSource::aws_updates().name())
+            .await
+            .unwrap();
+        scheduler.run_once(start + Duration::from_secs(60)).await;
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        let report = scheduler.run_once(start + Duration::from_secs(120)).await;
+
+        assert_eq!(report.collected, 1);
+    }
+    #[tokio::test]
+    async fn user_who_subscribes_later_gets_the_whole_feed() {
+        let conditional = Arc::new(Mutex::new(vec![]));
+        let url = serve_feed(RSS, conditional.clone()).await;
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        let mut scheduler =
+            TrendCollectionScheduler::new(trends.clone(), sources.clone(), remote_collector(url))
+                .with_default_interval(Duration::from_secs(60));
+        let start = Instant::now();
+
+        scheduler.run_once(start).await;
+        let not_modified = scheduler.run_once(start + Duration::from_secs(60)).await;
+        subscribe(&sources, "bob", TrendSource::aws_updates()).await;
+        let report = scheduler.run_once(start + Duration::from_secs(120)).await;
+
+        assert_eq!(not_modified.saved, 0);
+        assert_eq!(report.saved, 2);
+        assert_eq!(trends.list(UserId::new("bob")).await.unwrap().len(), 2);
+        assert_eq!(*conditional.lock().unwrap(), vec![false, true, false]);
+    }
+    #[tokio::test]
+    async fn validators_are_not_kept_when_the_feed_can_not_be_read() {
+        let conditional = Arc::new(Mutex::new(vec![]));
+        let url = serve_feed("<html></html>", conditional.clone()).await;
+        let trends = Arc::new(InMemoryUserTrendInfoRepository::new());
+        let sources = Arc::new(FakeTrendSourceRepository::new());
+        subscribe(&sources, "alice", TrendSource::aws_updates()).await;
+        let mut scheduler = TrendCollectionScheduler::new(trends, sources, remote_collector(url))
+            .with_default_interval(Duration::from_secs(60));
+        let start = Instant::now();
+
+        let first = scheduler.run_once(start).await;
+        scheduler.run_once(start + Duration::from_secs(60)).await;
+
+        assert_eq!(first.failures.len(), 1);
+        assert_eq!(*conditional.lock().unwrap(), vec![false, false]);
+    }
+}
@@ -0,0 +1,523 @@
+use std::{fmt::Display, path::Path, str::FromStr, time::Duration};
+
+pub mod repository;
+pub mod use_case;
+
+use crate::raw::{
+    feed::{FeedFormat, RemoteFeedRawTrendCollector},
+    hacker_news::{HackerNewsRawTrendCollector, StoryList},
+    public, AnyRawTrendCollector, Service,
+};
+
+// Where the trends are collected from.
+// format is detected from the body of the feed when it is not given.
+// A url of a story list of the Hacker News API is collected from the API instead of as a feed,
+// and its stories can be filtered by score and keywords.
+#[derive(Debug, Clone)]
+pub struct TrendSource {
+    name: String,
+    url: String,
+    format: Option<FeedFormat>,
+    service: Service,
+    interval: Option<Duration>,
+    min_score: Option<u32>,
+    keywords: Vec<String>,
+}
+impl TrendSource {
+    pub fn new(
+        name: impl Into<String>,
+        url: impl Into<String>,
+        format: Option<FeedFormat>,
+        service: Service,
+    ) -> Result<Self, TrendSourceError> {
+        let name = name.into();
+        let url = url.into();
+        if name.is_empty() {
+            return Err(TrendSourceError::EmptyName);
+        }
+        match reqwest::Url::parse(&url) {
+            Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => {
+                // names are resolved by `check_host` and by the client which fetches the feed
+                if public::literal_ip(parsed.host_str()).is_some_and(|ip| !public::is_public(ip)) {
+                    return Err(TrendSourceError::ForbiddenHost(url));
+                }
+            }
+            _ => return Err(TrendSourceError::InvalidUrl(url)),
+        }
+        Ok(Self {
+            name,
+            url,
+            format,
+            service,
+            interval: None,
+            min_score: None,
+            keywords: vec![],
+        })
+    }
+    // how often the scheduler collects this source, its default is used when not given
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+    // only stories with the score or more, for Hacker News sources
+    pub fn with_min_score(mut self, min_score: u32) -> Result<Self, TrendSourceError> {
+        if !self.is_hacker_news() {
+            return Err(TrendSourceError::UnsupportedFilter(self.name));
+        }
+        self.min_score = Some(min_score);
+        Ok(self)
+    }
+    // only stories with any of the keywords, for Hacker News sources
+    pub fn with_keywords(mut self, keywords: Vec<String>) -> Result<Self, TrendSourceError> {
+        if !self.is_hacker_news() {
+            return Err(TrendSourceError::UnsupportedFilter(self.name));
+        }
+        self.keywords = keywords;
+        Ok(self)
+    }
+    pub fn aws_updates() -> Self {
+        Self {
+            name: "aws_updates".to_string(),
+            url: "https://aws.amazon.com/jp/about-aws/whats-new/recent/feed/".to_string(),
+            format: Some(FeedFormat::Rss),
+            service: Service::aws_updates(),
+            interval: None,
+            min_score: None,
+            keywords: vec![],
+        }
+    }
+    pub fn hacker_news(list: StoryList) -> Self {
+        Self {
+            name: "hacker_news".to_string(),
+            url: format!(
+                "{}/{}",
+                HackerNewsRawTrendCollector::BASE_URL,
+                list.file_name()
+            ),
+            format: None,
+            service: Service::hacker_news(),
+            interval: None,
+            min_score: None,
+            keywords: vec![],
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    pub fn format(&self) -> Option<FeedFormat> {
+        self.format
+    }
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+    pub fn min_score(&self) -> Option<u32> {
+        self.min_score
+    }
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+    pub fn is_hacker_news(&self) -> bool {
+        HackerNewsRawTrendCollector::from_url(&self.url, self.service.clone()).is_some()
+    }
+    // the host must resolve to public addresses only, so users can not have the server
+    // fetch from its own network
+    pub async fn check_host(&self) -> Result<(), TrendSourceError> {
+        let host = reqwest::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()));
+        match host {
+            Some(host) if public::is_public_host(&host).await => Ok(()),
+            _ => Err(TrendSourceError::ForbiddenHost(self.url.clone())),
+        }
+    }
+    pub fn collector(&self) -> AnyRawTrendCollector {
+        if let Some(collector) =
+            HackerNewsRawTrendCollector::from_url(&self.url, self.service.clone())
+        {
+            let collector = collector.with_keywords(&self.keywords);
+            return AnyRawTrendCollector::HackerNews(match self.min_score {
+                Some(min_score) => collector.with_min_score(min_score),
+                None => collector,
+            });
+        }
+        let collector = RemoteFeedRawTrendCollector::new(self.url.clone(), self.service.clone());
+        AnyRawTrendCollector::Feed(match self.format {
+            Some(format) => collector.with_format(format),
+            None => collector,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum TrendSourceError {
+    EmptyName,
+    InvalidUrl(String),
+    // the url points to loopback, private or link local addresses
+    ForbiddenHost(String),
+    InvalidFormat(String),
+    // interval_minutes which is 0 or too large
+    InvalidInterval(u64),
+    DuplicateName(String),
+    NotFound(String),
+    // min_score or keywords given to a source which is not Hacker News
+    UnsupportedFilter(String),
+}
+impl Display for TrendSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrendSourceError::EmptyName => write!(f, "EmptyName"),
+            TrendSourceError::InvalidUrl(s) => write!(f, "InvalidUrl: {}", s),
+            TrendSourceError::ForbiddenHost(s) => write!(f, "ForbiddenHost: {}", s),
+            TrendSourceError::InvalidFormat(s) => write!(f, "InvalidFormat: {}", s),
+            TrendSourceError::InvalidInterval(minutes) => {
+                write!(f, "InvalidInterval: {} minutes", minutes)
+            }
+            TrendSourceError::DuplicateName(s) => write!(f, "DuplicateName: {}", s),
+            TrendSourceError::NotFound(s) => write!(f, "NotFound: {}", s),
+            TrendSourceError::UnsupportedFilter(s) => write!(f, "UnsupportedFilter: {}", s),
+        }
+    }
+}
+impl std::error::Error for TrendSourceError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrendSourceRegistry {
+    sources: Vec<TrendSource>,
+}
+impl TrendSourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // config file is chosen by its extension, `.toml` or `.json`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TrendSourceRegistryError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(TrendSourceRegistryError::IoError)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(TrendSourceRegistryError::UnknownExtension(
+                path.display().to_string(),
+            )),
+        }
+    }
+    pub fn from_toml(content: &str) -> Result<Self, TrendSourceRegistryError> {
+        let config: TrendSourcesConfig =
+            toml::from_str(content).map_err(TrendSourceRegistryError::TomlError)?;
+        config.try_into()
+    }
+    pub fn from_json(content: &str) -> Result<Self, TrendSourceRegistryError> {
+        let config: TrendSourcesConfig =
+            serde_json::from_str(content).map_err(TrendSourceRegistryError::JsonError)?;
+        config.try_into()
+    }
+    pub fn sources(&self) -> &[TrendSource] {
+        &self.sources
+    }
+    pub fn get(&self, name: &str) -> Option<&TrendSource> {
+        self.sources.iter().find(|source| source.name == name)
+    }
+    pub fn add(&mut self, source: TrendSource) -> Result<(), TrendSourceError> {
+        if self.get(&source.name).is_some() {
+            return Err(TrendSourceError::DuplicateName(source.name));
+        }
+        self.sources.push(source);
+        Ok(())
+    }
+    pub fn delete(&mut self, name: &str) -> Result<TrendSource, TrendSourceError> {
+        let index = self
+            .sources
+            .iter()
+            .position(|source| source.name == name)
+            .ok_or_else(|| TrendSourceError::NotFound(name.to_string()))?;
+        Ok(self.sources.remove(index))
+    }
+}
+
+#[derive(Debug)]
+pub enum TrendSourceRegistryError {
+    IoError(std::io::Error),
+    TomlError(toml::de::Error),
+    JsonError(serde_json::Error),
+    UnknownExtension(String),
+    InvalidSource(TrendSourceError),
+}
+impl Display for TrendSourceRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrendSourceRegistryError::IoError(e) => write!(f, "IoError: {}", e),
+            TrendSourceRegistryError::TomlError(e) => write!(f, "TomlError: {}", e),
+            TrendSourceRegistryError::JsonError(e) => write!(f, "JsonError: {}", e),
+            TrendSourceRegistryError::UnknownExtension(s) => {
+                write!(f, "UnknownExtension: {}", s)
+            }
+            TrendSourceRegistryError::InvalidSource(e) => write!(f, "InvalidSource: {}", e),
+        }
+    }
+}
+impl std::error::Error for TrendSourceRegistryError {}
+
+#[derive(serde::Deserialize)]
+struct TrendSourcesConfig {
+    #[serde(default)]
+    sources: Vec<TrendSourceConfig>,
+}
+// a source as written in the config file or sent to the api
+#[derive(serde::Deserialize)]
+pub struct TrendSourceConfig {
+    name: String,
+    url: String,
+    // `auto` or omitted means detect from the body
+    format: Option<String>,
+    // defaults to the name of the source, or hacker_news for Hacker News
+    service: Option<String>,
+    interval_minutes: Option<u64>,
+    // for Hacker News only
+    min_score: Option<u32>,
+    #[serde(default)]
+    keywords: Vec<String>,
+}
+impl TryFrom<TrendSourceConfig> for TrendSource {
+    type Error = TrendSourceError;
+    fn try_from(config: TrendSourceConfig) -> Result<Self, Self::Error> {
+        let format = match config.format.as_deref() {
+            None | Some("auto") => None,
+            Some(format) => Some(
+                FeedFormat::from_str(format)
+                    .map_err(|_| TrendSourceError::InvalidFormat(format.to_string()))?,
+            ),
+        };
+        let service = match config.service {
+            Some(service) => Service::from(service),
+            None if HackerNewsRawTrendCollector::from_url(&config.url, Service::hacker_news())
+                .is_some() =>
+            {
+                Service::hacker_news()
+            }
+            None => Service::from(config.name.clone()),
+        };
+        let mut source = TrendSource::new(config.name, config.url, format, service)?;
+        if let Some(minutes) = config.interval_minutes {
+            // 0 would have the scheduler collect the source over and over
+            let secs = minutes
+                .checked_mul(60)
+                .filter(|secs| *secs > 0)
+                .ok_or(TrendSourceError::InvalidInterval(minutes))?;
+            source = source.with_interval(Duration::from_secs(secs));
+        }
+        if let Some(min_score) = config.min_score {
+            source = source.with_min_score(min_score)?;
+        }
+        if !config.keywords.is_empty() {
+            source = source.with_keywords(config.keywords)?;
+        }
+        Ok(source)
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Source {
+    name: String,
+    url: String,
+    format: Option<FeedFormat>,
+    service: String,
+    interval_minutes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_score: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keywords: Vec<String>,
+}
+impl From<TrendSource> for Source {
+    fn from(source: TrendSource) -> Self {
+        Self {
+            service: source.service.to_str().to_string(),
+            interval_minutes: source.interval.map(|interval| interval.as_secs() / 60),
+            name: source.name,
+            url: source.url,
+            format: source.format,
+            min_score: source.min_score,
+            keywords: source.keywords,
+        }
+    }
+}
+impl TryFrom<TrendSourcesConfig> for TrendSourceRegistry {
+    type Error = TrendSourceRegistryError;
+    fn try_from(config: TrendSourcesConfig) -> Result<Self, Self::Error> {
+        let mut registry = TrendSourceRegistry::new();
+        for source in config.sources {
+            let source =
+                TrendSource::try_from(source).map_err(TrendSourceRegistryError::InvalidSource)?;
+            registry
+                .add(source)
+                .map_err(TrendSourceRegistryError::InvalidSource)?;
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_sources_from_toml() {
+        let toml = r#"
+[[sources]]
+name = "aws_updates"
+url = "https://aws.amazon.com/jp/about-aws/whats-new/recent/feed/"
+format = "rss"
+
+[[sources]]
+name = "rust_blog"
+url = "https://blog.rust-lang.org/feed.xml"
+service = "rust"
+interval_minutes = 120
+"#;
+        let registry = TrendSourceRegistry::from_toml(toml).unwrap();
+
+        assert_eq!(registry.sources().len(), 2);
+        let aws = registry.get("aws_updates").unwrap();
+        assert_eq!(aws.format(), Some(FeedFormat::Rss));
+        assert_eq!(aws.service().to_str(), "aws_updates");
+        let rust = registry.get("rust_blog").unwrap();
+        assert_eq!(rust.url(), "https://blog.rust-lang.org/feed.xml");
+        assert_eq!(rust.format(), None);
+        assert_eq!(rust.service().to_str(), "rust");
+        assert_eq!(rust.interval(), Some(Duration::from_secs(120 * 60)));
+        assert_eq!(aws.interval(), None);
+    }
+    #[test]
+    fn load_sources_from_json() {
+        let json = r#"
+{
+  "sources": [
+    { "name": "newsletter", "url": "https://example.com/feed.json", "format": "json_feed" }
+  ]
+}
+"#;
+        let registry = TrendSourceRegistry::from_json(json).unwrap();
+
+        assert_eq!(
+            registry.get("newsletter").unwrap().format(),
+            Some(FeedFormat::JsonFeed)
+        );
+    }
+    #[test]
+    fn load_sources_with_invalid_format_should_fail() {
+        let toml = r#"
+[[sources]]
+name = "aws_updates"
+url = "https://aws.amazon.com/jp/about-aws/whats-new/recent/feed/"
+format = "html"
+"#;
+        let result = TrendSourceRegistry::from_toml(toml);
+
+        assert!(matches!(
+            result,
+            Err(TrendSourceRegistryError::InvalidSource(
+                TrendSourceError::InvalidFormat(_)
+            ))
+        ));
+    }
+    #[test]
+    fn load_hacker_news_source_with_filters() {
+        let toml = r#"
+[[sources]]
+name = "hn_top"
+url = "https://hacker-news.firebaseio.com/v0/topstories.json"
+min_score = 100
+keywords = ["rust", "aws"]
+"#;
+        let registry = TrendSourceRegistry::from_toml(toml).unwrap();
+
+        let hn = registry.get("hn_top").unwrap();
+        assert!(hn.is_hacker_news());
+        assert_eq!(hn.service().to_str(), "hacker_news");
+        assert_eq!(hn.min_score(), Some(100));
+        assert_eq!(hn.keywords(), ["rust", "aws"]);
+    }
+    #[test]
+    fn feed_source_with_min_score_should_fail() {
+        let toml = r#"
+[[sources]]
+name = "rust_blog"
+url = "https://blog.rust-lang.org/feed.xml"
+min_score = 100
+"#;
+        let result = TrendSourceRegistry::from_toml(toml);
+
+        assert!(matches!(
+            result,
+            Err(TrendSourceRegistryError::InvalidSource(
+                TrendSourceError::UnsupportedFilter(_)
+            ))
+        ));
+    }
+    #[test]
+    fn source_with_zero_or_too_large_interval_should_fail() {
+        for minutes in [0, u64::MAX] {
+            let json = format!(
+                r#"{{ "sources": [{{ "name": "rust_blog", "url": "https://blog.rust-lang.org/feed.xml", "interval_minutes": {} }}] }}"#,
+                minutes
+            );
+            let result = TrendSourceRegistry::from_json(&json);
+
+            assert!(matches!(
+                result,
+                Err(TrendSourceRegistryError::InvalidSource(
+                    TrendSourceError::InvalidInterval(m)
+                )) if m == minutes
+            ));
+        }
+    }
+    #[test]
+    fn source_with_invalid_url_should_fail() {
+        let result = TrendSource::new("name", "ftp://example.com/feed", None, Service::x());
+
+        assert!(matches!(result, Err(TrendSourceError::InvalidUrl(_))));
+    }
+    #[tokio::test]
+    async fn source_on_local_network_should_fail() {
+        for url in [
+            "http://127.0.0.1:8080/feed",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/feed",
+        ] {
+            let result = TrendSource::new("name", url, None, Service::x());
+
+            assert!(
+                matches!(result, Err(TrendSourceError::ForbiddenHost(_))),
+                "{}",
+                url
+            );
+        }
+        let source =
+            TrendSource::new("name", "http://localhost:8080/feed", None, Service::x()).unwrap();
+        assert!(matches!(
+            source.check_host().await,
+            Err(TrendSourceError::ForbiddenHost(_))
+        ));
+    }
+    #[test]
+    fn registry_can_add_and_delete_source() {
+        let mut registry = TrendSourceRegistry::new();
+        registry.add(TrendSource::aws_updates()).unwrap();
+
+        assert!(matches!(
+            registry.add(TrendSource::aws_updates()),
+            Err(TrendSourceError::DuplicateName(_))
+        ));
+
+        registry.delete("aws_updates").unwrap();
+
+        assert!(registry.sources().is_empty());
+        assert!(matches!(
+            registry.delete("aws_updates"),
+            Err(TrendSourceError::NotFound(_))
+        ));
+    }
+}
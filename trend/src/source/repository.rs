@@ -0,0 +1,357 @@
+use std::{fmt::Display, path::PathBuf, time::Duration};
+
+use tokio::sync::Mutex;
+use user::UserId;
+
+use crate::raw::{feed::FeedFormat, Service};
+
+use super::TrendSource;
+
+pub trait TrendSourceRepository {
+    #[allow(async_fn_in_trait)]
+    async fn save(
+        &self,
+        user_id: UserId,
+        source: TrendSource,
+    ) -> Result<TrendSource, TrendSourceRepositoryError>;
+    #[allow(async_fn_in_trait)]
+    async fn list(&self, user_id: UserId) -> Result<Vec<TrendSource>, TrendSourceRepositoryError>;
+    #[allow(async_fn_in_trait)]
+    async fn delete(
+        &self,
+        user_id: UserId,
+        name: &str,
+    ) -> Result<TrendSource, TrendSourceRepositoryError>;
+    // sources of every user, for collecting in background
+    #[allow(async_fn_in_trait)]
+    async fn list_all(&self) -> Result<Vec<(UserId, TrendSource)>, TrendSourceRepositoryError>;
+}
+
+#[derive(Debug)]
+pub enum TrendSourceRepositoryError {
+    SaveError(String),
+    AlreadyExists(String),
+    ConvertError(String),
+    NotFoundError(String),
+}
+impl Display for TrendSourceRepositoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrendSourceRepositoryError::SaveError(s) => write!(f, "SaveError: {}", s),
+            TrendSourceRepositoryError::AlreadyExists(s) => write!(f, "AlreadyExists: {}", s),
+            TrendSourceRepositoryError::ConvertError(s) => write!(f, "ConvertError: {}", s),
+            TrendSourceRepositoryError::NotFoundError(s) => write!(f, "NotFoundError: {}", s),
+        }
+    }
+}
+impl std::error::Error for TrendSourceRepositoryError {}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TrendSourceEntity {
+    pub user_id: String,
+    pub name: String,
+    pub url: String,
+    pub format: Option<FeedFormat>,
+    pub service: String,
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub min_score: Option<u32>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+impl TrendSourceEntity {
+    pub fn new(user_id: &UserId, source: &TrendSource) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            name: source.name.clone(),
+            url: source.url.clone(),
+            format: source.format,
+            service: source.service.to_str().to_string(),
+            interval_secs: source.interval.map(|interval| interval.as_secs()),
+            min_score: source.min_score,
+            keywords: source.keywords.clone(),
+        }
+    }
+}
+impl TryInto<TrendSource> for TrendSourceEntity {
+    type Error = TrendSourceRepositoryError;
+    fn try_into(self) -> Result<TrendSource, Self::Error> {
+        let source = TrendSource::new(
+            self.name,
+            self.url,
+            self.format,
+            Service::from(self.service),
+        )
+        .map_err(|e| TrendSourceRepositoryError::ConvertError(e.to_string()))?;
+        let mut source = match self.interval_secs {
+            Some(secs) => source.with_interval(Duration::from_secs(secs)),
+            None => source,
+        };
+        if let Some(min_score) = self.min_score {
+            source = source
+                .with_min_score(min_score)
+                .map_err(|e| TrendSourceRepositoryError::ConvertError(e.to_string()))?;
+        }
+        if !self.keywords.is_empty() {
+            source = source
+                .with_keywords(self.keywords)
+                .map_err(|e| TrendSourceRepositoryError::ConvertError(e.to_string()))?;
+        }
+        Ok(source)
+    }
+}
+
+// Keeps every user's sources in one json file.
+// The whole file is rewritten on each change, which is fine for the number of sources a user has.
+pub struct JsonFileTrendSourceRepository {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+impl JsonFileTrendSourceRepository {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+    async fn read(&self) -> Result<Vec<TrendSourceEntity>, TrendSourceRepositoryError> {
+        match tokio::fs::read(&self.path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| TrendSourceRepositoryError::ConvertError(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(TrendSourceRepositoryError::SaveError(e.to_string())),
+        }
+    }
+    async fn write(
+        &self,
+        entities: &[TrendSourceEntity],
+    ) -> Result<(), TrendSourceRepositoryError> {
+        let bytes = serde_json::to_vec_pretty(entities)
+            .map_err(|e| TrendSourceRepositoryError::ConvertError(e.to_string()))?;
+        // write to a temporary file first, so a crash never leaves a half written store
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .map_err(|e| TrendSourceRepositoryError::SaveError(e.to_string()))?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .map_err(|e| TrendSourceRepositoryError::SaveError(e.to_string()))
+    }
+}
+impl TrendSourceRepository for JsonFileTrendSourceRepository {
+    async fn save(
+        &self,
+        user_id: UserId,
+        source: TrendSource,
+    ) -> Result<TrendSource, TrendSourceRepositoryError> {
+        let _guard = self.lock.lock().await;
+        let mut entities = self.read().await?;
+        if entities
+            .iter()
+            .any(|e| user_id.is_same(&e.user_id) && e.name == source.name)
+        {
+            return Err(TrendSourceRepositoryError::AlreadyExists(
+                source.name.clone(),
+            ));
+        }
+        entities.push(TrendSourceEntity::new(&user_id, &source));
+        self.write(&entities).await?;
+        Ok(source)
+    }
+    async fn list(&self, user_id: UserId) -> Result<Vec<TrendSource>, TrendSourceRepositoryError> {
+        let _guard = self.lock.lock().await;
+        self.read()
+            .await?
+            .into_iter()
+            .filter(|e| user_id.is_same(&e.user_id))
+            .map(|e| e.try_into())
+            .collect()
+    }
+    async fn delete(
+        &self,
+        user_id: UserId,
+        name: &str,
+    ) -> Result<TrendSource, TrendSourceRepositoryError> {
+        let _guard = self.lock.lock().await;
+        let mut entities = self.read().await?;
+        let index = entities
+            .iter()
+            .position(|e| user_id.is_same(&e.user_id) && e.name == name)
+            .ok_or_else(|| TrendSourceRepositoryError::NotFoundError(name.to_string()))?;
+        let entity = entities.remove(index);
+        self.write(&entities).await?;
+        entity.try_into()
+    }
+    async fn list_all(&self) -> Result<Vec<(UserId, TrendSource)>, TrendSourceRepositoryError> {
+        let _guard = self.lock.lock().await;
+        Ok(usable_sources(self.read().await?))
+    }
+}
+
+// An entry which can not be read any more, e.g. written by an older version with other rules,
+// is left out with a log, so it does not stop the collection of every other source.
+fn usable_sources(
+    entities: impl IntoIterator<Item = TrendSourceEntity>,
+) -> Vec<(UserId, TrendSource)> {
+    entities
+        .into_iter()
+        .filter_map(|e| {
+            let user_id = UserId::new(e.user_id.clone());
+            let name = e.name.clone();
+            match e.try_into() {
+                Ok(source) => Some((user_id, source)),
+                Err(e) => {
+                    println!("skipped source {} of {}: {}", name, user_id, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::cell::RefCell;
+
+    use user::UserId;
+
+    use crate::source::TrendSource;
+
+    use super::{
+        usable_sources, TrendSourceEntity, TrendSourceRepository, TrendSourceRepositoryError,
+    };
+
+    pub struct FakeTrendSourceRepository {
+        sources: RefCell<Vec<TrendSourceEntity>>,
+    }
+    impl Default for FakeTrendSourceRepository {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    impl FakeTrendSourceRepository {
+        pub fn new() -> Self {
+            Self {
+                sources: RefCell::new(vec![]),
+            }
+        }
+    }
+    impl TrendSourceRepository for FakeTrendSourceRepository {
+        async fn save(
+            &self,
+            user_id: UserId,
+            source: TrendSource,
+        ) -> Result<TrendSource, TrendSourceRepositoryError> {
+            if self
+                .sources
+                .borrow()
+                .iter()
+                .any(|e| user_id.is_same(&e.user_id) && e.name == source.name())
+            {
+                return Err(TrendSourceRepositoryError::AlreadyExists(
+                    source.name().to_string(),
+                ));
+            }
+            self.sources
+                .borrow_mut()
+                .push(TrendSourceEntity::new(&user_id, &source));
+            Ok(source)
+        }
+        async fn list(
+            &self,
+            user_id: UserId,
+        ) -> Result<Vec<TrendSource>, TrendSourceRepositoryError> {
+            self.sources
+                .borrow()
+                .iter()
+                .filter(|e| user_id.is_same(&e.user_id))
+                .map(|e| e.clone().try_into())
+                .collect()
+        }
+        async fn delete(
+            &self,
+            user_id: UserId,
+            name: &str,
+        ) -> Result<TrendSource, TrendSourceRepositoryError> {
+            let mut sources = self.sources.borrow_mut();
+            let index = sources
+                .iter()
+                .position(|e| user_id.is_same(&e.user_id) && e.name == name)
+                .ok_or_else(|| TrendSourceRepositoryError::NotFoundError(name.to_string()))?;
+            sources.remove(index).try_into()
+        }
+        async fn list_all(&self) -> Result<Vec<(UserId, TrendSource)>, TrendSourceRepositoryError> {
+            Ok(usable_sources(self.sources.borrow().iter().cloned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn json_file_repository_persists_sources_per_user() {
+        let path = std::env::temp_dir().join(format!(
+            "trend-sources-{}-persists.json",
+            std::process::id()
+        ));
+        let alice = UserId::new("alice");
+        let bob = UserId::new("bob");
+
+        let repository = JsonFileTrendSourceRepository::new(&path);
+        repository
+            .save(alice.clone(), TrendSource::aws_updates())
+            .await
+            .unwrap();
+        let result = repository
+            .save(alice.clone(), TrendSource::aws_updates())
+            .await;
+        assert!(matches!(
+            result,
+            Err(TrendSourceRepositoryError::AlreadyExists(_))
+        ));
+
+        // read back with a new repository, as after a restart
+        let repository = JsonFileTrendSourceRepository::new(&path);
+        let sources = repository.list(alice.clone()).await.unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].format(), Some(FeedFormat::Rss));
+        assert!(repository.list(bob.clone()).await.unwrap().is_empty());
+        assert!(repository.delete(bob, "aws_updates").await.is_err());
+
+        repository
+            .delete(alice.clone(), "aws_updates")
+            .await
+            .unwrap();
+        assert!(repository.list(alice).await.unwrap().is_empty());
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+    #[tokio::test]
+    async fn list_all_skips_sources_which_can_not_be_read() {
+        let path = std::env::temp_dir().join(format!(
+            "trend-sources-{}-unreadable.json",
+            std::process::id()
+        ));
+        let alice = UserId::new("alice");
+        let mut broken = TrendSourceEntity::new(&alice, &TrendSource::aws_updates());
+        broken.name = "broken".to_string();
+        broken.url = "ftp://example.com/feed".to_string();
+        let repository = JsonFileTrendSourceRepository::new(&path);
+        repository
+            .write(&[
+                broken,
+                TrendSourceEntity::new(&alice, &TrendSource::aws_updates()),
+            ])
+            .await
+            .unwrap();
+
+        let sources = repository.list_all().await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].1.name(), "aws_updates");
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt::Display;
+
+use user::UserId;
+
+use crate::raw::RawTrendCollector;
+
+use super::{
+    repository::{TrendSourceRepository, TrendSourceRepositoryError},
+    TrendSource,
+};
+
+// The source is accepted only when the collector can fetch it and read it as a feed,
+// so a typo in the url is noticed when adding, not on the next collection.
+// A name the user already has is rejected before anything is fetched.
+pub async fn add_trend_source<C: RawTrendCollector>(
+    repository: &impl TrendSourceRepository,
+    collector: &C,
+    user_id: UserId,
+    source: TrendSource,
+) -> Result<TrendSource, AddTrendSourceError> {
+    let exists = repository
+        .list(user_id.clone())
+        .await
+        .map_err(AddTrendSourceError::RepositoryError)?
+        .iter()
+        .any(|existing| existing.name() == source.name());
+    if exists {
+        return Err(AddTrendSourceError::RepositoryError(
+            TrendSourceRepositoryError::AlreadyExists(source.name().to_string()),
+        ));
+    }
+    collector
+        .collect()
+        .await
+        .map_err(|e| AddTrendSourceError::UnreadableFeed(e.to_string()))?;
+    repository
+        .save(user_id, source)
+        .await
+        .map_err(AddTrendSourceError::RepositoryError)
+}
+
+pub async fn list_trend_sources(
+    repository: &impl TrendSourceRepository,
+    user_id: UserId,
+) -> Result<Vec<TrendSource>, TrendSourceRepositoryError> {
+    repository.list(user_id).await
+}
+
+pub async fn delete_trend_source(
+    repository: &impl TrendSourceRepository,
+    user_id: UserId,
+    name: &str,
+) -> Result<TrendSource, TrendSourceRepositoryError> {
+    repository.delete(user_id, name).await
+}
+
+#[derive(Debug)]
+pub enum AddTrendSourceError {
+    UnreadableFeed(String),
+    RepositoryError(TrendSourceRepositoryError),
+}
+impl Display for AddTrendSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddTrendSourceError::UnreadableFeed(s) => write!(f, "UnreadableFeed: {}", s),
+            AddTrendSourceError::RepositoryError(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl std::error::Error for AddTrendSourceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        raw::{feed::FeedRawTrendCollector, Service},
+        source::repository::fake::FakeTrendSourceRepository,
+    };
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0">
+  <channel>
+    <title>Example RSS Feed</title>
+    <item>
+      <title>Example Item 1</title>
+      <pubDate>Sat, 22 Jun 2024 02:22:32 +0000</pubDate>
+    </item>
+  </channel>
+</rss>"#;
+
+    #[tokio::test]
+    async fn user_can_add_readable_source() {
+        let repository = FakeTrendSourceRepository::new();
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), RSS.as_bytes());
+        let user_id = UserId::new("user_id");
+
+        add_trend_source(
+            &repository,
+            &collector,
+            user_id.clone(),
+            TrendSource::aws_updates(),
+        )
+        .await
+        .unwrap();
+
+        let sources = list_trend_sources(&repository, user_id).await.unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].name(), "aws_updates");
+    }
+    #[tokio::test]
+    async fn user_can_not_add_unreadable_source() {
+        let repository = FakeTrendSourceRepository::new();
+        let collector =
+            FeedRawTrendCollector::new(Service::aws_updates(), "<html></html>".as_bytes());
+        let user_id = UserId::new("user_id");
+
+        let result = add_trend_source(
+            &repository,
+            &collector,
+            user_id.clone(),
+            TrendSource::aws_updates(),
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(AddTrendSourceError::UnreadableFeed(_))
+        ));
+        assert!(list_trend_sources(&repository, user_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+    #[tokio::test]
+    async fn existing_name_is_rejected_before_fetching() {
+        let repository = FakeTrendSourceRepository::new();
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), RSS.as_bytes());
+        let user_id = UserId::new("user_id");
+        add_trend_source(
+            &repository,
+            &collector,
+            user_id.clone(),
+            TrendSource::aws_updates(),
+        )
+        .await
+        .unwrap();
+        // would fail as an unreadable feed if it were read
+        let unreadable =
+            FeedRawTrendCollector::new(Service::aws_updates(), "<html></html>".as_bytes());
+
+        let result = add_trend_source(
+            &repository,
+            &unreadable,
+            user_id,
+            TrendSource::aws_updates(),
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(AddTrendSourceError::RepositoryError(
+                TrendSourceRepositoryError::AlreadyExists(_)
+            ))
+        ));
+    }
+    #[tokio::test]
+    async fn user_can_not_delete_other_users_source() {
+        let repository = FakeTrendSourceRepository::new();
+        let collector = FeedRawTrendCollector::new(Service::aws_updates(), RSS.as_bytes());
+        let owner = UserId::new("owner");
+        add_trend_source(
+            &repository,
+            &collector,
+            owner.clone(),
+            TrendSource::aws_updates(),
+        )
+        .await
+        .unwrap();
+
+        let result = delete_trend_source(&repository, UserId::new("other"), "aws_updates").await;
+
+        assert!(matches!(
+            result,
+            Err(TrendSourceRepositoryError::NotFoundError(_))
+        ));
+        delete_trend_source(&repository, owner, "aws_updates")
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+use std::convert::Infallible;
+
+use futures::StreamExt;
+
+use crate::{
+    domain::{Summary, UserTrendInfo, UserTrendInfoId},
+    raw::RawTrendInfo,
+};
+
+pub mod cache;
+pub mod extractive;
+pub mod llm;
+
+// Makes the gist shown in place of the description of a trend.
+pub trait TrendSummarizer {
+    type Error: std::error::Error;
+    #[allow(async_fn_in_trait)]
+    async fn summarize(&self, raw_info: &RawTrendInfo) -> Result<Summary, Self::Error>;
+}
+
+// Uses the fallback for the trends the primary could not summarize,
+// e.g. the extractive summarizer while a summary endpoint is down.
+pub struct FallbackSummarizer<P, F> {
+    primary: P,
+    fallback: F,
+}
+impl<P, F> FallbackSummarizer<P, F> {
+    pub fn new(primary: P, fallback: F) -> Self {
+        Self { primary, fallback }
+    }
+}
+impl<P: TrendSummarizer, F: TrendSummarizer> TrendSummarizer for FallbackSummarizer<P, F> {
+    type Error = F::Error;
+    async fn summarize(&self, raw_info: &RawTrendInfo) -> Result<Summary, Self::Error> {
+        match self.primary.summarize(raw_info).await {
+            Ok(summary) => Ok(summary),
+            Err(_) => self.fallback.summarize(raw_info).await,
+        }
+    }
+}
+
+// The summarizer chosen at startup, concrete so it can be kept in the state of the server.
+pub enum AnySummarizer {
+    Extractive(extractive::ExtractiveSummarizer),
+    Llm(FallbackSummarizer<llm::LlmSummarizer, extractive::ExtractiveSummarizer>),
+}
+impl TrendSummarizer for AnySummarizer {
+    type Error = Infallible;
+    async fn summarize(&self, raw_info: &RawTrendInfo) -> Result<Summary, Self::Error> {
+        match self {
+            AnySummarizer::Extractive(summarizer) => summarizer.summarize(raw_info).await,
+            AnySummarizer::Llm(summarizer) => summarizer.summarize(raw_info).await,
+        }
+    }
+}
+
+// summaries asked of an endpoint at once
+const CONCURRENCY: usize = 4;
+
+// Summaries are not stored, they are attached when trends are shown.
+// A trend which could not be summarized is shown without a summary,
+// the failures are returned for the caller to report.
+pub async fn summarize_trends<S: TrendSummarizer>(
+    summarizer: &S,
+    user_trends: &mut [UserTrendInfo],
+) -> Vec<(UserTrendInfoId, S::Error)> {
+    // futures do nothing until polled, the stream starts a few at a time
+    let pending: Vec<_> = user_trends
+        .iter()
+        .map(|user_trend| summarizer.summarize(user_trend.raw_info()))
+        .collect();
+    let summaries: Vec<_> = futures::stream::iter(pending)
+        .buffered(CONCURRENCY)
+        .collect()
+        .await;
+    let mut failures = vec![];
+    for (user_trend, summary) in user_trends.iter_mut().zip(summaries) {
+        match summary {
+            Ok(summary) => user_trend.set_summary(summary),
+            Err(e) => failures.push((user_trend.id().clone(), e)),
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use date::Date;
+
+    use super::*;
+    use crate::raw::Service;
+
+    #[derive(Debug)]
+    struct Unavailable;
+    impl std::fmt::Display for Unavailable {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Unavailable")
+        }
+    }
+    impl std::error::Error for Unavailable {}
+
+    // summarizes only trends with a description
+    struct FakeSummarizer;
+    impl TrendSummarizer for FakeSummarizer {
+        type Error = Unavailable;
+        async fn summarize(&self, raw_info: &RawTrendInfo) -> Result<Summary, Self::Error> {
+            if raw_info.desc().is_empty() {
+                return Err(Unavailable);
+            }
+            Ok(Summary::new(raw_info.desc()))
+        }
+    }
+
+    #[tokio::test]
+    async fn trends_which_fail_are_left_without_summary() {
+        let trend = |id: &str, desc: &str| {
+            UserTrendInfo::new(
+                UserTrendInfoId::new(id),
+                RawTrendInfo::new("title", id, desc, Service::aws_updates(), Date::now()),
+            )
+        };
+        let mut user_trends = vec![trend("1", "gist"), trend("2", "")];
+
+        let failures = summarize_trends(&FakeSummarizer, &mut user_trends).await;
+
+        assert_eq!(user_trends[0].summary().unwrap().as_str(), "gist");
+        assert!(user_trends[1].summary().is_none());
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, UserTrendInfoId::new("2"));
+    }
+
+    #[tokio::test]
+    async fn fallback_summarizes_what_primary_could_not() {
+        let summarizer =
+            FallbackSummarizer::new(FakeSummarizer, extractive::ExtractiveSummarizer::new());
+        let raw_info = |desc: &str| {
+            RawTrendInfo::new("title", "link", desc, Service::aws_updates(), Date::now())
+        };
+
+        let primary = summarizer.summarize(&raw_info("gist")).await.unwrap();
+        let fallback = summarizer.summarize(&raw_info("")).await.unwrap();
+
+        assert_eq!(primary.as_str(), "gist");
+        assert!(fallback.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use tokio::sync::Mutex;
+
+use crate::domain::Summary;
+
+// Summaries by the link of the trend, so an item is summarized once.
+// They are written to the file on every change when a path is given,
+// so a restart of the server does not ask for every summary again.
+pub struct SummaryCache {
+    path: Option<PathBuf>,
+    summaries: Mutex<HashMap<String, String>>,
+}
+impl SummaryCache {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            summaries: Mutex::new(HashMap::new()),
+        }
+    }
+    pub async fn load(path: impl Into<PathBuf>) -> Result<Self, SummaryCacheError> {
+        let path = path.into();
+        let summaries = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| SummaryCacheError::ConvertError(e.to_string()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(SummaryCacheError::IoError(e.to_string())),
+        };
+        Ok(Self {
+            path: Some(path),
+            summaries: Mutex::new(summaries),
+        })
+    }
+    pub async fn get(&self, link: &str) -> Option<Summary> {
+        self.summaries.lock().await.get(link).map(Summary::new)
+    }
+    pub async fn insert(&self, link: &str, summary: &Summary) -> Result<(), SummaryCacheError> {
+        let mut all = self.summaries.lock().await;
+        all.insert(link.to_string(), summary.as_str().to_string());
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let bytes = serde_json::to_vec_pretty(&*all)
+            .map_err(|e| SummaryCacheError::ConvertError(e.to_string()))?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .map_err(|e| SummaryCacheError::IoError(e.to_string()))?;
+        tokio::fs::rename(&tmp, path)
+            .await
+            .map_err(|e| SummaryCacheError::IoError(e.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum SummaryCacheError {
+    IoError(String),
+    ConvertError(String),
+}
+impl std::fmt::Display for SummaryCacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SummaryCacheError::IoError(s) => write!(f, "IoError: {}", s),
+            SummaryCacheError::ConvertError(s) => write!(f, "ConvertError: {}", s),
+        }
+    }
+}
+impl std::error::Error for SummaryCacheError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn summaries_are_kept_after_reload() {
+        let path =
+            std::env::temp_dir().join(format!("trend-summary-cache-{}.json", std::process::id()));
+        let _ = tokio::fs::remove_file(&path).await;
+
+        let cache = SummaryCache::load(&path).await.unwrap();
+        cache
+            .insert("https://example.com/1", &Summary::new("gist"))
+            .await
+            .unwrap();
+        let reloaded = SummaryCache::load(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(
+            reloaded
+                .get("https://example.com/1")
+                .await
+                .unwrap()
+                .as_str(),
+            "gist"
+        );
+        assert!(reloaded.get("https://example.com/2").await.is_none());
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+};
+
+use crate::{
+    domain::Summary,
+    raw::{description::html_to_text, RawTrendInfo},
+};
+
+use super::TrendSummarizer;
+
+// Picks the sentences of the description which say the most, without any service.
+// A sentence scores by how often its words appear in the whole description
+// and by how many words it shares with the title.
+// The picked sentences are kept in the order of the description.
+#[derive(Debug, Clone)]
+pub struct ExtractiveSummarizer {
+    max_sentences: usize,
+    // counted in chars, a longer summary is cut and ends with `…`
+    max_chars: usize,
+}
+impl Default for ExtractiveSummarizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ExtractiveSummarizer {
+    // about two lines of the list view
+    const DEFAULT_MAX_SENTENCES: usize = 2;
+    const DEFAULT_MAX_CHARS: usize = 200;
+    const TITLE_WEIGHT: f64 = 2.0;
+    // announcements usually say what is new first
+    const LEAD_WEIGHT: f64 = 1.2;
+    pub fn new() -> Self {
+        Self {
+            max_sentences: Self::DEFAULT_MAX_SENTENCES,
+            max_chars: Self::DEFAULT_MAX_CHARS,
+        }
+    }
+    pub fn with_max_sentences(mut self, max_sentences: usize) -> Self {
+        self.max_sentences = max_sentences.max(1);
+        self
+    }
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars.max(1);
+        self
+    }
+    pub fn summarize_text(&self, title: &str, html: &str) -> Summary {
+        let sentences = split_sentences(&html_to_text(html));
+        let sentence_tokens: Vec<Vec<String>> = sentences.iter().map(|s| tokens(s)).collect();
+        let mut frequency: HashMap<&str, usize> = HashMap::new();
+        for token in sentence_tokens.iter().flatten() {
+            *frequency.entry(token.as_str()).or_default() += 1;
+        }
+        let title: HashSet<String> = tokens(title).into_iter().collect();
+
+        let mut scored: Vec<(usize, f64)> = sentence_tokens
+            .iter()
+            .enumerate()
+            .map(|(index, tokens)| {
+                if tokens.is_empty() {
+                    return (index, 0.0);
+                }
+                let sum: f64 = tokens
+                    .iter()
+                    .map(|token| {
+                        let in_title = if title.contains(token) {
+                            Self::TITLE_WEIGHT
+                        } else {
+                            0.0
+                        };
+                        frequency[token.as_str()] as f64 + in_title
+                    })
+                    .sum();
+                // long sentences score higher, but not in proportion to their length
+                let mut score = sum / (tokens.len() as f64).sqrt();
+                if index == 0 {
+                    score *= Self::LEAD_WEIGHT;
+                }
+                (index, score)
+            })
+            .collect();
+        // the earlier sentence wins a tie, sorting is stable
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut picked: Vec<usize> = scored
+            .into_iter()
+            .take(self.max_sentences)
+            .map(|(index, _)| index)
+            .collect();
+        picked.sort();
+
+        let mut summary = String::new();
+        for index in picked {
+            let sentence = &sentences[index];
+            // Japanese sentences are not separated by spaces
+            if summary.chars().last().is_some_and(|c| !is_cjk_stop(c)) {
+                summary.push(' ');
+            }
+            summary.push_str(sentence);
+        }
+        Summary::new(truncate(&summary, self.max_chars))
+    }
+}
+impl TrendSummarizer for ExtractiveSummarizer {
+    type Error = Infallible;
+    async fn summarize(&self, raw_info: &RawTrendInfo) -> Result<Summary, Self::Error> {
+        Ok(self.summarize_text(raw_info.title(), raw_info.desc()))
+    }
+}
+
+// words which end with `.` without ending the sentence
+const ABBREVIATIONS: &[&str] = &["e.g", "i.e", "etc", "vs", "inc", "mr", "ms", "dr", "u.s"];
+
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = vec![];
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut start = 0;
+        for (i, &(at, c)) in chars.iter().enumerate() {
+            let end = at + c.len_utf8();
+            let is_end = match c {
+                c if is_cjk_stop(c) => true,
+                '.' | '!' | '?' => {
+                    chars.get(i + 1).is_none_or(|(_, next)| *next == ' ')
+                        && !(c == '.' && is_abbreviation(&line[start..at]))
+                }
+                _ => false,
+            };
+            if is_end {
+                push_sentence(&mut sentences, &line[start..end]);
+                start = end;
+            }
+        }
+        push_sentence(&mut sentences, &line[start..]);
+    }
+    sentences
+}
+fn push_sentence(sentences: &mut Vec<String>, sentence: &str) {
+    let sentence = sentence.trim();
+    if !sentence.is_empty() {
+        sentences.push(sentence.to_string());
+    }
+}
+fn is_abbreviation(before: &str) -> bool {
+    let word = before.split(' ').next_back().unwrap_or("").to_lowercase();
+    // a single letter, like the initial of a name
+    ABBREVIATIONS.contains(&word.as_str())
+        || (word.chars().count() == 1 && word.chars().all(char::is_alphabetic))
+}
+fn is_cjk_stop(c: char) -> bool {
+    matches!(c, '。' | '！' | '？')
+}
+
+// words which say nothing about the topic
+const STOP_WORDS: &[&str] = &[
+    "an", "and", "are", "as", "at", "be", "by", "can", "for", "from", "has", "have", "in", "is",
+    "it", "its", "now", "of", "on", "or", "that", "the", "this", "to", "with", "you", "your",
+];
+
+// Lowercase words, and pairs of chars of text written without spaces like Japanese.
+fn tokens(sentence: &str) -> Vec<String> {
+    fn push_word(tokens: &mut Vec<String>, word: &mut String) {
+        if word.len() > 1 && !STOP_WORDS.contains(&word.as_str()) {
+            tokens.push(word.clone());
+        }
+        word.clear();
+    }
+    fn push_run(tokens: &mut Vec<String>, run: &mut Vec<char>) {
+        if run.len() == 1 {
+            tokens.push(run[0].to_string());
+        }
+        tokens.extend(
+            run.windows(2)
+                // pairs of hiragana are mostly particles and endings
+                .filter(|pair| !pair.iter().all(|c| ('\u{3041}'..='\u{309f}').contains(c)))
+                .map(|pair| pair.iter().collect::<String>()),
+        );
+        run.clear();
+    }
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut run = vec![];
+    for c in sentence.chars() {
+        if c.is_ascii_alphanumeric() {
+            push_run(&mut tokens, &mut run);
+            word.push(c.to_ascii_lowercase());
+        } else if c.is_alphanumeric() {
+            push_word(&mut tokens, &mut word);
+            run.push(c);
+        } else {
+            push_word(&mut tokens, &mut word);
+            push_run(&mut tokens, &mut run);
+        }
+    }
+    push_word(&mut tokens, &mut word);
+    push_run(&mut tokens, &mut run);
+    tokens
+}
+
+// cut between words when the text has spaces
+pub(super) fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars - 1).collect();
+    let next = text.chars().nth(max_chars - 1);
+    let cut = match cut.rfind(' ') {
+        Some(space) if next.is_some_and(|c| c.is_ascii_alphanumeric()) => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentences_are_split_in_english_and_japanese() {
+        let text = "Use e.g. Amazon S3. It is 2.5x faster! Done\n送電設備を監視。生成 AI を活用";
+
+        assert_eq!(
+            split_sentences(text),
+            vec![
+                "Use e.g. Amazon S3.",
+                "It is 2.5x faster!",
+                "Done",
+                "送電設備を監視。",
+                "生成 AI を活用",
+            ]
+        );
+    }
+    #[test]
+    fn sentences_about_the_title_are_picked_in_order() {
+        let html = "<p>Today we are excited to share news. \
+            AWS Lambda now supports response streaming for Lambda functions. \
+            Streaming lowers the time to first byte of Lambda functions. \
+            Our team worked hard on it.</p>";
+
+        let summary = ExtractiveSummarizer::new().summarize_text("Lambda response streaming", html);
+
+        assert_eq!(
+            summary.as_str(),
+            "AWS Lambda now supports response streaming for Lambda functions. \
+            Streaming lowers the time to first byte of Lambda functions."
+        );
+    }
+    #[test]
+    fn japanese_description_is_summarized() {
+        let html = "<p>機械学習と IoT 技術を活用し、送電設備の予知保全やリアルタイムでの状態監視保全を実施<br> \
+            今後は生成 AI を活用し、さらに保全業務の効率化を加速<br> </p>";
+
+        let summary = ExtractiveSummarizer::new()
+            .with_max_sentences(1)
+            .summarize_text("送電設備の予知保全に AWS を活用", html);
+
+        assert_eq!(
+            summary.as_str(),
+            "機械学習と IoT 技術を活用し、送電設備の予知保全やリアルタイムでの状態監視保全を実施"
+        );
+    }
+    #[test]
+    fn long_summary_is_cut_between_words() {
+        let summarizer = ExtractiveSummarizer::new().with_max_chars(20);
+
+        assert_eq!(
+            summarizer
+                .summarize_text("", "Amazon Bedrock is now available in Tokyo.")
+                .as_str(),
+            "Amazon Bedrock is…"
+        );
+        assert_eq!(
+            summarizer
+                .summarize_text(
+                    "",
+                    "生成 AI を活用し、さらに保全業務の効率化を加速する取り組み"
+                )
+                .as_str(),
+            "生成 AI を活用し、さらに保全業務の…"
+        );
+        assert!(summarizer.summarize_text("title", "").is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{domain::Summary, raw::RawTrendInfo};
+
+use super::{cache::SummaryCache, extractive::truncate, TrendSummarizer};
+
+// Asks a chat completions endpoint compatible with OpenAI for the summary,
+// e.g. OpenAI itself, Ollama or llama.cpp server.
+// Summaries are cached by link, an item is sent once.
+// When the endpoint is down it is not asked again for a while,
+// so a listing does not wait for a timeout of every trend.
+pub struct LlmSummarizer {
+    client: reqwest::Client,
+    // e.g. `https://api.openai.com/v1`, `/chat/completions` is appended
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    // `{title}` and `{desc}` are replaced by those of the trend
+    prompt: String,
+    // the most tokens the summary may take
+    max_tokens: u32,
+    // the description is cut at this many chars before it is sent
+    max_input_chars: usize,
+    retry_after: Duration,
+    cache: Arc<SummaryCache>,
+    down_until: Mutex<Option<Instant>>,
+}
+impl LlmSummarizer {
+    pub const DEFAULT_PROMPT: &'static str =
+        "Summarize the following announcement in one or two sentences, \
+in the language it is written in. Answer with the summary only.\n\nTitle: {title}\n\n{desc}";
+    const DEFAULT_MAX_TOKENS: u32 = 120;
+    const DEFAULT_MAX_INPUT_CHARS: usize = 4000;
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+    const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            client: Self::client(Self::DEFAULT_TIMEOUT),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+            api_key: None,
+            prompt: Self::DEFAULT_PROMPT.to_string(),
+            max_tokens: Self::DEFAULT_MAX_TOKENS,
+            max_input_chars: Self::DEFAULT_MAX_INPUT_CHARS,
+            retry_after: Self::DEFAULT_RETRY_AFTER,
+            cache: Arc::new(SummaryCache::in_memory()),
+            down_until: Mutex::new(None),
+        }
+    }
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens.max(1);
+        self
+    }
+    pub fn with_max_input_chars(mut self, max_input_chars: usize) -> Self {
+        self.max_input_chars = max_input_chars.max(1);
+        self
+    }
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = Self::client(timeout);
+        self
+    }
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+    pub fn with_cache(mut self, cache: Arc<SummaryCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+    fn client(timeout: Duration) -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("failed to build the http client")
+    }
+    fn prompt_of(&self, raw_info: &RawTrendInfo) -> String {
+        let desc = truncate(raw_info.desc_text(), self.max_input_chars);
+        // one pass over the template, a placeholder inside the title is left as it is
+        let mut prompt = String::with_capacity(self.prompt.len() + desc.len());
+        let mut rest = self.prompt.as_str();
+        while let Some(start) = rest.find('{') {
+            prompt.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(after) = rest.strip_prefix("{title}") {
+                prompt.push_str(raw_info.title());
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{desc}") {
+                prompt.push_str(&desc);
+                rest = after;
+            } else {
+                prompt.push('{');
+                rest = &rest[1..];
+            }
+        }
+        prompt.push_str(rest);
+        prompt
+    }
+    fn is_down(&self) -> bool {
+        let mut down_until = self.down_until.lock().unwrap();
+        match *down_until {
+            Some(until) if Instant::now() < until => true,
+            Some(_) => {
+                *down_until = None;
+                false
+            }
+            None => false,
+        }
+    }
+    fn mark_down(&self) {
+        let mut down_until = self.down_until.lock().unwrap();
+        if down_until.is_none() {
+            println!(
+                "summary endpoint {} is down, retrying after {}s",
+                self.base_url,
+                self.retry_after.as_secs()
+            );
+        }
+        *down_until = Some(Instant::now() + self.retry_after);
+    }
+    async fn complete(&self, prompt: String) -> Result<Summary, LlmSummarizerError> {
+        let body = serde_json::to_vec(&ChatRequest {
+            model: &self.model,
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: prompt,
+            }],
+            max_tokens: self.max_tokens,
+        })
+        .map_err(|e| LlmSummarizerError::ConvertError(e.to_string()))?;
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await.map_err(|e| {
+            self.mark_down();
+            LlmSummarizerError::RequestError(e.to_string())
+        })?;
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            self.mark_down();
+        }
+        if !status.is_success() {
+            return Err(LlmSummarizerError::StatusError(status.as_u16()));
+        }
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| LlmSummarizerError::RequestError(e.to_string()))?;
+        let response: ChatResponse = serde_json::from_slice(&bytes)
+            .map_err(|e| LlmSummarizerError::ConvertError(e.to_string()))?;
+        let summary = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| Summary::new(choice.message.content.trim()))
+            .filter(|summary| !summary.is_empty())
+            .ok_or(LlmSummarizerError::EmptyResponse)?;
+        Ok(summary)
+    }
+}
+impl TrendSummarizer for LlmSummarizer {
+    type Error = LlmSummarizerError;
+    async fn summarize(&self, raw_info: &RawTrendInfo) -> Result<Summary, Self::Error> {
+        if let Some(summary) = self.cache.get(raw_info.link()).await {
+            return Ok(summary);
+        }
+        if self.is_down() {
+            return Err(LlmSummarizerError::Unavailable);
+        }
+        let summary = self.complete(self.prompt_of(raw_info)).await?;
+        // failing to remember only costs another request next time
+        if let Err(e) = self.cache.insert(raw_info.link(), &summary).await {
+            println!("failed to cache summary of {}: {}", raw_info.link(), e);
+        }
+        Ok(summary)
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage>,
+    max_tokens: u32,
+}
+#[derive(Serialize, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Debug)]
+pub enum LlmSummarizerError {
+    // the endpoint failed a moment ago and is not asked until `retry_after` passes
+    Unavailable,
+    RequestError(String),
+    StatusError(u16),
+    ConvertError(String),
+    EmptyResponse,
+}
+impl std::fmt::Display for LlmSummarizerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LlmSummarizerError::Unavailable => write!(f, "Unavailable"),
+            LlmSummarizerError::RequestError(s) => write!(f, "RequestError: {}", s),
+            LlmSummarizerError::StatusError(status) => write!(f, "StatusError: {}", status),
+            LlmSummarizerError::ConvertError(s) => write!(f, "ConvertError: {}", s),
+            LlmSummarizerError::EmptyResponse => write!(f, "EmptyResponse"),
+        }
+    }
+}
+impl std::error::Error for LlmSummarizerError {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{http::HeaderMap, routing::post, Json, Router};
+    use date::Date;
+
+    use super::*;
+    use crate::raw::Service;
+
+    // stands in for a chat completions endpoint, answers with the start of the prompt
+    async fn serve_completions(requests: Arc<AtomicUsize>, status: StatusCode) -> String {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(
+                move |headers: HeaderMap, Json(request): Json<serde_json::Value>| {
+                    let requests = requests.clone();
+                    async move {
+                        requests.fetch_add(1, Ordering::SeqCst);
+                        assert_eq!(headers[header::AUTHORIZATION], "Bearer secret");
+                        assert_eq!(request["model"], "tiny");
+                        assert_eq!(request["max_tokens"], 50);
+                        let prompt = request["messages"][0]["content"].as_str().unwrap();
+                        let answer = prompt.lines().next().unwrap().to_string();
+                        let body = serde_json::json!({
+                            "choices": [{"message": {"role": "assistant", "content": answer}}]
+                        });
+                        (status, Json(body))
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/v1/", addr)
+    }
+
+    fn raw_info(link: &str) -> RawTrendInfo {
+        RawTrendInfo::new(
+            "S3 is faster",
+            link,
+            "<p>Amazon S3 now answers in half the time.</p>",
+            Service::aws_updates(),
+            Date::now(),
+        )
+    }
+
+    fn summarizer(base_url: &str) -> LlmSummarizer {
+        LlmSummarizer::new(base_url, "tiny")
+            .with_api_key("secret")
+            .with_prompt("{title}: {desc}\nin one sentence")
+            .with_max_tokens(50)
+    }
+
+    #[test]
+    fn placeholders_in_the_title_are_not_replaced() {
+        let info = RawTrendInfo::new(
+            "What {desc} means",
+            "http://example.com/1",
+            "the description",
+            Service::aws_updates(),
+            Date::now(),
+        );
+
+        let prompt = summarizer("http://127.0.0.1:1/v1/").prompt_of(&info);
+
+        assert_eq!(
+            prompt,
+            "What {desc} means: the description\nin one sentence"
+        );
+    }
+    #[tokio::test]
+    async fn each_link_is_summarized_once() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let base_url = serve_completions(requests.clone(), StatusCode::OK).await;
+        let summarizer = summarizer(&base_url);
+
+        let first = summarizer
+            .summarize(&raw_info("https://example.com/1"))
+            .await;
+        let again = summarizer
+            .summarize(&raw_info("https://example.com/1"))
+            .await;
+        let other = summarizer
+            .summarize(&raw_info("https://example.com/2"))
+            .await;
+
+        assert_eq!(
+            first.unwrap().as_str(),
+            "S3 is faster: Amazon S3 now answers in half the time."
+        );
+        assert!(again.is_ok());
+        assert!(other.is_ok());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn endpoint_which_fails_is_not_asked_for_a_while() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let base_url = serve_completions(requests.clone(), StatusCode::SERVICE_UNAVAILABLE).await;
+        let summarizer = summarizer(&base_url);
+
+        let first = summarizer
+            .summarize(&raw_info("https://example.com/1"))
+            .await;
+        let second = summarizer
+            .summarize(&raw_info("https://example.com/2"))
+            .await;
+
+        assert!(matches!(first, Err(LlmSummarizerError::StatusError(503))));
+        assert!(matches!(second, Err(LlmSummarizerError::Unavailable)));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn endpoint_which_is_down_is_retried_later() {
+        // nothing listens on the port once the listener is dropped
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        drop(listener);
+        let summarizer = summarizer(&base_url).with_retry_after(Duration::ZERO);
+
+        let first = summarizer
+            .summarize(&raw_info("https://example.com/1"))
+            .await;
+        let second = summarizer
+            .summarize(&raw_info("https://example.com/1"))
+            .await;
+
+        assert!(matches!(first, Err(LlmSummarizerError::RequestError(_))));
+        assert!(matches!(second, Err(LlmSummarizerError::RequestError(_))));
+    }
+}
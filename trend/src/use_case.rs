@@ -1,8 +1,15 @@
+use std::{fmt::Display, str::FromStr};
+
+use date::Date;
 use user::UserId;
 
 use crate::{
-    domain::{Status, UserTrendInfo},
-    raw::RawTrendInfo,
+    domain::{
+        Status, Tag, TrendHistoryRecord, UserTrend, UserTrendInfo, UserTrendInfoError,
+        UserTrendInfoId,
+    },
+    raw::{RawTrendInfo, Service},
+    workflow::Workflow,
 };
 
 pub trait UserTrendInfoRepository {
@@ -11,16 +18,42 @@ pub trait UserTrendInfoRepository {
         &self,
         user_trend: SaveNewTrendInfo,
     ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError>;
+    // a trend of another user is NotFoundError and is left as it is
     #[allow(async_fn_in_trait)]
     async fn update(
         &self,
+        user_id: UserId,
         user_trend: UserTrendInfo,
     ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError>;
+    // the trends come without their history, see `get`
     #[allow(async_fn_in_trait)]
     async fn list(
         &self,
         user_id: UserId,
     ) -> Result<Vec<UserTrendInfo>, UserTrendInfoRepositoryError>;
+    // one page of the trends of the user of the query, without their history
+    #[allow(async_fn_in_trait)]
+    async fn search(&self, query: &TrendQuery) -> Result<TrendPage, UserTrendInfoRepositoryError>;
+    // the trend with its stored history
+    // a trend of another user is NotFoundError, as if it does not exist
+    #[allow(async_fn_in_trait)]
+    async fn get(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError>;
+    #[allow(async_fn_in_trait)]
+    async fn delete(
+        &self,
+        user_id: UserId,
+        id: &UserTrendInfoId,
+    ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError>;
+    // changes of the trends of the user of the query, oldest first
+    #[allow(async_fn_in_trait)]
+    async fn history(
+        &self,
+        query: &TrendHistoryQuery,
+    ) -> Result<Vec<TrendHistoryRecord>, UserTrendInfoRepositoryError>;
 }
 
 #[derive(Debug)]
@@ -30,6 +63,17 @@ pub enum UserTrendInfoRepositoryError {
     ConvertError(String),
     NotFoundError(String),
 }
+impl std::fmt::Display for UserTrendInfoRepositoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserTrendInfoRepositoryError::SaveError(s) => write!(f, "SaveError: {}", s),
+            UserTrendInfoRepositoryError::AlreadyExists(s) => write!(f, "AlreadyExists: {}", s),
+            UserTrendInfoRepositoryError::ConvertError(s) => write!(f, "ConvertError: {}", s),
+            UserTrendInfoRepositoryError::NotFoundError(s) => write!(f, "NotFoundError: {}", s),
+        }
+    }
+}
+impl std::error::Error for UserTrendInfoRepositoryError {}
 
 pub async fn save_new_trend(
     repository: &impl UserTrendInfoRepository,
@@ -38,11 +82,42 @@ pub async fn save_new_trend(
     repository.save(save_info).await
 }
 
+pub async fn search_trends(
+    repository: &impl UserTrendInfoRepository,
+    query: &TrendQuery,
+) -> Result<TrendPage, UserTrendInfoRepositoryError> {
+    repository.search(query).await
+}
+
+pub async fn get_trend(
+    repository: &impl UserTrendInfoRepository,
+    user_id: UserId,
+    id: &UserTrendInfoId,
+) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+    repository.get(user_id, id).await
+}
+
+pub async fn delete_trend(
+    repository: &impl UserTrendInfoRepository,
+    user_id: UserId,
+    id: &UserTrendInfoId,
+) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
+    repository.delete(user_id, id).await
+}
+
+pub async fn trend_history(
+    repository: &impl UserTrendInfoRepository,
+    query: &TrendHistoryQuery,
+) -> Result<Vec<TrendHistoryRecord>, UserTrendInfoRepositoryError> {
+    repository.history(query).await
+}
+
 pub async fn update_trend(
     repository: &impl UserTrendInfoRepository,
+    user_id: UserId,
     user_trend: UserTrendInfo,
 ) -> Result<UserTrendInfo, UserTrendInfoRepositoryError> {
-    repository.update(user_trend).await
+    repository.update(user_id, user_trend).await
 }
 
 #[derive(Debug, Clone)]
@@ -51,12 +126,14 @@ pub struct SaveNewTrendInfo {
     pub(super) raw_trend: RawTrendInfo,
     pub(super) memo: String,
     pub(super) status: Status,
+    pub(super) tags: Vec<Tag>,
 }
 pub struct SaveNewTrendInfoBuilder {
     user_id: UserId,
     raw_trend: RawTrendInfo,
     memo: String,
     status: Status,
+    tags: Vec<Tag>,
 }
 impl SaveNewTrendInfoBuilder {
     pub fn new(user_id: UserId, raw_trend: RawTrendInfo) -> Self {
@@ -64,7 +141,8 @@ impl SaveNewTrendInfoBuilder {
             user_id,
             raw_trend,
             memo: "".to_string(),
-            status: Status::New,
+            status: Status::NEW,
+            tags: vec![],
         }
     }
     pub fn memo(mut self, memo: impl Into<String>) -> Self {
@@ -75,12 +153,522 @@ impl SaveNewTrendInfoBuilder {
         self.status = status;
         self
     }
+    pub fn tags(mut self, tags: impl IntoIterator<Item = Tag>) -> Self {
+        self.tags = tags.into_iter().collect();
+        self
+    }
     pub fn build(self) -> SaveNewTrendInfo {
         SaveNewTrendInfo {
             user_id: self.user_id,
             raw_trend: self.raw_trend,
             memo: self.memo,
             status: self.status,
+            tags: self.tags,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+}
+
+// Where a page ended, the next page starts right after it.
+// Trends are ordered by created_at and then by id, so the position is stable
+// even when trends are saved between the requests of two pages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrendCursor {
+    pub(super) created_at: Date,
+    pub(super) id: UserTrendInfoId,
+}
+impl TrendCursor {
+    fn of(user_trend: &UserTrendInfo) -> Self {
+        Self {
+            created_at: *user_trend.created_at(),
+            id: user_trend.id().clone(),
+        }
+    }
+    // true when the trend comes after the cursor in the order
+    fn is_before(&self, user_trend: &UserTrendInfo, order: SortOrder) -> bool {
+        let key = (*user_trend.created_at(), user_trend.id().0.as_str());
+        let cursor = (self.created_at, self.id.0.as_str());
+        match order {
+            SortOrder::NewestFirst => key < cursor,
+            SortOrder::OldestFirst => key > cursor,
+        }
+    }
+}
+impl Display for TrendCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}|{}", self.created_at, self.id.0)
+    }
+}
+impl FromStr for TrendCursor {
+    type Err = TrendCursorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (created_at, id) = s
+            .split_once('|')
+            .ok_or_else(|| TrendCursorError::InvalidCursor(s.to_string()))?;
+        let created_at = Date::from_str(created_at)
+            .map_err(|_| TrendCursorError::InvalidCursor(s.to_string()))?;
+        if id.is_empty() {
+            return Err(TrendCursorError::InvalidCursor(s.to_string()));
+        }
+        Ok(Self {
+            created_at,
+            id: UserTrendInfoId(id.to_string()),
+        })
+    }
+}
+#[derive(Debug)]
+pub enum TrendCursorError {
+    InvalidCursor(String),
+}
+impl Display for TrendCursorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrendCursorError::InvalidCursor(s) => write!(f, "InvalidCursor: {}", s),
+        }
+    }
+}
+impl std::error::Error for TrendCursorError {}
+
+// Which trends of a user to list. Conditions left empty match everything.
+#[derive(Debug, Clone)]
+pub struct TrendQuery {
+    pub(super) user_id: UserId,
+    pub(super) statuses: Vec<Status>,
+    pub(super) service: Option<Service>,
+    // trends with every one of the tags
+    pub(super) tags: Vec<Tag>,
+    // both ends are included
+    pub(super) created_since: Option<Date>,
+    pub(super) created_until: Option<Date>,
+    // matched against title, desc and memo, ignoring case
+    pub(super) text: Option<String>,
+    pub(super) order: SortOrder,
+    pub(super) limit: usize,
+    pub(super) after: Option<TrendCursor>,
+}
+impl TrendQuery {
+    fn matches(&self, user_trend: &UserTrendInfo) -> bool {
+        let raw = user_trend.raw_info();
+        (self.statuses.is_empty() || self.statuses.contains(&user_trend.status()))
+            && self
+                .service
+                .as_ref()
+                .is_none_or(|service| service.to_str() == raw.from())
+            && self.tags.iter().all(|tag| user_trend.has_tag(tag))
+            && self
+                .created_since
+                .is_none_or(|since| *raw.created_at() >= since)
+            && self
+                .created_until
+                .is_none_or(|until| *raw.created_at() <= until)
+            && self.text.as_ref().is_none_or(|text| {
+                let text = text.to_lowercase();
+                [raw.title(), raw.desc(), user_trend.memo()]
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&text))
+            })
+            && self
+                .after
+                .as_ref()
+                .is_none_or(|after| after.is_before(user_trend, self.order))
+    }
+    // for repositories which hold every trend of the user in memory
+    pub(crate) fn page(&self, user_trends: Vec<UserTrendInfo>) -> TrendPage {
+        let mut user_trends: Vec<_> = user_trends
+            .into_iter()
+            .filter(|user_trend| self.matches(user_trend))
+            .collect();
+        user_trends.sort_by(|a, b| {
+            let a = (*a.created_at(), a.id().0.as_str());
+            let b = (*b.created_at(), b.id().0.as_str());
+            match self.order {
+                SortOrder::NewestFirst => b.cmp(&a),
+                SortOrder::OldestFirst => a.cmp(&b),
+            }
+        });
+        user_trends.truncate(self.limit + 1);
+        TrendPage::new(user_trends, self.limit)
+    }
+}
+pub struct TrendQueryBuilder {
+    query: TrendQuery,
+}
+impl TrendQueryBuilder {
+    const DEFAULT_LIMIT: usize = 50;
+    const MAX_LIMIT: usize = 200;
+    pub fn new(user_id: UserId) -> Self {
+        Self {
+            query: TrendQuery {
+                user_id,
+                statuses: vec![],
+                service: None,
+                tags: vec![],
+                created_since: None,
+                created_until: None,
+                text: None,
+                order: SortOrder::default(),
+                limit: Self::DEFAULT_LIMIT,
+                after: None,
+            },
+        }
+    }
+    pub fn statuses(mut self, statuses: impl IntoIterator<Item = Status>) -> Self {
+        self.query.statuses = statuses.into_iter().collect();
+        self
+    }
+    pub fn service(mut self, service: Service) -> Self {
+        self.query.service = Some(service);
+        self
+    }
+    pub fn tags(mut self, tags: impl IntoIterator<Item = Tag>) -> Self {
+        self.query.tags = tags.into_iter().collect();
+        self
+    }
+    pub fn created_since(mut self, since: Date) -> Self {
+        self.query.created_since = Some(since);
+        self
+    }
+    pub fn created_until(mut self, until: Date) -> Self {
+        self.query.created_until = Some(until);
+        self
+    }
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        self.query.text = (!text.is_empty()).then_some(text);
+        self
+    }
+    pub fn order(mut self, order: SortOrder) -> Self {
+        self.query.order = order;
+        self
+    }
+    // at most MAX_LIMIT trends are returned at once
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.query.limit = limit.clamp(1, Self::MAX_LIMIT);
+        self
+    }
+    pub fn after(mut self, cursor: TrendCursor) -> Self {
+        self.query.after = Some(cursor);
+        self
+    }
+    pub fn build(self) -> TrendQuery {
+        self.query
+    }
+}
+
+// Changes of status and memo, e.g. what was read in a week or how long trends stayed in ToDo.
+// Both ends of the period are included.
+#[derive(Debug, Clone)]
+pub struct TrendHistoryQuery {
+    pub(super) user_id: UserId,
+    pub(super) trend_id: Option<UserTrendInfoId>,
+    pub(super) since: Option<Date>,
+    pub(super) until: Option<Date>,
+}
+impl TrendHistoryQuery {
+    pub fn new(user_id: UserId) -> Self {
+        Self {
+            user_id,
+            trend_id: None,
+            since: None,
+            until: None,
+        }
+    }
+    pub fn with_trend(mut self, trend_id: UserTrendInfoId) -> Self {
+        self.trend_id = Some(trend_id);
+        self
+    }
+    pub fn with_since(mut self, since: Date) -> Self {
+        self.since = Some(since);
+        self
+    }
+    pub fn with_until(mut self, until: Date) -> Self {
+        self.until = Some(until);
+        self
+    }
+    // the owner of the trend is not checked here
+    pub(crate) fn matches(&self, record: &TrendHistoryRecord) -> bool {
+        let at = record.entry().at();
+        self.trend_id
+            .as_ref()
+            .is_none_or(|id| id == record.trend_id())
+            && self.since.is_none_or(|since| since.start_of_day() <= at)
+            && self.until.is_none_or(|until| at <= until.end_of_day())
+    }
+}
+
+#[derive(Debug)]
+pub struct TrendPage {
+    trends: Vec<UserTrendInfo>,
+    next: Option<TrendCursor>,
+}
+impl TrendPage {
+    // trends are fetched one more than the limit, to know whether a next page exists
+    pub(crate) fn new(mut trends: Vec<UserTrendInfo>, limit: usize) -> Self {
+        let next = if trends.len() > limit {
+            trends.truncate(limit);
+            trends.last().map(TrendCursor::of)
+        } else {
+            None
+        };
+        Self { trends, next }
+    }
+    pub fn trends(&self) -> &[UserTrendInfo] {
+        &self.trends
+    }
+    pub fn trends_mut(&mut self) -> &mut [UserTrendInfo] {
+        &mut self.trends
+    }
+    pub fn into_trends(self) -> Vec<UserTrendInfo> {
+        self.trends
+    }
+    // None on the last page
+    pub fn next(&self) -> Option<&TrendCursor> {
+        self.next.as_ref()
+    }
+}
+
+// query string of the listing api,
+// e.g. `?status=New,ToDo&service=aws_updates&tag=lambda,security&since=2024-06-17&limit=20`
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct TrendQueryParams {
+    // comma separated
+    status: Option<String>,
+    service: Option<String>,
+    // comma separated, trends with every one of them
+    tag: Option<String>,
+    // YYYY-MM-DD, both ends are included
+    since: Option<String>,
+    until: Option<String>,
+    q: Option<String>,
+    // `newest` (default) or `oldest`
+    order: Option<String>,
+    limit: Option<usize>,
+    // `next` of the previous page
+    after: Option<String>,
+}
+impl TrendQueryParams {
+    // statuses are the ones of the workflow
+    pub fn into_query(
+        self,
+        user_id: UserId,
+        workflow: &Workflow,
+    ) -> Result<TrendQuery, TrendRequestError> {
+        let mut builder = TrendQueryBuilder::new(user_id);
+        if let Some(status) = self.status {
+            let statuses = status
+                .split(',')
+                .map(|s| workflow.status(s.trim()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| TrendRequestError::InvalidStatus(status.clone()))?;
+            builder = builder.statuses(statuses);
+        }
+        if let Some(service) = self.service {
+            builder = builder.service(Service::from(service));
+        }
+        if let Some(tag) = self.tag {
+            let tags = tag
+                .split(',')
+                .map(Tag::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| TrendRequestError::InvalidTag(tag.clone()))?;
+            builder = builder.tags(tags);
+        }
+        if let Some(since) = self.since {
+            builder = builder.created_since(parse_day(&since)?);
+        }
+        if let Some(until) = self.until {
+            builder = builder.created_until(parse_day(&until)?);
+        }
+        if let Some(q) = self.q {
+            builder = builder.text(q);
+        }
+        match self.order.as_deref() {
+            None | Some("newest") => {}
+            Some("oldest") => builder = builder.order(SortOrder::OldestFirst),
+            Some(order) => return Err(TrendRequestError::InvalidOrder(order.to_string())),
+        }
+        if let Some(limit) = self.limit {
+            builder = builder.limit(limit);
+        }
+        if let Some(after) = self.after {
+            let cursor = TrendCursor::from_str(&after)
+                .map_err(|_| TrendRequestError::InvalidCursor(after.clone()))?;
+            builder = builder.after(cursor);
+        }
+        Ok(builder.build())
+    }
+}
+fn parse_day(value: &str) -> Result<Date, TrendRequestError> {
+    Date::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| TrendRequestError::InvalidDate(value.to_string()))
+}
+
+// query string of the history api, e.g. `?since=2024-06-17&until=2024-06-23`
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct TrendHistoryParams {
+    // id of a trend, every trend of the user when omitted
+    trend: Option<String>,
+    // YYYY-MM-DD, both ends are included
+    since: Option<String>,
+    until: Option<String>,
+}
+impl TrendHistoryParams {
+    pub fn into_query(self, user_id: UserId) -> Result<TrendHistoryQuery, TrendRequestError> {
+        let mut query = TrendHistoryQuery::new(user_id);
+        if let Some(trend) = self.trend {
+            query = query.with_trend(UserTrendInfoId(trend));
+        }
+        if let Some(since) = self.since {
+            query = query.with_since(parse_day(&since)?);
+        }
+        if let Some(until) = self.until {
+            query = query.with_until(parse_day(&until)?);
+        }
+        Ok(query)
+    }
+}
+
+// body of the api to save a trend by hand
+#[derive(serde::Deserialize)]
+pub struct NewTrend {
+    title: String,
+    link: String,
+    #[serde(default)]
+    desc: String,
+    service: String,
+    // any date a feed may have, today when omitted
+    created_at: Option<String>,
+    #[serde(default)]
+    memo: String,
+    status: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+impl NewTrend {
+    // the status must be one New may change to in the workflow
+    pub fn into_save_info(
+        self,
+        user_id: UserId,
+        workflow: &Workflow,
+    ) -> Result<SaveNewTrendInfo, TrendRequestError> {
+        let created_at = match &self.created_at {
+            Some(date) => Date::parse_feed_date(date)
+                .map_err(|_| TrendRequestError::InvalidDate(date.clone()))?,
+            None => Date::now(),
+        };
+        let raw_trend = RawTrendInfo::new(
+            self.title,
+            self.link,
+            self.desc,
+            Service::from(self.service),
+            created_at,
+        );
+        // memo and tags are checked by the rules of the domain before saving
+        let mut user_trend = UserTrendInfo::new(UserTrendInfoId::new(""), raw_trend.clone());
+        user_trend
+            .change_memo(self.memo.clone())
+            .map_err(TrendRequestError::InvalidChange)?;
+        for tag in &self.tags {
+            user_trend
+                .add_tag(tag)
+                .map_err(TrendRequestError::InvalidChange)?;
+        }
+        let mut builder = SaveNewTrendInfoBuilder::new(user_id, raw_trend)
+            .memo(self.memo)
+            .tags(user_trend.tags().cloned());
+        if let Some(status) = self.status {
+            let status = workflow
+                .status(&status)
+                .map_err(|_| TrendRequestError::InvalidStatus(status))?;
+            workflow.change(&Status::NEW, &status).map_err(|e| {
+                TrendRequestError::InvalidChange(UserTrendInfoError::InvalidStatus(e))
+            })?;
+            builder = builder.status(status);
+        }
+        Ok(builder.build())
+    }
+}
+
+// body of the api to edit a trend, fields left out are not changed
+#[derive(serde::Deserialize)]
+pub struct TrendChange {
+    memo: Option<String>,
+    status: Option<String>,
+    #[serde(default)]
+    add_tags: Vec<String>,
+    #[serde(default)]
+    remove_tags: Vec<String>,
+}
+impl TrendChange {
+    pub fn apply(
+        self,
+        user_trend: &mut UserTrendInfo,
+        workflow: &Workflow,
+    ) -> Result<(), TrendRequestError> {
+        if let Some(memo) = self.memo {
+            user_trend
+                .change_memo(memo)
+                .map_err(TrendRequestError::InvalidChange)?;
+        }
+        if let Some(status) = self.status {
+            let status = workflow
+                .status(&status)
+                .map_err(|_| TrendRequestError::InvalidStatus(status))?;
+            user_trend
+                .change_status(status, workflow)
+                .map_err(TrendRequestError::InvalidChange)?;
+        }
+        // removed first, so a full trend can swap a tag in one request
+        user_trend
+            .change_tags(
+                self.remove_tags.iter().map(String::as_str),
+                self.add_tags.iter().map(String::as_str),
+            )
+            .map_err(TrendRequestError::InvalidChange)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum TrendRequestError {
+    InvalidStatus(String),
+    InvalidTag(String),
+    InvalidDate(String),
+    InvalidOrder(String),
+    InvalidCursor(String),
+    // the request is well formed, but the domain does not allow it
+    InvalidChange(UserTrendInfoError),
+}
+impl Display for TrendRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrendRequestError::InvalidStatus(s) => write!(f, "InvalidStatus: {}", s),
+            TrendRequestError::InvalidTag(s) => write!(f, "InvalidTag: {}", s),
+            TrendRequestError::InvalidDate(s) => write!(f, "InvalidDate: {}", s),
+            TrendRequestError::InvalidOrder(s) => write!(f, "InvalidOrder: {}", s),
+            TrendRequestError::InvalidCursor(s) => write!(f, "InvalidCursor: {}", s),
+            TrendRequestError::InvalidChange(e) => write!(f, "InvalidChange: {}", e),
+        }
+    }
+}
+impl std::error::Error for TrendRequestError {}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TrendList {
+    trends: Vec<UserTrend>,
+    next: Option<String>,
+}
+impl From<TrendPage> for TrendList {
+    fn from(page: TrendPage) -> Self {
+        Self {
+            next: page.next.as_ref().map(|next| next.to_string()),
+            trends: page.trends.into_iter().map(UserTrend::from).collect(),
         }
     }
 }
@@ -89,7 +677,7 @@ impl SaveNewTrendInfoBuilder {
 mod tests {
     use super::*;
     use crate::{
-        domain::Status,
+        domain::{Status, TrendEvent},
         raw::{RawTrendInfo, Service},
         repository::fake::FakeUserTrendInfoRepository,
     };
@@ -108,8 +696,8 @@ mod tests {
             Date::parse_from_str("2021-01-01", "%Y-%m-%d").unwrap(),
         );
         let memo = "so, interesting!";
-        let status = Status::ToDo;
-        let save_info = SaveNewTrendInfoBuilder::new(user_id, raw_trend)
+        let status = Status::TO_DO;
+        let save_info = SaveNewTrendInfoBuilder::new(user_id.clone(), raw_trend)
             .memo(memo)
             .status(status)
             .build();
@@ -117,11 +705,35 @@ mod tests {
         let repository = FakeUserTrendInfoRepository::new();
 
         let mut user_trend = save_new_trend(&repository, save_info).await.unwrap();
-        user_trend.change_status(Status::Done).unwrap();
+        user_trend
+            .change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+
+        let user_trend = update_trend(&repository, user_id, user_trend)
+            .await
+            .unwrap();
+
+        assert_eq!(user_trend.status(), Status::DONE);
+    }
+    #[tokio::test]
+    async fn user_cannot_update_trend_of_other_user() {
+        let user_id = UserId::new("user_id");
+        let repository = FakeUserTrendInfoRepository::new();
+        let mut user_trend = save_new_trend(&repository, save_info(&user_id, "link"))
+            .await
+            .unwrap();
+        user_trend.change_memo("not mine".to_string()).unwrap();
 
-        let user_trend = update_trend(&repository, user_trend).await.unwrap();
+        let result = update_trend(&repository, UserId::new("other"), user_trend.clone()).await;
 
-        assert_eq!(user_trend.status(), Status::Done);
+        assert!(matches!(
+            result,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+        let stored = get_trend(&repository, user_id, user_trend.id())
+            .await
+            .unwrap();
+        assert_eq!(stored.memo(), "");
     }
     #[tokio::test]
     async fn user_can_save_new_trend_with_other_info() {
@@ -136,10 +748,10 @@ mod tests {
             Date::parse_from_str("2021-01-01", "%Y-%m-%d").unwrap(),
         );
         let memo = "so, interesting!";
-        let status = Status::ToDo;
+        let status = Status::TO_DO;
         let save_info = SaveNewTrendInfoBuilder::new(user_id, raw_trend)
             .memo(memo)
-            .status(status)
+            .status(status.clone())
             .build();
 
         let repository = FakeUserTrendInfoRepository::new();
@@ -151,4 +763,360 @@ mod tests {
         assert_eq!(user_trend.memo(), memo);
         assert_eq!(user_trend.status(), status);
     }
+    #[tokio::test]
+    async fn user_can_see_history_of_own_trends() {
+        let user_id = UserId::new("user_id");
+        let repository = FakeUserTrendInfoRepository::new();
+        let mut user_trend = save_new_trend(&repository, save_info(&user_id, "link"))
+            .await
+            .unwrap();
+        user_trend
+            .change_status(Status::TO_DO, &Workflow::default())
+            .unwrap();
+        let mut user_trend = update_trend(&repository, user_id.clone(), user_trend)
+            .await
+            .unwrap();
+        user_trend
+            .change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+        update_trend(&repository, user_id.clone(), user_trend)
+            .await
+            .unwrap();
+
+        let history = trend_history(&repository, &TrendHistoryQuery::new(user_id.clone()))
+            .await
+            .unwrap();
+        let others = trend_history(&repository, &TrendHistoryQuery::new(UserId::new("other")))
+            .await
+            .unwrap();
+
+        // saved as New, which is not a change
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history[1].entry().event(),
+            &TrendEvent::StatusChanged {
+                from: Status::TO_DO,
+                to: Status::DONE
+            }
+        );
+        assert!(others.is_empty());
+    }
+    #[test]
+    fn history_params_into_query() {
+        let params = TrendHistoryParams {
+            trend: Some("id-0".to_string()),
+            since: Some("2024-06-17".to_string()),
+            until: Some("2024-06-23".to_string()),
+        };
+
+        let query = params.into_query(UserId::new("user_id")).unwrap();
+
+        assert_eq!(query.trend_id, Some(UserTrendInfoId::new("id-0")));
+        assert_eq!(
+            query.since,
+            Some(Date::parse_from_str("2024-06-17", "%Y-%m-%d").unwrap())
+        );
+        let params = TrendHistoryParams {
+            since: Some("last week".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            params.into_query(UserId::new("user_id")),
+            Err(TrendRequestError::InvalidDate(_))
+        ));
+    }
+    fn save_info(user_id: &UserId, link: &str) -> SaveNewTrendInfo {
+        let raw_trend = RawTrendInfo::new(
+            "title",
+            link,
+            "desc",
+            Service::aws_updates(),
+            Date::parse_from_str("2021-01-01", "%Y-%m-%d").unwrap(),
+        );
+        SaveNewTrendInfoBuilder::new(user_id.clone(), raw_trend).build()
+    }
+    fn dated_save_info(user_id: &UserId, link: &str, title: &str, date: &str) -> SaveNewTrendInfo {
+        let raw_trend = RawTrendInfo::new(
+            title,
+            link,
+            "desc",
+            Service::aws_updates(),
+            Date::parse_from_str(date, "%Y-%m-%d").unwrap(),
+        );
+        SaveNewTrendInfoBuilder::new(user_id.clone(), raw_trend).build()
+    }
+    #[tokio::test]
+    async fn user_can_search_trends_by_status_and_text() {
+        let user_id = UserId::new("user_id");
+        let repository = FakeUserTrendInfoRepository::new();
+        for (link, title) in [
+            ("1", "Lambda update"),
+            ("2", "EC2 update"),
+            ("3", "lambda pricing"),
+        ] {
+            save_new_trend(
+                &repository,
+                dated_save_info(&user_id, link, title, "2024-06-21"),
+            )
+            .await
+            .unwrap();
+        }
+        let mut read = repository.list(user_id.clone()).await.unwrap().remove(0);
+        read.change_status(Status::DONE, &Workflow::default())
+            .unwrap();
+        update_trend(&repository, user_id.clone(), read.clone())
+            .await
+            .unwrap();
+
+        let query = TrendQueryBuilder::new(user_id.clone())
+            .statuses([Status::NEW])
+            .text("LAMBDA")
+            .build();
+        let page = search_trends(&repository, &query).await.unwrap();
+
+        let links: Vec<_> = page.trends().iter().map(|t| t.link()).collect();
+        assert!(!links.contains(&read.link()));
+        assert!(links.iter().all(|link| *link == "1" || *link == "3"));
+        assert!(page.next().is_none());
+    }
+    #[tokio::test]
+    async fn user_can_search_trends_by_service_and_date() {
+        let user_id = UserId::new("user_id");
+        let repository = FakeUserTrendInfoRepository::new();
+        for (link, date) in [
+            ("old", "2024-06-01"),
+            ("week", "2024-06-20"),
+            ("new", "2024-06-27"),
+        ] {
+            save_new_trend(&repository, dated_save_info(&user_id, link, "title", date))
+                .await
+                .unwrap();
+        }
+
+        let query = TrendQueryBuilder::new(user_id.clone())
+            .service(Service::aws_updates())
+            .created_since(Date::parse_from_str("2024-06-17", "%Y-%m-%d").unwrap())
+            .created_until(Date::parse_from_str("2024-06-23", "%Y-%m-%d").unwrap())
+            .build();
+        let page = search_trends(&repository, &query).await.unwrap();
+        assert_eq!(page.trends().len(), 1);
+        assert_eq!(page.trends()[0].link(), "week");
+
+        let query = TrendQueryBuilder::new(user_id)
+            .service(Service::x())
+            .build();
+        assert!(search_trends(&repository, &query)
+            .await
+            .unwrap()
+            .trends()
+            .is_empty());
+    }
+    #[tokio::test]
+    async fn user_can_page_through_trends() {
+        let user_id = UserId::new("user_id");
+        let repository = FakeUserTrendInfoRepository::new();
+        for (link, date) in [
+            ("a", "2024-06-21"),
+            ("b", "2024-06-22"),
+            ("c", "2024-06-22"),
+            ("d", "2024-06-23"),
+            ("e", "2024-06-24"),
+        ] {
+            save_new_trend(&repository, dated_save_info(&user_id, link, "title", date))
+                .await
+                .unwrap();
+        }
+
+        let mut links = vec![];
+        let mut after = None;
+        loop {
+            let mut builder = TrendQueryBuilder::new(user_id.clone())
+                .order(SortOrder::OldestFirst)
+                .limit(2);
+            if let Some(cursor) = after {
+                // cursors are given back by clients as text
+                let cursor: String = format!("{}", cursor);
+                builder = builder.after(cursor.parse().unwrap());
+            }
+            let page = search_trends(&repository, &builder.build()).await.unwrap();
+            links.extend(page.trends().iter().map(|t| t.link().to_string()));
+            match page.next() {
+                Some(next) => after = Some(next.clone()),
+                None => break,
+            }
+        }
+
+        assert_eq!(links, vec!["a", "b", "c", "d", "e"]);
+    }
+    #[tokio::test]
+    async fn user_can_tag_trends_and_search_by_tags() {
+        let user_id = UserId::new("user_id");
+        let repository = FakeUserTrendInfoRepository::new();
+        let new_trend: NewTrend = serde_json::from_value(serde_json::json!({
+            "title": "Lambda pricing",
+            "link": "1",
+            "service": "aws_updates",
+            "tags": ["Lambda", "cost"],
+        }))
+        .unwrap();
+        let new_info = new_trend
+            .into_save_info(user_id.clone(), &Workflow::default())
+            .unwrap();
+        let mut tagged = save_new_trend(&repository, new_info).await.unwrap();
+        save_new_trend(&repository, save_info(&user_id, "2"))
+            .await
+            .unwrap();
+        let change: TrendChange =
+            serde_json::from_str(r#"{"add_tags": ["security"], "remove_tags": ["cost"]}"#).unwrap();
+        change.apply(&mut tagged, &Workflow::default()).unwrap();
+        update_trend(&repository, user_id.clone(), tagged)
+            .await
+            .unwrap();
+
+        let params: TrendQueryParams =
+            serde_json::from_str(r#"{"tag": "security,LAMBDA"}"#).unwrap();
+        let query = params
+            .into_query(user_id.clone(), &Workflow::default())
+            .unwrap();
+        let page = search_trends(&repository, &query).await.unwrap();
+
+        assert_eq!(page.trends().len(), 1);
+        let tags: Vec<_> = page.trends()[0].tags().map(|tag| tag.as_str()).collect();
+        assert_eq!(tags, vec!["lambda", "security"]);
+
+        let query = TrendQueryBuilder::new(user_id)
+            .tags(["cost".parse().unwrap()])
+            .build();
+        assert!(search_trends(&repository, &query)
+            .await
+            .unwrap()
+            .trends()
+            .is_empty());
+    }
+    #[test]
+    fn query_params_with_invalid_tag_should_fail() {
+        let params: TrendQueryParams = serde_json::from_str(r#"{"tag": "lambda,"}"#).unwrap();
+
+        assert!(matches!(
+            params.into_query(UserId::new("user_id"), &Workflow::default()),
+            Err(TrendRequestError::InvalidTag(_))
+        ));
+    }
+    #[test]
+    fn query_params_into_query() {
+        let params: TrendQueryParams = serde_json::from_str(
+            r#"{"status": "New, ToDo", "service": "aws_updates", "since": "2024-06-17", "order": "oldest", "limit": 500}"#,
+        )
+        .unwrap();
+
+        let query = params
+            .into_query(UserId::new("user_id"), &Workflow::default())
+            .unwrap();
+
+        assert_eq!(query.statuses, vec![Status::NEW, Status::TO_DO]);
+        assert_eq!(query.service.unwrap().to_str(), "aws_updates");
+        assert_eq!(
+            query.created_since.unwrap().to_string(),
+            "2024-06-17:00:00:00"
+        );
+        assert_eq!(query.order, SortOrder::OldestFirst);
+        assert_eq!(query.limit, 200);
+    }
+    #[test]
+    fn query_params_with_invalid_status_should_fail() {
+        let params: TrendQueryParams = serde_json::from_str(r#"{"status": "New,Unread"}"#).unwrap();
+
+        assert!(matches!(
+            params.into_query(UserId::new("user_id"), &Workflow::default()),
+            Err(TrendRequestError::InvalidStatus(_))
+        ));
+    }
+    #[test]
+    fn change_back_to_new_should_fail() {
+        let raw_trend = RawTrendInfo::new(
+            "title",
+            "link",
+            "desc",
+            Service::aws_updates(),
+            Date::parse_from_str("2021-01-01", "%Y-%m-%d").unwrap(),
+        );
+        let mut user_trend = UserTrendInfo::new(UserTrendInfoId::new("id"), raw_trend);
+        let change: TrendChange =
+            serde_json::from_str(r#"{"memo": "later", "status": "Done"}"#).unwrap();
+        change.apply(&mut user_trend, &Workflow::default()).unwrap();
+        assert_eq!(user_trend.memo(), "later");
+
+        let change: TrendChange = serde_json::from_str(r#"{"status": "New"}"#).unwrap();
+
+        assert!(matches!(
+            change.apply(&mut user_trend, &Workflow::default()),
+            Err(TrendRequestError::InvalidChange(_))
+        ));
+    }
+    #[test]
+    fn new_trend_with_too_long_memo_should_fail() {
+        let new_trend: NewTrend = serde_json::from_value(serde_json::json!({
+            "title": "title",
+            "link": "link",
+            "service": "aws_updates",
+            "created_at": "2024-06-21",
+            "memo": "a".repeat(501),
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            new_trend.into_save_info(UserId::new("user_id"), &Workflow::default()),
+            Err(TrendRequestError::InvalidChange(_))
+        ));
+    }
+    #[test]
+    fn invalid_cursor_should_fail() {
+        assert!("2024-06-21".parse::<TrendCursor>().is_err());
+        assert!("yesterday|id-1".parse::<TrendCursor>().is_err());
+    }
+    #[tokio::test]
+    async fn user_can_get_and_delete_own_trend() {
+        let user_id = UserId::new("user_id");
+        let repository = FakeUserTrendInfoRepository::new();
+        let saved = save_new_trend(&repository, save_info(&user_id, "link"))
+            .await
+            .unwrap();
+
+        let got = get_trend(&repository, user_id.clone(), saved.id())
+            .await
+            .unwrap();
+        assert_eq!(got.link(), "link");
+
+        delete_trend(&repository, user_id.clone(), saved.id())
+            .await
+            .unwrap();
+        assert!(matches!(
+            get_trend(&repository, user_id.clone(), saved.id()).await,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+        // ids are not reused after a delete
+        let other = save_new_trend(&repository, save_info(&user_id, "other"))
+            .await
+            .unwrap();
+        assert_ne!(other.id(), saved.id());
+    }
+    #[tokio::test]
+    async fn user_can_not_get_or_delete_other_users_trend() {
+        let owner = UserId::new("owner");
+        let other = UserId::new("other");
+        let repository = FakeUserTrendInfoRepository::new();
+        let saved = save_new_trend(&repository, save_info(&owner, "link"))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            get_trend(&repository, other.clone(), saved.id()).await,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+        assert!(matches!(
+            delete_trend(&repository, other, saved.id()).await,
+            Err(UserTrendInfoRepositoryError::NotFoundError(_))
+        ));
+        assert!(get_trend(&repository, owner, saved.id()).await.is_ok());
+    }
 }
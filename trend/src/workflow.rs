@@ -0,0 +1,446 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+    str::FromStr,
+};
+
+use user::UserId;
+
+use crate::domain::{Status, StatusError};
+
+// Which statuses a trend can have and to which each of them may change.
+// Every trend starts as New, so New is in every workflow.
+// The default is New, Reading, ToDo and Done, where anything but going back to New is allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workflow {
+    // statuses in the order of the definition, with the statuses they may change to
+    statuses: Vec<(Status, Vec<Status>)>,
+}
+impl Default for Workflow {
+    fn default() -> Self {
+        let all = [Status::READING, Status::TO_DO, Status::DONE];
+        let others = |status: &Status| {
+            all.iter()
+                .filter(|other| *other != status)
+                .cloned()
+                .collect()
+        };
+        Self {
+            statuses: vec![
+                (Status::NEW, all.to_vec()),
+                (Status::READING, others(&Status::READING)),
+                (Status::TO_DO, others(&Status::TO_DO)),
+                (Status::DONE, others(&Status::DONE)),
+            ],
+        }
+    }
+}
+impl Workflow {
+    pub fn new(statuses: Vec<(Status, Vec<Status>)>) -> Result<Self, WorkflowError> {
+        let mut names = HashSet::new();
+        for (status, _) in &statuses {
+            if !names.insert(status) {
+                return Err(WorkflowError::DuplicateStatus(status.to_str().to_string()));
+            }
+        }
+        if !names.contains(&Status::NEW) {
+            return Err(WorkflowError::MissingNew);
+        }
+        for (status, next) in &statuses {
+            if let Some(unknown) = next.iter().find(|next| !names.contains(next)) {
+                return Err(WorkflowError::UnknownNext(
+                    status.to_str().to_string(),
+                    unknown.to_str().to_string(),
+                ));
+            }
+        }
+        Ok(Self { statuses })
+    }
+    // config file is chosen by its extension, `.toml` or `.json`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WorkflowError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(WorkflowError::IoError)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(WorkflowError::UnknownExtension(path.display().to_string())),
+        }
+    }
+    pub fn from_toml(content: &str) -> Result<Self, WorkflowError> {
+        let config: WorkflowConfig = toml::from_str(content).map_err(WorkflowError::TomlError)?;
+        config.try_into()
+    }
+    pub fn from_json(content: &str) -> Result<Self, WorkflowError> {
+        let config: WorkflowConfig =
+            serde_json::from_str(content).map_err(WorkflowError::JsonError)?;
+        config.try_into()
+    }
+    pub fn statuses(&self) -> impl Iterator<Item = &Status> {
+        self.statuses.iter().map(|(status, _)| status)
+    }
+    pub fn contains(&self, status: &Status) -> bool {
+        self.next(status).is_some()
+    }
+    // the status of the name, when the workflow has it
+    pub fn status(&self, name: &str) -> Result<Status, StatusError> {
+        let status = Status::from_str(name)?;
+        if !self.contains(&status) {
+            return Err(StatusError::InvalidStatus(name.to_string()));
+        }
+        Ok(status)
+    }
+    fn next(&self, status: &Status) -> Option<&[Status]> {
+        self.statuses
+            .iter()
+            .find(|(s, _)| s == status)
+            .map(|(_, next)| next.as_slice())
+    }
+    // Staying in the same status is always allowed.
+    // A trend left in a status the workflow no longer has may move to any status but New.
+    pub fn change(&self, from: &Status, to: &Status) -> Result<(), StatusError> {
+        if !self.contains(to) {
+            return Err(StatusError::InvalidStatus(to.to_str().to_string()));
+        }
+        if from == to {
+            return Ok(());
+        }
+        let allowed = match self.next(from) {
+            Some(next) => next.contains(to),
+            None => *to != Status::NEW,
+        };
+        if !allowed {
+            return Err(StatusError::InvalidStatusChange(from.clone(), to.clone()));
+        }
+        Ok(())
+    }
+}
+
+// The workflow of the deployment, and the workflows of the users who have their own.
+#[derive(Debug, Clone, Default)]
+pub struct Workflows {
+    default: Workflow,
+    // by the id of the user
+    users: HashMap<String, Workflow>,
+}
+impl Workflows {
+    pub fn new(default: Workflow) -> Self {
+        Self {
+            default,
+            users: HashMap::new(),
+        }
+    }
+    pub fn with_user(mut self, user_id: &UserId, workflow: Workflow) -> Self {
+        self.users.insert(user_id.to_string(), workflow);
+        self
+    }
+    // config file is chosen by its extension, `.toml` or `.json`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WorkflowError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(WorkflowError::IoError)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(WorkflowError::UnknownExtension(path.display().to_string())),
+        }
+    }
+    pub fn from_toml(content: &str) -> Result<Self, WorkflowError> {
+        let config: WorkflowsConfig = toml::from_str(content).map_err(WorkflowError::TomlError)?;
+        config.try_into()
+    }
+    pub fn from_json(content: &str) -> Result<Self, WorkflowError> {
+        let config: WorkflowsConfig =
+            serde_json::from_str(content).map_err(WorkflowError::JsonError)?;
+        config.try_into()
+    }
+    pub fn default_workflow(&self) -> &Workflow {
+        &self.default
+    }
+    // the workflow of the user, the default one unless the user has their own
+    pub fn of(&self, user_id: &UserId) -> &Workflow {
+        self.users
+            .get(&user_id.to_string())
+            .unwrap_or(&self.default)
+    }
+}
+
+#[derive(Debug)]
+pub enum WorkflowError {
+    IoError(std::io::Error),
+    TomlError(toml::de::Error),
+    JsonError(serde_json::Error),
+    UnknownExtension(String),
+    InvalidStatus(StatusError),
+    DuplicateStatus(String),
+    MissingNew,
+    // a status and the status it changes to which is not defined
+    UnknownNext(String, String),
+    DuplicateUser(String),
+    // the workflow of the user is invalid
+    InvalidUserWorkflow(String, Box<WorkflowError>),
+}
+impl Display for WorkflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkflowError::IoError(e) => write!(f, "IoError: {}", e),
+            WorkflowError::TomlError(e) => write!(f, "TomlError: {}", e),
+            WorkflowError::JsonError(e) => write!(f, "JsonError: {}", e),
+            WorkflowError::UnknownExtension(s) => write!(f, "UnknownExtension: {}", s),
+            WorkflowError::InvalidStatus(e) => write!(f, "InvalidStatus: {}", e),
+            WorkflowError::DuplicateStatus(s) => write!(f, "DuplicateStatus: {}", s),
+            WorkflowError::MissingNew => write!(f, "MissingNew"),
+            WorkflowError::UnknownNext(from, to) => {
+                write!(f, "UnknownNext: {} -> {}", from, to)
+            }
+            WorkflowError::DuplicateUser(s) => write!(f, "DuplicateUser: {}", s),
+            WorkflowError::InvalidUserWorkflow(user, e) => {
+                write!(f, "InvalidUserWorkflow: {}: {}", user, e)
+            }
+        }
+    }
+}
+impl std::error::Error for WorkflowError {}
+
+// the workflow as written in the config file or shown by the api
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct WorkflowConfig {
+    statuses: Vec<StatusConfig>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StatusConfig {
+    name: String,
+    // statuses this one may change to
+    #[serde(default)]
+    next: Vec<String>,
+}
+impl TryFrom<WorkflowConfig> for Workflow {
+    type Error = WorkflowError;
+    fn try_from(config: WorkflowConfig) -> Result<Self, Self::Error> {
+        let parse = |name: &str| Status::from_str(name).map_err(WorkflowError::InvalidStatus);
+        let statuses = config
+            .statuses
+            .iter()
+            .map(|status| {
+                let next = status
+                    .next
+                    .iter()
+                    .map(|name| parse(name))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((parse(&status.name)?, next))
+            })
+            .collect::<Result<Vec<_>, WorkflowError>>()?;
+        Workflow::new(statuses)
+    }
+}
+impl From<&Workflow> for WorkflowConfig {
+    fn from(workflow: &Workflow) -> Self {
+        Self {
+            statuses: workflow
+                .statuses
+                .iter()
+                .map(|(status, next)| StatusConfig {
+                    name: status.to_str().to_string(),
+                    next: next.iter().map(|s| s.to_str().to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// the workflows as written in the config file,
+// the statuses at the top are the default and a user may have their own
+#[derive(serde::Deserialize)]
+struct WorkflowsConfig {
+    // New, Reading, ToDo and Done when left out
+    statuses: Option<Vec<StatusConfig>>,
+    #[serde(default)]
+    users: Vec<UserWorkflowConfig>,
+}
+#[derive(serde::Deserialize)]
+struct UserWorkflowConfig {
+    id: String,
+    statuses: Vec<StatusConfig>,
+}
+impl TryFrom<WorkflowsConfig> for Workflows {
+    type Error = WorkflowError;
+    fn try_from(config: WorkflowsConfig) -> Result<Self, Self::Error> {
+        let default = match config.statuses {
+            Some(statuses) => WorkflowConfig { statuses }.try_into()?,
+            None => Workflow::default(),
+        };
+        let mut workflows = Workflows::new(default);
+        for user in config.users {
+            if workflows.users.contains_key(&user.id) {
+                return Err(WorkflowError::DuplicateUser(user.id));
+            }
+            let workflow = WorkflowConfig {
+                statuses: user.statuses,
+            }
+            .try_into()
+            .map_err(|e| WorkflowError::InvalidUserWorkflow(user.id.clone(), Box::new(e)))?;
+            workflows = workflows.with_user(&UserId::new(user.id), workflow);
+        }
+        Ok(workflows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIAGE: &str = r#"
+[[statuses]]
+name = "New"
+next = ["Reading", "Skipped"]
+
+[[statuses]]
+name = "Reading"
+next = ["Shared", "Archived"]
+
+[[statuses]]
+name = "Shared"
+next = ["Archived"]
+
+[[statuses]]
+name = "Skipped"
+
+[[statuses]]
+name = "Archived"
+"#;
+
+    fn status(name: &str) -> Status {
+        Status::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn default_workflow_denies_only_going_back_to_new() {
+        let workflow = Workflow::default();
+
+        assert!(workflow.change(&Status::NEW, &Status::DONE).is_ok());
+        assert!(workflow.change(&Status::DONE, &Status::READING).is_ok());
+        assert!(workflow.change(&Status::TO_DO, &Status::TO_DO).is_ok());
+        assert!(matches!(
+            workflow.change(&Status::DONE, &Status::NEW),
+            Err(StatusError::InvalidStatusChange(_, _))
+        ));
+        assert!(matches!(
+            workflow.change(&Status::NEW, &status("Archived")),
+            Err(StatusError::InvalidStatus(_))
+        ));
+    }
+    #[test]
+    fn load_workflow_from_toml() {
+        let workflow = Workflow::from_toml(TRIAGE).unwrap();
+
+        assert_eq!(workflow.statuses().count(), 5);
+        assert!(workflow.change(&Status::NEW, &status("Skipped")).is_ok());
+        assert!(workflow
+            .change(&status("Reading"), &status("Archived"))
+            .is_ok());
+        assert!(workflow.change(&Status::NEW, &status("Archived")).is_err());
+        // terminal
+        assert!(workflow
+            .change(&status("Archived"), &status("Reading"))
+            .is_err());
+        assert!(workflow.status("Done").is_err());
+    }
+    #[test]
+    fn status_left_out_of_workflow_can_move_on() {
+        let workflow = Workflow::from_toml(TRIAGE).unwrap();
+
+        assert!(workflow.change(&Status::DONE, &status("Archived")).is_ok());
+        assert!(workflow.change(&Status::DONE, &Status::NEW).is_err());
+    }
+    #[test]
+    fn invalid_workflows_are_refused_on_load() {
+        let without_new = r#"
+[[statuses]]
+name = "Reading"
+"#;
+        let unknown_next = r#"
+[[statuses]]
+name = "New"
+next = ["Reading"]
+"#;
+        let duplicate = r#"
+[[statuses]]
+name = "New"
+
+[[statuses]]
+name = "New"
+"#;
+
+        assert!(matches!(
+            Workflow::from_toml(without_new),
+            Err(WorkflowError::MissingNew)
+        ));
+        assert!(matches!(
+            Workflow::from_toml(unknown_next),
+            Err(WorkflowError::UnknownNext(_, _))
+        ));
+        assert!(matches!(
+            Workflow::from_toml(duplicate),
+            Err(WorkflowError::DuplicateStatus(_))
+        ));
+    }
+    #[test]
+    fn user_may_have_own_workflow() {
+        let toml = format!(
+            r#"{}
+[[users]]
+id = "bob"
+
+[[users.statuses]]
+name = "New"
+next = ["Done"]
+
+[[users.statuses]]
+name = "Done"
+"#,
+            TRIAGE
+        );
+        let workflows = Workflows::from_toml(&toml).unwrap();
+
+        let bob = workflows.of(&UserId::new("bob"));
+        assert_eq!(bob.statuses().count(), 2);
+        assert!(bob.change(&Status::NEW, &Status::DONE).is_ok());
+        assert!(bob.change(&Status::NEW, &status("Skipped")).is_err());
+        let alice = workflows.of(&UserId::new("alice"));
+        assert_eq!(alice, &Workflow::from_toml(TRIAGE).unwrap());
+    }
+    #[test]
+    fn workflows_without_statuses_have_the_default() {
+        let json = r#"{ "users": [{ "id": "bob", "statuses": [{ "name": "New" }] }] }"#;
+
+        let workflows = Workflows::from_json(json).unwrap();
+
+        assert_eq!(workflows.default_workflow(), &Workflow::default());
+        assert_eq!(workflows.of(&UserId::new("bob")).statuses().count(), 1);
+    }
+    #[test]
+    fn invalid_user_workflows_are_refused_on_load() {
+        let duplicate = r#"{ "users": [
+            { "id": "bob", "statuses": [{ "name": "New" }] },
+            { "id": "bob", "statuses": [{ "name": "New" }] }
+        ] }"#;
+        let without_new = r#"{ "users": [{ "id": "bob", "statuses": [{ "name": "Done" }] }] }"#;
+
+        assert!(matches!(
+            Workflows::from_json(duplicate),
+            Err(WorkflowError::DuplicateUser(_))
+        ));
+        assert!(matches!(
+            Workflows::from_json(without_new),
+            Err(WorkflowError::InvalidUserWorkflow(user, e))
+                if user == "bob" && matches!(*e, WorkflowError::MissingNew)
+        ));
+    }
+    #[test]
+    fn workflow_round_trips_through_config() {
+        let workflow = Workflow::default();
+
+        let json = serde_json::to_string(&WorkflowConfig::from(&workflow)).unwrap();
+
+        assert_eq!(Workflow::from_json(&json).unwrap(), workflow);
+    }
+}
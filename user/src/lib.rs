@@ -8,7 +8,9 @@ impl UserId {
     pub fn is_same(&self, other: &str) -> bool {
         self.0 == other
     }
-    pub fn to_string(&self) -> String {
-        self.0.clone()
+}
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }